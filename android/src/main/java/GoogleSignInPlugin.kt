@@ -22,6 +22,10 @@ import app.tauri.plugin.Plugin
 import com.google.android.gms.auth.api.identity.AuthorizationClient
 import com.google.android.gms.auth.api.identity.AuthorizationRequest
 import com.google.android.gms.auth.api.identity.Identity
+import com.google.android.gms.common.ConnectionResult
+import com.google.android.gms.common.GoogleApiAvailability
+import com.google.android.gms.common.api.ApiException
+import com.google.android.gms.common.api.CommonStatusCodes
 import com.google.android.gms.common.api.Scope
 import com.google.android.libraries.identity.googleid.GetGoogleIdOption
 import com.google.android.libraries.identity.googleid.GoogleIdTokenCredential
@@ -29,12 +33,16 @@ import com.google.gson.Gson
 import com.google.gson.reflect.TypeToken
 import kotlinx.coroutines.CoroutineScope
 import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.Job
 import kotlinx.coroutines.launch
 import kotlinx.coroutines.tasks.await
 import kotlinx.coroutines.withContext
 import okhttp3.FormBody
 import okhttp3.OkHttpClient
 import okhttp3.Request
+import java.io.IOException
+import java.net.SocketTimeoutException
+import java.util.concurrent.CancellationException
 import java.util.concurrent.TimeUnit
 
 @InvokeArg
@@ -46,6 +54,7 @@ class SignInArgs {
     var loginHint: String? = null
     var redirectUri: String? = ""
     var flowType: String? = null
+    var serverClientId: String? = null
 }
 
 @InvokeArg
@@ -79,6 +88,14 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         const val AUTH_CODE = "authCode"
         const val GRANTED_SCOPES = "grantedScopes"
         const val ERROR_MESSAGE = "errorMessage"
+
+        // Error code the Rust side maps to Error::PlayServicesUnavailable
+        const val PLAY_SERVICES_UNAVAILABLE = "PLAY_SERVICES_UNAVAILABLE"
+        // Error codes the Rust side maps to Error::NetworkError, Error::Timeout and,
+        // suffixed with the status, Error::HttpStatus
+        const val NETWORK_ERROR = "NETWORK_ERROR"
+        const val TIMEOUT = "TIMEOUT"
+        const val HTTP_STATUS_PREFIX = "HTTP_STATUS_"
         
         var RESULT_EXTRA_PREFIX = ""
     }
@@ -100,8 +117,20 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         credentialManager = CredentialManager.create(activity)
     }
     
+    // Both flows go through Google Play services; without them sign-in fails with a generic error
+    private fun rejectIfPlayServicesUnavailable(invoke: Invoke): Boolean {
+        val availability = GoogleApiAvailability.getInstance()
+        val status = availability.isGooglePlayServicesAvailable(activity)
+        if (status == ConnectionResult.SUCCESS) {
+            return false
+        }
+        invoke.reject(availability.getErrorString(status), PLAY_SERVICES_UNAVAILABLE)
+        return true
+    }
+
     @Command
     fun signIn(invoke: Invoke) {
+        if (rejectIfPlayServicesUnavailable(invoke)) return
         try {
             val args = invoke.parseArgs(SignInArgs::class.java)
 
@@ -125,6 +154,28 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         }
     }
 
+    // AuthorizationClient is incremental by itself, only asking consent for new scopes
+    @Command
+    fun requestAdditionalScopes(invoke: Invoke) {
+        if (rejectIfPlayServicesUnavailable(invoke)) return
+        try {
+            val args = invoke.parseArgs(SignInArgs::class.java)
+
+            if (args.flowType == "web") {
+                if (args.clientSecret == null) {
+                    invoke.reject("clientSecret is required for web flow")
+                    return
+                }
+                signInWeb(invoke, args)
+            } else {
+                startNativeAuthorization(invoke, currentIdToken ?: "", args.scopes, args.serverClientId)
+            }
+        } catch (e: Exception) {
+            Log.e(TAG, "Failed to request additional scopes", e)
+            invoke.reject("Failed to request additional scopes: ${e.message}")
+        }
+    }
+
     private fun signInWeb(invoke: Invoke, args: SignInArgs) {
         val intent = Intent(activity, GoogleSignInActivity::class.java).apply {
             putExtra(CLIENT_ID, args.clientId)
@@ -137,8 +188,10 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         startActivityForResult(invoke, intent, "signInResult")
     }
 
+    private var pendingSignInJob: Job? = null
+
     private fun signInNative(invoke: Invoke, args: SignInArgs) {
-        scope.launch {
+        pendingSignInJob = scope.launch {
             try {
                 // Step 1: Get ID token via CredentialManager (using main activity)
                 val googleIdOption = GetGoogleIdOption.Builder()
@@ -156,8 +209,11 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
                 val idToken = googleIdTokenCredential.idToken
 
                 // Step 2: Get access token via AuthorizationClient
-                startNativeAuthorization(invoke, idToken, args.scopes)
+                pendingSignInJob = null
+                startNativeAuthorization(invoke, idToken, args.scopes, args.serverClientId)
 
+            } catch (e: CancellationException) {
+                invoke.reject("User cancelled the sign-in flow")
             } catch (e: GetCredentialCancellationException) {
                 invoke.reject("Sign-in cancelled: ${e.message}")
             } catch (e: NoCredentialException) {
@@ -170,12 +226,29 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         }
     }
 
+    @Command
+    fun cancelSignIn(invoke: Invoke) {
+        // Only the account picker can be dismissed; consent screens belong to Google Play services
+        val job = pendingSignInJob
+        pendingSignInJob = null
+        val cancelled = job?.isActive == true
+        job?.cancel()
+
+        val ret = JSObject()
+        ret.put("cancelled", cancelled)
+        invoke.resolve(ret)
+    }
+
     private var pendingNativeInvoke: Invoke? = null
     private var pendingIdToken: String? = null
 
-    private fun startNativeAuthorization(invoke: Invoke, idToken: String, scopes: List<String>) {
+    private fun startNativeAuthorization(invoke: Invoke, idToken: String, scopes: List<String>, serverClientId: String?) {
         val authRequest = AuthorizationRequest.builder()
             .setRequestedScopes(scopes.map { Scope(it) })
+            .apply {
+                // Issues a one-time code for the backend to get its own refresh token
+                serverClientId?.let { requestOfflineAccess(it) }
+            }
             .build()
 
         authorizationClient.authorize(authRequest)
@@ -198,7 +271,7 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
                     val accessToken = authResult.accessToken
                     if (accessToken != null) {
                         val grantedScopes = authResult.grantedScopes.map { it.toString() }.toTypedArray()
-                        resolveNativeSignIn(invoke, idToken, accessToken, grantedScopes)
+                        resolveNativeSignIn(invoke, idToken, accessToken, grantedScopes, authResult.serverAuthCode)
                     } else {
                         invoke.reject("Failed to get access token")
                     }
@@ -209,15 +282,15 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
             }
     }
 
-    private fun resolveNativeSignIn(invoke: Invoke, idToken: String?, accessToken: String, grantedScopes: Array<String>) {
+    private fun resolveNativeSignIn(invoke: Invoke, idToken: String?, accessToken: String, grantedScopes: Array<String>, serverAuthCode: String?) {
+        currentIdToken = idToken
         val tokenObject = JSObject().apply {
             put("idToken", idToken ?: "")
             put("accessToken", accessToken)
-            put("refreshToken", "")
-            put("expiresAt", 0)
             put("scopes", JSArray().apply {
                 grantedScopes.forEach { put(it) }
             })
+            serverAuthCode?.let { put("serverAuthCode", it) }
         }
         invoke.resolve(tokenObject)
     }
@@ -242,13 +315,14 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
 
         val accessToken = data.getStringExtra(NativeSignInActivity.RESULT_ACCESS_TOKEN)
         val grantedScopes = data.getStringArrayExtra(NativeSignInActivity.RESULT_GRANTED_SCOPES)
+        val serverAuthCode = data.getStringExtra(NativeSignInActivity.RESULT_SERVER_AUTH_CODE)
 
         if (accessToken == null) {
             invoke.reject("No access token received")
             return
         }
 
-        resolveNativeSignIn(invoke, idToken, accessToken, grantedScopes ?: emptyArray())
+        resolveNativeSignIn(invoke, idToken, accessToken, grantedScopes ?: emptyArray(), serverAuthCode)
     }
     
     @ActivityCallback
@@ -304,6 +378,7 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
                 )
                 
                 val tokenObject = createTokenResponse(tokenResponse, grantedScopes?.toList())
+                currentIdToken = tokenResponse["id_token"] as? String
                 invoke.resolve(tokenObject)
             } catch (e: Exception) {
                 Log.e(TAG, "Failed to exchange auth code", e)
@@ -312,8 +387,19 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         }
     }
     
+    // ID token of the last sign-in, so getCurrentUser can tell who is signed in
+    private var currentIdToken: String? = null
+
+    @Command
+    fun getCurrentUser(invoke: Invoke) {
+        val ret = JSObject()
+        currentIdToken?.let { ret.put("idToken", it) }
+        invoke.resolve(ret)
+    }
+
     @Command
     fun signOut(invoke: Invoke) {
+        currentIdToken = null
         scope.launch {
             try {
                 val args = invoke.parseArgs(SignOutArgs::class.java)
@@ -397,11 +483,12 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
                         invoke.reject("scopes is required for native flow refresh")
                         return@launch
                     }
+                    if (rejectIfPlayServicesUnavailable(invoke)) return@launch
                     refreshNative(invoke, args)
                 }
             } catch (e: Exception) {
                 Log.e(TAG, "Failed to refresh token", e)
-                invoke.reject("Failed to refresh token: ${e.message}")
+                rejectWithCause(invoke, "Failed to refresh token", e)
             }
         }
     }
@@ -428,8 +515,6 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         val tokenObject = JSObject().apply {
             put("idToken", "")
             put("accessToken", accessToken)
-            put("refreshToken", "")
-            put("expiresAt", 0)
             put("scopes", JSArray().apply {
                 authResult.grantedScopes.forEach { put(it.toString()) }
             })
@@ -437,6 +522,58 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         invoke.resolve(tokenObject)
     }
     
+    @Command
+    fun silentSignIn(invoke: Invoke) {
+        scope.launch {
+            try {
+                val args = invoke.parseArgs(RefreshTokenArgs::class.java)
+
+                if (args.flowType == "web") {
+                    if (args.refreshToken == null || args.clientSecret == null) {
+                        // Without a stored refresh token there is no session to restore
+                        invoke.resolve(JSObject())
+                        return@launch
+                    }
+                    refreshWeb(invoke, args)
+                } else {
+                    if (args.scopes.isNullOrEmpty()) {
+                        invoke.reject("scopes is required for native flow silent sign-in")
+                        return@launch
+                    }
+                    if (rejectIfPlayServicesUnavailable(invoke)) return@launch
+                    silentSignInNative(invoke, args)
+                }
+            } catch (e: Exception) {
+                Log.e(TAG, "Silent sign-in failed", e)
+                rejectWithCause(invoke, "Silent sign-in failed", e)
+            }
+        }
+    }
+
+    private suspend fun silentSignInNative(invoke: Invoke, args: RefreshTokenArgs) {
+        val authRequest = AuthorizationRequest.builder()
+            .setRequestedScopes(args.scopes!!.map { Scope(it) })
+            .build()
+
+        val authResult = authorizationClient.authorize(authRequest).await()
+        val accessToken = authResult.accessToken
+
+        // A resolution means the user has to pick an account or consent first
+        if (authResult.hasResolution() || accessToken == null) {
+            invoke.resolve(JSObject())
+            return
+        }
+
+        val tokenObject = JSObject().apply {
+            put("idToken", "")
+            put("accessToken", accessToken)
+            put("scopes", JSArray().apply {
+                authResult.grantedScopes.forEach { put(it.toString()) }
+            })
+        }
+        invoke.resolve(tokenObject)
+    }
+
     private suspend fun exchangeAuthCodeForTokens(
         authCode: String,
         clientId: String,
@@ -474,6 +611,22 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         )
     }
     
+    /**
+     * Rejects with an error code for transient failures, so the Rust side can tell
+     * them apart from a revoked grant, e.g. to keep the current token for a while.
+     */
+    private fun rejectWithCause(invoke: Invoke, message: String, e: Exception) {
+        val code = when {
+            e is TokenRequestException -> "$HTTP_STATUS_PREFIX${e.status}"
+            e is SocketTimeoutException -> TIMEOUT
+            e is IOException -> NETWORK_ERROR
+            e is ApiException && e.statusCode == CommonStatusCodes.TIMEOUT -> TIMEOUT
+            e is ApiException && e.statusCode == CommonStatusCodes.NETWORK_ERROR -> NETWORK_ERROR
+            else -> null
+        }
+        invoke.reject("$message: ${e.message}", code)
+    }
+
     private suspend fun refreshAccessToken(
         refreshToken: String,
         clientId: String,
@@ -496,8 +649,7 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
         val response = httpClient.newCall(request).execute()
         
         if (!response.isSuccessful) {
-            val errorBody = response.body?.string()
-            throw Exception("Token refresh failed: $errorBody")
+            throw TokenRequestException(response.code, response.body?.string())
         }
         
         val responseBody = response.body?.string()
@@ -534,12 +686,15 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
     
     private fun createTokenResponse(tokenResponse: Map<String, Any?>, grantedScopes: List<String>? = null): JSObject {
         val expiresIn = (tokenResponse["expires_in"] as? Number)?.toLong() ?: 3600
-        val expiresAt = System.currentTimeMillis() + (expiresIn * 1000)
+        // Unix seconds, as the plugin expects
+        val expiresAt = System.currentTimeMillis() / 1000 + expiresIn
         
         return JSObject().apply {
             put("idToken", tokenResponse["id_token"] as? String ?: "")
             put("accessToken", tokenResponse["access_token"] as? String ?: "")
-            put("refreshToken", tokenResponse["refresh_token"] as? String ?: "")
+            (tokenResponse["refresh_token"] as? String)?.takeIf { it.isNotEmpty() }?.let {
+                put("refreshToken", it)
+            }
             put("expiresAt", expiresAt)
             
             // Include granted scopes if available, otherwise try to parse from the token response
@@ -549,4 +704,8 @@ class GoogleSignInPlugin(private val activity: Activity) : Plugin(activity) {
             })
         }
     }
-}
\ No newline at end of file
+}
+
+/** A non-success response of Google's token endpoint. */
+class TokenRequestException(val status: Int, val body: String?) :
+    Exception("Token request failed with status $status: $body")
@@ -1,4 +1,17 @@
-const COMMANDS: &[&str] = &["sign_in", "sign_out", "refresh_token"];
+const COMMANDS: &[&str] = &[
+    "sign_in",
+    "request_additional_scopes",
+    "cancel_sign_in",
+    "sign_in_device_code",
+    "sign_out",
+    "build_sign_out_request",
+    "refresh_token",
+    "silent_sign_in",
+    "get_current_user",
+    "check_connectivity",
+    "plugin_info",
+    "validate_scopes",
+];
 
 fn main() {
     tauri_plugin::Builder::new(COMMANDS)
@@ -1,19 +1,175 @@
 import { invoke } from "@tauri-apps/api/core";
 
+/**
+ * Event emitted after a desktop sign-in with the granted scopes (`string[]`) as payload
+ *
+ * @example
+ * ```typescript
+ * import { listen } from '@tauri-apps/api/event'
+ *
+ * await listen<string[]>(GRANTED_SCOPES_EVENT, (event) => {
+ *   console.log('Granted scopes:', event.payload)
+ * })
+ * ```
+ */
+export const GRANTED_SCOPES_EVENT = "google-auth://granted-scopes";
+
+/**
+ * Payload of {@link SIGN_IN_STARTED_EVENT}
+ */
+export interface SignInStarted {
+  /** ID to pass to cancelSignIn */
+  sessionId: string;
+  /** Correlation ID sent as X-Request-Id on requests to Google */
+  requestId: string;
+  /** URL opened in the browser */
+  authorizeUrl: string;
+}
+
+/**
+ * Event emitted when a desktop sign-in starts waiting for the browser redirect,
+ * with a {@link SignInStarted} payload
+ *
+ * @example
+ * ```typescript
+ * import { listen } from '@tauri-apps/api/event'
+ *
+ * await listen<SignInStarted>(SIGN_IN_STARTED_EVENT, (event) => {
+ *   cancelButton.onclick = () => cancelSignIn(event.payload.sessionId)
+ * })
+ * ```
+ */
+export const SIGN_IN_STARTED_EVENT = "google-auth://sign-in-started";
+
+/**
+ * Payload of {@link SIGN_IN_STAGE_EVENT}
+ */
+export interface SignInProgress {
+  /** ID to pass to cancelSignIn */
+  sessionId: string;
+  /** Correlation ID sent as X-Request-Id on requests to Google */
+  requestId: string;
+  stage:
+    | "openingBrowser"
+    | "awaitingCallback"
+    | "exchangingCode"
+    | "complete"
+    | "failed";
+  /** Why the sign-in failed, for the `failed` stage */
+  error?: string;
+}
+
+/**
+ * Event emitted whenever a desktop sign-in moves to another stage, with a
+ * {@link SignInProgress} payload
+ *
+ * @example
+ * ```typescript
+ * import { listen } from '@tauri-apps/api/event'
+ *
+ * await listen<SignInProgress>(SIGN_IN_STAGE_EVENT, (event) => {
+ *   statusLabel.textContent = event.payload.stage
+ * })
+ * ```
+ */
+export const SIGN_IN_STAGE_EVENT = "google-auth://stage";
+
+/**
+ * Payload of {@link REDIRECT_URI_NORMALIZED_EVENT}
+ */
+export interface RedirectUriNormalized {
+  /** redirectUri as given */
+  original: string;
+  /** Redirect URI actually sent to Google, which must match the registered one */
+  normalized: string;
+}
+
+/**
+ * Warning event emitted when a desktop redirectUri was sent to Google in a
+ * different form than given. The root path is always sent without a trailing
+ * slash, so register `http://localhost:1234` rather than `http://localhost:1234/`.
+ */
+export const REDIRECT_URI_NORMALIZED_EVENT =
+  "google-auth://redirect-uri-normalized";
+
 /**
  * Response containing authentication tokens from Google OAuth2
  */
 export interface TokenResponse {
   /** JWT ID token containing user information (requires 'openid' scope) */
   idToken?: string;
-  /** Access token for making API requests */
-  accessToken: string;
-  /** List of scopes granted with the access token */
+  /**
+   * Access token for making API requests. Omitted when a desktop sign-in with
+   * serverClientId hands its serverAuthCode to your backend, which gets no tokens to the app
+   */
+  accessToken?: string;
+  /**
+   * List of scopes granted with the access token. When Google's response omits
+   * them, as refresh responses may, these are the requested scopes
+   */
   scopes: string[];
   /** Refresh token for obtaining new access tokens (optional) */
   refreshToken?: string;
   /** Unix timestamp (seconds) when the access token expires */
   expiresAt?: number;
+  /** Same instant as expiresAt as an RFC 3339 string, e.g. for `new Date(expiresAtIso)` */
+  expiresAtIso?: string;
+  /**
+   * Lifetime in seconds as Google reported it. Unlike expiresAt it doesn't depend on
+   * the local clock, e.g. to recompute the expiry against a trusted one (desktop only)
+   */
+  expiresInSeconds?: number;
+  /** Whether a refresh token was obtained, i.e. the session can outlive the access token */
+  isOffline: boolean;
+  /** Query parameters of the loopback callback, minus `code` (desktop only, see returnCallbackParams) */
+  callbackParams?: Record<string, string>;
+  /** Non-fatal issues noticed during sign-in, e.g. "No refresh token returned" (omitted when there are none) */
+  warnings?: string[];
+  /** The user's profile from Google's userinfo endpoint (desktop only, see fetchProfile) */
+  profile?: UserProfile;
+  /** One-time code for your backend, when signing in with serverClientId */
+  serverAuthCode?: string;
+  /** The state the serverAuthCode's callback was validated with, for your backend to check (desktop only) */
+  serverAuthState?: string;
+  /** PKCE verifier your backend must send when redeeming serverAuthCode, unless pkceMethod is 'none' (desktop only) */
+  serverCodeVerifier?: string;
+  /** The `sub` of the user's ID token, identifying the session in multi-account apps (omitted without an ID token) */
+  accountId?: string;
+  /**
+   * Google session cookies of the sign-in window (desktop only, see returnWebviewCookies).
+   * They grant access to the user's whole Google account; never log, sync or send them
+   */
+  webviewCookies?: WebviewCookie[];
+}
+
+/**
+ * A cookie of the "webview" sign-in window
+ */
+export interface WebviewCookie {
+  name: string;
+  value: string;
+  domain?: string;
+  path?: string;
+  secure: boolean;
+  httpOnly: boolean;
+  /** Unix timestamp (seconds) the cookie expires at, omitted for session cookies */
+  expiresAt?: number;
+}
+
+/**
+ * The signed-in user's profile, as returned by Google's userinfo endpoint
+ */
+export interface UserProfile {
+  /** Google's stable user ID */
+  sub: string;
+  email?: string;
+  emailVerified?: boolean;
+  name?: string;
+  givenName?: string;
+  familyName?: string;
+  /** URL of the profile picture */
+  picture?: string;
+  locale?: string;
 }
 
 /**
@@ -22,18 +178,170 @@ export interface TokenResponse {
 export interface SignInOptions {
   /** Google OAuth2 client ID from Google Cloud Console */
   clientId: string;
-  /** Google OAuth2 client secret (required for desktop platforms) */
+  /** Google OAuth2 client secret; optional for public "Desktop app" clients, which rely on PKCE */
   clientSecret?: string;
-  /** List of OAuth2 scopes to request (e.g., ['openid', 'email', 'profile']) */
+  /**
+   * List of OAuth2 scopes to request, defaults to ['openid', 'email', 'profile'] when omitted.
+   * Entries are trimmed and deduplicated with a warning; an empty one or one with a space or
+   * other invalid character fails with configuration_error. On desktop, openid is added
+   * when an ID token is required or verified
+   */
   scopes?: string[];
-  /** Restrict sign-in to a specific G Suite domain */
+  /** Scopes that must all be granted or the sign-in fails, e.g. when the user unticks them on the consent screen */
+  requiredScopes?: string[];
+  /**
+   * Restrict sign-in to a specific Workspace domain, or '*' for any. The sign-in fails
+   * with "Hosted domain mismatch" unless the ID token's hd claim matches
+   */
   hostedDomain?: string;
   /** Pre-fill the email field in the sign-in flow */
   loginHint?: string;
+  /** Have the token also cover the scopes granted before, as requestAdditionalScopes does (desktop only) */
+  includeGrantedScopes?: boolean;
   /** Custom redirect URI (defaults to localhost with random port on desktop) */
   redirectUri?: string;
   /** Custom HTML message shown after successful authentication (desktop only) */
   successHtmlResponse?: string;
+  /** Wrap a plain-text successHtmlResponse in a minimal UTF-8 HTML document (desktop only) */
+  wrapSuccessHtml?: boolean;
+  /**
+   * Redirect the browser to this http(s) URL after a successful sign-in, e.g. a branded
+   * "login complete" page, instead of showing the success HTML (desktop only)
+   */
+  successRedirectUrl?: string;
+  /** Return the loopback callback query parameters in the response, for debugging (desktop only) */
+  returnCallbackParams?: boolean;
+  /**
+   * Open the sign-in page in a private/incognito window when the default browser supports
+   * it, or an incognito "webview" window that keeps no cookies (desktop only)
+   */
+  privateWindow?: boolean;
+  /**
+   * Where to show the sign-in page: the default browser, or a window of the app. Both
+   * return to the loopback redirect URI, so "webview" needs the loopback listener too.
+   * Google blocks sign-ins from embedded webviews it detects, so "webview" may fail with
+   * disallowed_useragent (desktop only, default: "systemBrowser")
+   */
+  authMode?: "systemBrowser" | "webview";
+  /**
+   * Directory the "webview" window keeps its cookies in, apart from the app's windows, so
+   * an SSO session survives restarts. Ignored on macOS, which uses the app's default
+   * storage; see the README for the privacy trade-offs (desktop only)
+   */
+  webviewDataDirectory?: string;
+  /**
+   * Return the Google session cookies of the "webview" window in webviewCookies, e.g. to
+   * keep the user signed in to Google in other embedded views. They are credentials for
+   * the user's whole Google account; read the README's warning first (desktop only,
+   * default: false)
+   */
+  returnWebviewCookies?: boolean;
+  /** Attempts at binding a random loopback port before giving up, default 3 (desktop only) */
+  bindAttempts?: number;
+  /**
+   * Loopback address to listen on instead of 127.0.0.1 and ::1, e.g. '127.0.0.2'. Without a
+   * redirect URI, the browser is redirected to it (desktop only)
+   */
+  bindHost?: string;
+  /**
+   * Inclusive [start, end] range of ports to listen on instead of a random one; the first free
+   * port is used. Can't be combined with a redirect URI port (desktop only)
+   */
+  portRange?: [number, number];
+  /**
+   * Listen only on 127.0.0.1 ('v4') or ::1 ('v6') for a `localhost` redirect URI. By default
+   * both are used, as browsers may resolve localhost to either (desktop only)
+   */
+  ipVersion?: "v4" | "v6";
+  /** OAuth response type: 'code' (default) or 'code id_token' for the hybrid flow (desktop only) */
+  responseType?: "code" | "code id_token";
+  /**
+   * OAuth `prompt` parameter, e.g. 'none' for a silent check that fails with an
+   * "interaction required" error instead of showing UI, or 'select_account' for a
+   * "switch account" button. Values other than 'none' can be combined with spaces (desktop only)
+   */
+  prompt?: "none" | "consent" | "select_account" | "login" | "select_account consent";
+  /**
+   * Request offline access. On desktop this asks for a refresh token with
+   * `access_type=offline`; Google only returns one on consent, so this also sends
+   * `prompt=consent` unless `prompt` is set. The Android native flow returns a
+   * serverAuthCode instead, for serverClientId or else clientId. See the README's
+   * "Offline Access" section for every platform
+   */
+  offlineAccess?: boolean;
+  /** Reject the sign-in unless the ID token carries a verified email (recommended when matching accounts by email) */
+  requireVerifiedEmail?: boolean;
+  /** Clock skew in seconds tolerated when `verifyIdToken` checks the ID token's exp/iat/nbf (default: 60) */
+  idTokenLeewaySeconds?: number;
+  /**
+   * Verify the ID token's signature against Google's published keys, plus its issuer and
+   * audience (desktop only; rejected on mobile). Use when trusting the token for authorization
+   */
+  verifyIdToken?: boolean;
+  /**
+   * Audiences the verified ID token may be issued for, e.g. the client IDs of your web and
+   * installed clients (default: clientId; desktop only)
+   */
+  acceptedAudiences?: string[];
+  /** Fail when no ID token is returned instead of only adding a warning */
+  requireIdToken?: boolean;
+  /**
+   * Fetch the user's profile from Google's userinfo endpoint after sign-in and return it as
+   * `profile`. Requires the openid, profile and email scopes (desktop only)
+   */
+  fetchProfile?: boolean;
+  /**
+   * How the PKCE code challenge is derived: 's256' (default), 'plain' for test
+   * harnesses, or 'none' to disable PKCE. Disabling it is insecure, as an intercepted
+   * code can then be exchanged by anyone; only use it to debug (desktop only)
+   */
+  pkceMethod?: "s256" | "plain" | "none";
+  /**
+   * Nonce to bind the ID token to, e.g. derived from your own session. A random one is
+   * generated when omitted; the sign-in fails if the ID token's nonce differs (desktop only)
+   */
+  nonce?: string;
+  /** Seconds to wait for the user to finish in the browser before failing with a timeout (desktop only, default: no limit) */
+  timeoutSeconds?: number;
+  /** Correlation ID sent as X-Request-Id to Google and echoed in SIGN_IN_STARTED_EVENT, generated when omitted (desktop only) */
+  requestId?: string;
+  /**
+   * OAuth client type registered in the Google Cloud Console (desktop only). 'web' requires
+   * a redirectUri with a fixed port; 'limitedInput' clients can't use this flow and are
+   * rejected, use {@link signInDeviceCode} for them.
+   * Inferred when omitted.
+   */
+  clientType?: "desktop" | "web" | "limitedInput";
+  /**
+   * Headers added to the token requests, e.g. app version for a gateway in front of
+   * Google, which itself ignores unknown headers. Authorization, Cookie, Host and
+   * other request-critical headers are rejected (desktop only)
+   */
+  extraHeaders?: Record<string, string>;
+  /** Authorization endpoint instead of Google's, e.g. a mock server in tests (desktop only) */
+  authUrl?: string;
+  /** Token endpoint instead of Google's (desktop only) */
+  tokenUrl?: string;
+  /** Revocation endpoint instead of Google's (desktop only) */
+  revocationUrl?: string;
+  /** HTTPS proxy for requests to Google, e.g. `http://proxy.corp:3128` (desktop only) */
+  proxyUrl?: string;
+  /** Skip TLS certificate checks (e.g. for a TLS-intercepting proxy); never use in production (desktop only) */
+  dangerAcceptInvalidCerts?: boolean;
+  /** Seconds to wait for a connection to Google (desktop only, default: 10) */
+  connectTimeoutSeconds?: number;
+  /** Seconds each request to Google may take (desktop only, default: 30) */
+  requestTimeoutSeconds?: number;
+  /** Retries of a token request that failed in transit or with a 5xx (desktop only, default: 0) */
+  maxRetries?: number;
+  /** Delay before the first retry, doubled with every further one (desktop only, default: 500) */
+  retryBaseDelayMs?: number;
+  /**
+   * OAuth client ID of your backend, to get a one-time serverAuthCode it redeems for its
+   * own tokens (Android native flow and desktop). On desktop the code is handed over
+   * unexchanged, with its state and PKCE verifier, and requires a fixed redirectUri
+   */
+  serverClientId?: string;
   /** Authentication flow type (Android only, ignored on other platforms) */
   flowType?: "native" | "web";
 }
@@ -55,7 +363,7 @@ export interface SignInOptions {
  * console.log('Access token:', tokens.accessToken)
  * ```
  *
- * @throws {Error} If authentication fails or user cancels the flow
+ * @throws {PluginError} If authentication fails or user cancels the flow
  */
 export async function signIn(options: SignInOptions): Promise<TokenResponse> {
   const response = await invoke<TokenResponse>("plugin:google-auth|sign_in", {
@@ -64,21 +372,201 @@ export async function signIn(options: SignInOptions): Promise<TokenResponse> {
   return response;
 }
 
+/**
+ * Asks for more scopes on top of those already granted, e.g. Drive access once the
+ * user opens a Drive feature
+ *
+ * Re-runs the sign-in with the new scopes added to `options.scopes`; Google only asks
+ * consent for the new ones, and the returned token covers all of them.
+ *
+ * @param scopes - The scopes to add
+ * @param options - The sign-in options, as for {@link signIn}
+ * @returns Promise that resolves with tokens covering the old and new scopes
+ *
+ * @example
+ * ```typescript
+ * const tokens = await requestAdditionalScopes(
+ *   ['https://www.googleapis.com/auth/drive.file'],
+ *   { clientId: 'your-client-id' }
+ * )
+ * ```
+ */
+export async function requestAdditionalScopes(
+  scopes: string[],
+  options: SignInOptions,
+): Promise<TokenResponse> {
+  return await invoke<TokenResponse>(
+    "plugin:google-auth|request_additional_scopes",
+    {
+      scopes,
+      payload: options,
+    },
+  );
+}
+
+/**
+ * Cancels an in-progress sign-in, making its signIn call reject. On desktop it
+ * rejects with code `user_cancelled`; on mobile the native account picker is
+ * dismissed.
+ *
+ * @param sessionId - Session ID from the {@link SIGN_IN_STARTED_EVENT} payload
+ *   (required on desktop, ignored on mobile)
+ * @returns Whether a matching sign-in was in progress
+ */
+export async function cancelSignIn(sessionId?: string): Promise<boolean> {
+  return await invoke<boolean>("plugin:google-auth|cancel_sign_in", {
+    sessionId,
+  });
+}
+
+/**
+ * Payload of {@link DEVICE_CODE_EVENT}
+ */
+export interface DeviceCode {
+  /** Correlation ID sent as X-Request-Id on requests to Google */
+  requestId: string;
+  /** Code the user enters at verificationUrl */
+  userCode: string;
+  /** Page to open on another device, e.g. https://www.google.com/device */
+  verificationUrl: string;
+  /** Seconds until the code expires */
+  expiresIn: number;
+}
+
+/**
+ * Event emitted when a device sign-in has a code for the user to enter, with a
+ * {@link DeviceCode} payload
+ */
+export const DEVICE_CODE_EVENT = "google-auth://device-code";
+
+/**
+ * Options for signing in with the device authorization grant
+ */
+export interface DeviceSignInOptions {
+  /** OAuth client ID of a "TVs and Limited Input devices" client */
+  clientId: string;
+  /** OAuth client secret, which Google requires for these clients */
+  clientSecret?: string;
+  /** List of OAuth2 scopes to request, defaults to ['openid', 'email', 'profile'] when omitted; normalized like SignInOptions.scopes */
+  scopes?: string[];
+  /** Give up after this many seconds instead of when the code expires */
+  timeoutSeconds?: number;
+  /** Correlation ID sent as X-Request-Id on requests to Google, generated when omitted */
+  requestId?: string;
+  /** Device code endpoint instead of Google's, e.g. a mock server in tests */
+  deviceCodeUrl?: string;
+  /** Token endpoint polled instead of Google's */
+  tokenUrl?: string;
+  /** HTTPS proxy for requests to Google, e.g. `http://proxy.corp:3128` */
+  proxyUrl?: string;
+  /** Skip TLS certificate checks (e.g. for a TLS-intercepting proxy); never use in production */
+  dangerAcceptInvalidCerts?: boolean;
+  /** Seconds to wait for a connection to Google (default: 10) */
+  connectTimeoutSeconds?: number;
+  /** Seconds each request to Google may take (default: 30) */
+  requestTimeoutSeconds?: number;
+}
+
+/**
+ * Signs in with a code entered on another device, for desktops that can't open
+ * a browser, e.g. kiosks or SSH sessions. The code arrives as
+ * {@link DEVICE_CODE_EVENT}; the promise resolves once the user approves (desktop only)
+ *
+ * @example
+ * ```typescript
+ * import { listen } from '@tauri-apps/api/event'
+ *
+ * await listen<DeviceCode>(DEVICE_CODE_EVENT, (event) => {
+ *   showCode(event.payload.verificationUrl, event.payload.userCode)
+ * })
+ * const tokens = await signInDeviceCode({ clientId, clientSecret })
+ * ```
+ *
+ * @throws {PluginError} If the user denies the sign-in or the code expires
+ */
+export async function signInDeviceCode(
+  options: DeviceSignInOptions,
+): Promise<TokenResponse> {
+  return await invoke<TokenResponse>("plugin:google-auth|sign_in_device_code", {
+    payload: options,
+  });
+}
+
 /**
  * Options for signing out
  */
 export interface SignOutOptions {
+  /**
+   * Account to sign out of, see TokenResponse.accountId. Without tokens, those the
+   * token store holds for it are revoked (desktop only)
+   */
+  accountId?: string;
   /** Access token to revoke with Google (if not provided, performs local sign-out only) */
   accessToken?: string;
+  /** Refresh token to revoke with Google as well; alone when revokeGrant is set (desktop only) */
+  refreshToken?: string;
+  /** Revoke the refresh token to end the whole grant rather than only the access token (desktop only) */
+  revokeGrant?: boolean;
+  /**
+   * Fail with an `http_status` error, carrying Google's status and body, when a revocation
+   * is rejected. By default sign-out succeeds regardless (desktop only)
+   */
+  strict?: boolean;
+  /** Revocation endpoint instead of Google's, e.g. a mock server in tests (desktop only) */
+  revocationUrl?: string;
+  /** HTTPS proxy for requests to Google, e.g. `http://proxy.corp:3128` (desktop only) */
+  proxyUrl?: string;
+  /** Skip TLS certificate checks (e.g. for a TLS-intercepting proxy); never use in production (desktop only) */
+  dangerAcceptInvalidCerts?: boolean;
+  /** Seconds to wait for a connection to Google (desktop only, default: 10) */
+  connectTimeoutSeconds?: number;
+  /** Seconds each request to Google may take (desktop only, default: 30) */
+  requestTimeoutSeconds?: number;
   /** Authentication flow type (Android only, ignored on other platforms) */
   flowType?: "native" | "web";
 }
 
+/**
+ * Outcome of a sign-out
+ */
+export interface SignOutResponse {
+  success: boolean;
+  /** Whether Google confirmed revoking the access token (desktop only) */
+  accessTokenRevoked: boolean;
+  /** Whether Google confirmed revoking the refresh token (desktop only) */
+  refreshTokenRevoked: boolean;
+}
+
+/**
+ * Builds the sign-out options for a stored session
+ *
+ * Revokes the whole grant (via the refresh token) when the session has one, so the
+ * next sign-in asks for consent again; otherwise revokes the access token.
+ *
+ * @param session - Tokens returned by signIn or refreshToken
+ * @returns Options to pass straight to signOut
+ *
+ * @example
+ * ```typescript
+ * await signOut(await buildSignOutRequest(storedTokens))
+ * ```
+ */
+export async function buildSignOutRequest(
+  session: TokenResponse,
+): Promise<SignOutOptions> {
+  return await invoke<SignOutOptions>(
+    "plugin:google-auth|build_sign_out_request",
+    {
+      payload: session,
+    },
+  );
+}
+
 /**
  * Signs out the current user
  *
  * @param options - Optional configuration for sign-out
- * @returns Promise that resolves when sign-out is complete
+ * @returns Promise that resolves with the revoked tokens when sign-out is complete
  *
  * @example
  * ```typescript
@@ -89,8 +577,10 @@ export interface SignOutOptions {
  * await signOut()
  * ```
  */
-export async function signOut(options?: SignOutOptions): Promise<void> {
-  await invoke("plugin:google-auth|sign_out", {
+export async function signOut(
+  options?: SignOutOptions,
+): Promise<SignOutResponse> {
+  return await invoke<SignOutResponse>("plugin:google-auth|sign_out", {
     payload: options || {},
   });
 }
@@ -103,10 +593,36 @@ export interface RefreshTokenOptions {
   refreshToken?: string;
   /** Google OAuth2 client ID from Google Cloud Console */
   clientId: string;
-  /** Google OAuth2 client secret (required for desktop platforms) */
+  /** Google OAuth2 client secret; optional for public "Desktop app" clients, which rely on PKCE */
   clientSecret?: string;
-  /** List of OAuth2 scopes to request */
+  /**
+   * Scopes of the session being refreshed. Android requests them again; desktop
+   * reports them as granted when Google's response omits them
+   */
   scopes?: string[];
+  /** Seconds to wait for the token endpoint before failing (desktop only, default: 30) */
+  timeoutSeconds?: number;
+  /** Correlation ID sent as X-Request-Id to the token endpoint, generated when omitted (desktop only) */
+  requestId?: string;
+  /** Headers added to the refresh request; see {@link SignInOptions.extraHeaders} (desktop only) */
+  extraHeaders?: Record<string, string>;
+  /** Token endpoint instead of Google's, e.g. a mock server in tests (desktop only) */
+  tokenUrl?: string;
+  /** HTTPS proxy for requests to Google, e.g. `http://proxy.corp:3128` (desktop only) */
+  proxyUrl?: string;
+  /** Skip TLS certificate checks (e.g. for a TLS-intercepting proxy); never use in production (desktop only) */
+  dangerAcceptInvalidCerts?: boolean;
+  /** Seconds to wait for a connection to Google (desktop only, default: 10) */
+  connectTimeoutSeconds?: number;
+  /** Retries of a token request that failed in transit or with a 5xx (desktop only, default: 0) */
+  maxRetries?: number;
+  /** Delay before the first retry, doubled with every further one (desktop only, default: 500) */
+  retryBaseDelayMs?: number;
+  /**
+   * Account whose session silentSignIn restores from the token store when no
+   * refreshToken is given, see TokenResponse.accountId (desktop only)
+   */
+  accountId?: string;
   /** Authentication flow type (Android only, ignored on other platforms) */
   flowType?: "native" | "web";
 }
@@ -115,7 +631,9 @@ export interface RefreshTokenOptions {
  * Refreshes the access token using a refresh token
  *
  * @param options - Configuration for token refresh
- * @returns Promise that resolves with new authentication tokens
+ * @returns Promise that resolves with new authentication tokens. Its `refreshToken`
+ * is the rotated one when Google issues a new token, otherwise the one used; store
+ * it in place of the old one.
  *
  * @example
  * ```typescript
@@ -127,7 +645,7 @@ export interface RefreshTokenOptions {
  * console.log('New access token:', newTokens.accessToken)
  * ```
  *
- * @throws {Error} If refresh token is invalid or expired
+ * @throws {PluginError} If refresh token is invalid or expired
  */
 export async function refreshToken(
   options: RefreshTokenOptions,
@@ -140,3 +658,147 @@ export async function refreshToken(
   );
   return response;
 }
+
+/**
+ * Restores a previous session without showing any UI
+ *
+ * Desktop, iOS and the Android web flow refresh the stored `refreshToken`; the Android
+ * native flow gets tokens for `scopes` the user granted before.
+ *
+ * @param options - The stored session to restore
+ * @returns Promise that resolves with the restored authentication tokens
+ *
+ * @throws {PluginError} With code `no_user_signed_in` if there is no session to restore
+ */
+export async function silentSignIn(
+  options: RefreshTokenOptions,
+): Promise<TokenResponse> {
+  const response = await invoke<TokenResponse>(
+    "plugin:google-auth|silent_sign_in",
+    {
+      payload: options,
+    },
+  );
+  return response;
+}
+
+/**
+ * Returns the signed-in user without signing in again
+ *
+ * Read from the ID token of the last sign-in since the app started, or the fetched
+ * profile on desktop, so the user is only known with the `openid` scope.
+ *
+ * @returns Promise that resolves with the user's profile, or null when signed out
+ */
+export async function getCurrentUser(): Promise<UserProfile | null> {
+  return await invoke<UserProfile | null>("plugin:google-auth|get_current_user");
+}
+
+/**
+ * Network settings for {@link checkConnectivity}; pass those of your sign-in options
+ */
+export interface ConnectivityCheckOptions {
+  /** HTTPS proxy for the check (desktop only) */
+  proxyUrl?: string;
+  /** Skip TLS certificate checks; never use in production (desktop only) */
+  dangerAcceptInvalidCerts?: boolean;
+  /** Seconds to wait for a connection to Google (desktop only, default: 10) */
+  connectTimeoutSeconds?: number;
+  /** Seconds the check may take (default: 10) */
+  requestTimeoutSeconds?: number;
+}
+
+/**
+ * Checks that Google's OAuth endpoints are reachable
+ *
+ * Useful on a setup screen to distinguish "can't reach Google" from bad credentials
+ * before attempting a sign-in. Pass the proxy and timeouts your sign-ins use, so the
+ * check goes the same way.
+ *
+ * @throws {PluginError} With code `network_error` if Google can't be reached
+ */
+export async function checkConnectivity(
+  options?: ConnectivityCheckOptions,
+): Promise<void> {
+  await invoke("plugin:google-auth|check_connectivity", { payload: options });
+}
+
+/**
+ * Stable identifier of a plugin error's kind
+ */
+export type ErrorCode =
+  | "io"
+  | "plugin_invoke"
+  | "authentication_failed"
+  | "user_cancelled"
+  | "timeout"
+  | "interaction_required"
+  | "no_user_signed_in"
+  | "invalid_client_id"
+  | "token_refresh_failed"
+  | "invalid_grant"
+  | "network_error"
+  | "play_services_unavailable"
+  | "http_status"
+  | "configuration_error";
+
+/**
+ * What the plugin's functions reject with
+ *
+ * @example
+ * ```typescript
+ * try {
+ *   await signIn({ clientId: 'your-client-id' })
+ * } catch (e) {
+ *   const error = e as PluginError
+ *   if (error.code !== 'user_cancelled') {
+ *     console.error(error.message)
+ *   }
+ * }
+ * ```
+ */
+export interface PluginError {
+  code: ErrorCode;
+  /** Human-readable description, e.g. "User cancelled the sign-in flow" */
+  message: string;
+}
+
+/**
+ * Build details of the plugin, for diagnostics
+ */
+export interface PluginInfo {
+  /** Crate version, e.g. "0.6.0" */
+  version: string;
+  /** Target OS, e.g. "macos", "windows", "linux", "android", "ios" */
+  platform: string;
+  /** Enabled Cargo features */
+  features: string[];
+}
+
+/**
+ * Returns the plugin version, platform and enabled features, to include in bug reports
+ */
+export async function pluginInfo(): Promise<PluginInfo> {
+  return await invoke<PluginInfo>("plugin:google-auth|plugin_info");
+}
+
+/**
+ * A scope flagged by {@link validateScopes}
+ */
+export interface ScopeWarning {
+  scope: string;
+  /** 'unknown': not in the bundled list, possibly a typo. 'restricted': needs Google's verification */
+  kind: "unknown" | "restricted";
+}
+
+/**
+ * Checks scopes against a bundled list of Google scopes, as a setup aid. Unlisted
+ * scopes are only warned about, so new or API-specific scopes still work.
+ *
+ * @param scopes - Scopes as passed to signIn
+ */
+export async function validateScopes(scopes: string[]): Promise<ScopeWarning[]> {
+  return await invoke<ScopeWarning[]>("plugin:google-auth|validate_scopes", {
+    scopes,
+  });
+}
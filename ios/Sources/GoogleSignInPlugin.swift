@@ -18,7 +18,15 @@ class RefreshTokenArgs: Decodable {
     let refreshToken: String
 }
 
+class SilentSignInArgs: Decodable {
+    let refreshToken: String?
+}
+
 class GoogleSignInPlugin: Plugin {
+    private var pendingSignIn: Invoke?
+    // ID token of the last sign-in, so getCurrentUser can tell who is signed in
+    private var currentIdToken: String?
+
     @objc public func signIn(_ invoke: Invoke) throws {
         let args = try invoke.parseArgs(SignInArgs.self)
 
@@ -39,23 +47,32 @@ class GoogleSignInPlugin: Plugin {
 
             SimpleGoogleSignIn.shared.configure(configuration: configuration)
 
+            self?.pendingSignIn = invoke
             SimpleGoogleSignIn.shared.signIn(
                 presentingViewController: rootViewController, scopes: scopes
-            ) { result in
+            ) { [weak self] result in
                 DispatchQueue.main.async {
+                    // A cancelled sign-in has already been rejected
+                    guard self?.pendingSignIn === invoke else { return }
+                    self?.pendingSignIn = nil
+
                     switch result {
                     case .success(let signInResult):
                         var tokenDict: [String: Any] = [
                             "idToken": signInResult.openIdToken,
                             "accessToken": signInResult.accessToken.tokenString,
-                            "refreshToken": signInResult.refreshToken ?? "",
                             "scopes": signInResult.grantedScopes ?? []
                         ]
 
+                         // Unix seconds, as the plugin expects
                          if let expirationDate = signInResult.accessToken.expirationDate {
-                             tokenDict["expiresAt"] = Int64(expirationDate.timeIntervalSince1970 * 1000)
+                             tokenDict["expiresAt"] = Int64(expirationDate.timeIntervalSince1970)
+                         }
+                         if let refreshToken = signInResult.refreshToken, !refreshToken.isEmpty {
+                             tokenDict["refreshToken"] = refreshToken
                          }
 
+                        self?.currentIdToken = signInResult.openIdToken
                         invoke.resolve(tokenDict)
 
                     case .failure(let error):
@@ -66,10 +83,40 @@ class GoogleSignInPlugin: Plugin {
         }
     }
 
+    // The sign-in screen only asks consent for the scopes not granted yet
+    @objc public func requestAdditionalScopes(_ invoke: Invoke) throws {
+        try signIn(invoke)
+    }
+
+    @objc public func cancelSignIn(_ invoke: Invoke) throws {
+        DispatchQueue.main.async { [weak self] in
+            guard let pending = self?.pendingSignIn else {
+                invoke.resolve(["cancelled": false])
+                return
+            }
+
+            self?.pendingSignIn = nil
+            self?.manager.viewController?.presentedViewController?.dismiss(animated: true)
+            pending.reject("User cancelled the sign-in flow")
+            invoke.resolve(["cancelled": true])
+        }
+    }
+
+    @objc public func getCurrentUser(_ invoke: Invoke) throws {
+        DispatchQueue.main.async { [weak self] in
+            guard let idToken = self?.currentIdToken else {
+                invoke.resolve([:])
+                return
+            }
+            invoke.resolve(["idToken": idToken])
+        }
+    }
+
     @objc public func signOut(_ invoke: Invoke) throws {
         let args = try invoke.parseArgs(SignOutArgs.self)
 
-        DispatchQueue.main.async {
+        DispatchQueue.main.async { [weak self] in
+            self?.currentIdToken = nil
             SimpleGoogleSignIn.shared.signOut(accessToken: args.accessToken) { _ in
                 invoke.resolve(["success": true])
             }
@@ -86,23 +133,81 @@ class GoogleSignInPlugin: Plugin {
                      var tokenDict: [String: Any] = [
                          "idToken": signInResult.openIdToken,
                          "accessToken": signInResult.accessToken.tokenString,
-                         "refreshToken": signInResult.refreshToken ?? "",
                          "scopes": signInResult.grantedScopes ?? []
                      ]
 
+                      // Unix seconds, as the plugin expects
                       if let expirationDate = signInResult.accessToken.expirationDate {
-                          tokenDict["expiresAt"] = Int64(expirationDate.timeIntervalSince1970 * 1000)
+                          tokenDict["expiresAt"] = Int64(expirationDate.timeIntervalSince1970)
+                      }
+                      if let refreshToken = signInResult.refreshToken, !refreshToken.isEmpty {
+                          tokenDict["refreshToken"] = refreshToken
                       }
 
                      invoke.resolve(tokenDict)
 
                  case .failure(let error):
-                     invoke.reject(error.localizedDescription)
+                     invoke.reject(error.localizedDescription, code: self.transientErrorCode(error))
                  }
              }
          }
     }
 
+    @objc public func silentSignIn(_ invoke: Invoke) throws {
+        let args = try invoke.parseArgs(SilentSignInArgs.self)
+
+        // Sessions are only restored from a stored refresh token; without one there is none
+        guard let refreshToken = args.refreshToken, !refreshToken.isEmpty else {
+            invoke.resolve([:])
+            return
+        }
+
+        DispatchQueue.main.async {
+            SimpleGoogleSignIn.shared.refreshTokens(refreshToken: refreshToken) { result in
+                switch result {
+                case .success(let signInResult):
+                    var tokenDict: [String: Any] = [
+                        "idToken": signInResult.openIdToken,
+                        "accessToken": signInResult.accessToken.tokenString,
+                        "scopes": signInResult.grantedScopes ?? []
+                    ]
+
+                    // Unix seconds, as the plugin expects
+                    if let expirationDate = signInResult.accessToken.expirationDate {
+                        tokenDict["expiresAt"] = Int64(expirationDate.timeIntervalSince1970)
+                    }
+                    if let refreshToken = signInResult.refreshToken, !refreshToken.isEmpty {
+                        tokenDict["refreshToken"] = refreshToken
+                    }
+
+                    invoke.resolve(tokenDict)
+
+                case .failure(let error):
+                    invoke.reject(error.localizedDescription, code: self.transientErrorCode(error))
+                }
+            }
+        }
+    }
+
+    /// Error code the Rust side maps to `Error::Timeout` or `Error::NetworkError`,
+    /// so transient refresh failures can be told apart from a revoked grant.
+    private func transientErrorCode(_ error: Error) -> String? {
+        let nsError = error as NSError
+        guard nsError.domain == NSURLErrorDomain else {
+            return nil
+        }
+        switch nsError.code {
+        case NSURLErrorTimedOut:
+            return "TIMEOUT"
+        case NSURLErrorNotConnectedToInternet, NSURLErrorNetworkConnectionLost,
+             NSURLErrorCannotConnectToHost, NSURLErrorCannotFindHost,
+             NSURLErrorDNSLookupFailed:
+            return "NETWORK_ERROR"
+        default:
+            return nil
+        }
+    }
+
     @objc public func handleUrl(_ url: URL) -> Bool {
         return SimpleGoogleSignIn.shared.handleURL(url)
     }
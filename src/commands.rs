@@ -1,17 +1,62 @@
-use tauri::{AppHandle, Runtime, command};
+use std::time::Instant;
+
+use tauri::{AppHandle, Manager, Runtime, command};
 
-use crate::GoogleAuthExt;
-use crate::Result;
 use crate::models::{
-    RefreshTokenRequest, SignInRequest, SignOutRequest, SignOutResponse, TokenResponse,
+    AuthEvent, AuthOperation, ConnectivityCheckRequest, DeviceSignInRequest, PluginInfo,
+    RefreshTokenRequest, ScopeWarning, SignInRequest, SignOutRequest, SignOutResponse,
+    TokenResponse, UserProfile,
 };
+use crate::{AuthEventHook, GoogleAuthExt, Result};
 
 #[command]
 pub async fn sign_in<R: Runtime>(
     app: AppHandle<R>,
     payload: SignInRequest,
 ) -> Result<TokenResponse> {
-    app.google_auth().sign_in(payload)
+    observe(
+        &app,
+        AuthOperation::SignIn,
+        app.google_auth().sign_in_async(payload),
+    )
+    .await
+}
+
+#[command]
+pub async fn request_additional_scopes<R: Runtime>(
+    app: AppHandle<R>,
+    scopes: Vec<String>,
+    payload: SignInRequest,
+) -> Result<TokenResponse> {
+    observe(
+        &app,
+        AuthOperation::SignIn,
+        app.google_auth().request_additional_scopes(scopes, payload),
+    )
+    .await
+}
+
+#[command]
+pub async fn cancel_sign_in<R: Runtime>(
+    app: AppHandle<R>,
+    session_id: Option<String>,
+) -> Result<bool> {
+    Ok(app
+        .google_auth()
+        .cancel_sign_in(session_id.as_deref().unwrap_or_default()))
+}
+
+#[command]
+pub async fn sign_in_device_code<R: Runtime>(
+    app: AppHandle<R>,
+    payload: DeviceSignInRequest,
+) -> Result<TokenResponse> {
+    observe(
+        &app,
+        AuthOperation::SignIn,
+        app.google_auth().sign_in_device_code(payload),
+    )
+    .await
 }
 
 #[command]
@@ -19,7 +64,15 @@ pub async fn sign_out<R: Runtime>(
     app: AppHandle<R>,
     payload: SignOutRequest,
 ) -> Result<SignOutResponse> {
-    app.google_auth().sign_out(payload)
+    observe(&app, AuthOperation::SignOut, async {
+        app.google_auth().sign_out(payload)
+    })
+    .await
+}
+
+#[command]
+pub async fn build_sign_out_request(payload: TokenResponse) -> Result<SignOutRequest> {
+    Ok(SignOutRequest::for_session(&payload))
 }
 
 #[command]
@@ -27,5 +80,67 @@ pub async fn refresh_token<R: Runtime>(
     app: AppHandle<R>,
     payload: RefreshTokenRequest,
 ) -> Result<TokenResponse> {
-    app.google_auth().refresh_token(payload)
+    observe(&app, AuthOperation::RefreshToken, async {
+        app.google_auth().refresh_token(payload)
+    })
+    .await
+}
+
+#[command]
+pub async fn silent_sign_in<R: Runtime>(
+    app: AppHandle<R>,
+    payload: RefreshTokenRequest,
+) -> Result<TokenResponse> {
+    observe(&app, AuthOperation::SignIn, async {
+        app.google_auth().silent_sign_in(payload)
+    })
+    .await
+}
+
+#[command]
+pub async fn get_current_user<R: Runtime>(app: AppHandle<R>) -> Result<Option<UserProfile>> {
+    app.google_auth().get_current_user()
+}
+
+#[command]
+pub async fn check_connectivity<R: Runtime>(
+    app: AppHandle<R>,
+    payload: Option<ConnectivityCheckRequest>,
+) -> Result<()> {
+    app.google_auth()
+        .check_connectivity(&payload.unwrap_or_default())
+}
+
+#[command]
+pub async fn plugin_info() -> Result<PluginInfo> {
+    Ok(PluginInfo::current())
+}
+
+#[command]
+pub async fn validate_scopes(scopes: Vec<String>) -> Result<Vec<ScopeWarning>> {
+    crate::validate_scopes(&scopes)
+}
+
+/// Runs `operation` and reports its outcome to the [`crate::Builder::on_auth_event`]
+/// hook, if one is registered, without waiting for it.
+async fn observe<R: Runtime, T>(
+    app: &AppHandle<R>,
+    operation: AuthOperation,
+    run: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let started = Instant::now();
+    let result = run.await;
+
+    if let Some(hook) = app.try_state::<AuthEventHook>() {
+        let hook = hook.0.clone();
+        let event = AuthEvent {
+            operation,
+            success: result.is_ok(),
+            duration: started.elapsed(),
+            error_code: result.as_ref().err().map(crate::Error::code),
+        };
+        tauri::async_runtime::spawn_blocking(move || hook(&event));
+    }
+
+    result
 }
@@ -1,22 +1,49 @@
 use oauth2::basic::{
-    BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
-    BasicTokenType,
+    BasicErrorResponse, BasicErrorResponseType, BasicRevocationErrorResponse,
+    BasicTokenIntrospectionResponse, BasicTokenType,
 };
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Runtime, plugin::PluginApi};
+use tauri::{AppHandle, Emitter, Runtime, plugin::PluginApi};
 
+use oauth2::reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use oauth2::{
-    AuthUrl, AuthorizationCode, Client, ClientId, ClientSecret, CsrfToken, EndpointNotSet,
-    ExtraTokenFields, PkceCodeChallenge, RedirectUrl, RevocationUrl, Scope, StandardRevocableToken,
-    StandardTokenResponse, TokenResponse, TokenUrl,
+    AsyncHttpClient, Client, ClientId, ClientSecret, DeviceAuthorizationUrl,
+    DeviceCodeErrorResponse, DeviceCodeErrorResponseType, EndpointNotSet, EndpointSet,
+    EndpointState, ExtraTokenFields, HttpClientError, HttpRequest, HttpResponse, RequestTokenError,
+    Scope, StandardDeviceAuthorizationResponse, StandardRevocableToken, StandardTokenResponse,
+    SyncHttpClient, TokenResponse, TokenUrl,
 };
 use url::Url;
+use uuid::Uuid;
 
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpListener;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
-use crate::models::{RefreshTokenRequest, SignInRequest, SignOutRequest, SignOutResponse};
+mod browser;
+mod flow;
+mod jwks;
+#[cfg(test)]
+mod test_support;
+mod transport;
+mod webview;
+
+pub use flow::{SignInCancelHandle, SignInFlow, SignInState};
+
+use transport::{CallbackTransport, TcpTransport};
+
+use crate::models::{
+    ConnectivityCheckRequest, DeviceCode, DeviceSignInRequest, IdTokenClaims, NetworkSettings,
+    RefreshTokenRequest, SignInRequest, SignOutRequest, SignOutResponse, TokenIntrospection,
+    UserProfile,
+};
+use crate::success_page::{SuccessPage, SuccessPageRenderer};
+use crate::token_store::TokenStore;
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct GoogleTokenFields {
@@ -43,358 +70,3053 @@ type SpecialClient<
     HasRevocationUrl,
     HasTokenUrl,
 >;
+/// Cancellation flags of the in-progress sign-ins, keyed by session ID.
+type Sessions = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+type TokenRequestError =
+    RequestTokenError<HttpClientError<oauth2::reqwest::Error>, BasicErrorResponse>;
+type HttpError = HttpClientError<oauth2::reqwest::Error>;
+/// A refresh result, with the status and body of a non-success response on failure.
+type RefreshResult = Result<SpecialTokenResponse, (TokenRequestError, Option<(u16, String)>)>;
+
+/// Wraps an HTTP client to retry transient failures per `retry` and record the
+/// last non-success response, since `oauth2` errors keep its body but not its status.
+struct RecordingClient<'a, C> {
+    inner: &'a C,
+    retry: RetryPolicy,
+    failed: Mutex<Option<(u16, String)>>,
+}
+
+impl<'a, C> RecordingClient<'a, C> {
+    const fn new(inner: &'a C, retry: RetryPolicy) -> Self {
+        Self {
+            inner,
+            retry,
+            failed: Mutex::new(None),
+        }
+    }
+
+    fn record(&self, response: Result<HttpResponse, HttpError>) -> Result<HttpResponse, HttpError> {
+        if let Ok(response) = &response
+            && !response.status().is_success()
+        {
+            *self.failed.lock().unwrap_or_else(PoisonError::into_inner) = Some((
+                response.status().as_u16(),
+                String::from_utf8_lossy(response.body()).into_owned(),
+            ));
+        }
+        response
+    }
+
+    /// The status and body of the last non-success response.
+    fn take_failed(&self) -> Option<(u16, String)> {
+        self.failed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
+
+impl SyncHttpClient for RecordingClient<'_, oauth2::reqwest::blocking::Client> {
+    type Error = HttpError;
+
+    fn call(&self, request: HttpRequest) -> Result<HttpResponse, HttpError> {
+        let mut attempt = 0;
+        loop {
+            let response = self.record(self.inner.call(copy_request(&request)));
+            if attempt >= self.retry.max_retries || !is_transient(&response) {
+                return response;
+            }
+            std::thread::sleep(self.retry.delay(attempt));
+            attempt += 1;
+        }
+    }
+}
+
+impl<'c> AsyncHttpClient<'c> for RecordingClient<'_, oauth2::reqwest::Client> {
+    type Error = HttpError;
+    type Future = Pin<Box<dyn Future<Output = Result<HttpResponse, HttpError>> + Send + 'c>>;
+
+    fn call(&'c self, request: HttpRequest) -> Self::Future {
+        Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                let response = self.record(self.inner.call(copy_request(&request)).await);
+                if attempt >= self.retry.max_retries || !is_transient(&response) {
+                    return response;
+                }
+                tokio::time::sleep(self.retry.delay(attempt)).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+/// How often to retry a token request that failed in transit or with a 5xx.
+/// Other responses, such as a rejected code, are final and never retried.
+#[derive(Clone, Copy, Default)]
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Doubles `base_delay` with every attempt and adds up to half of that again
+    /// at random, so clients that failed together don't retry together.
+    fn delay(&self, attempt: u32) -> Duration {
+        use std::hash::{BuildHasher, Hasher};
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt));
+        let half = u64::try_from(backoff.as_millis() / 2).unwrap_or(u64::MAX);
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        backoff.saturating_add(Duration::from_millis(random % half.saturating_add(1)))
+    }
+}
+
+fn is_transient(response: &Result<HttpResponse, HttpError>) -> bool {
+    match response {
+        Ok(response) => response.status().is_server_error(),
+        Err(HttpClientError::Reqwest(_)) => true,
+        Err(_) => false,
+    }
+}
+
+/// Copies a request for another attempt; `http::Request` isn't `Clone`.
+fn copy_request(request: &HttpRequest) -> HttpRequest {
+    let mut copy = HttpRequest::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
 
 // Google OAuth2 URL constants
 const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/auth";
 const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
 const GOOGLE_REVOCATION_URL: &str = "https://oauth2.googleapis.com/revoke";
+const GOOGLE_DISCOVERY_URL: &str = "https://accounts.google.com/.well-known/openid-configuration";
+const GOOGLE_CERTS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+const GOOGLE_USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v3/userinfo";
+const GOOGLE_TOKENINFO_URL: &str = "https://oauth2.googleapis.com/tokeninfo";
+const GOOGLE_DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
 const LOCALHOST_ADDR: &str = "127.0.0.1";
+const LOCALHOST_V6_HOST: &str = "[::1]";
 const DEFAULT_REDIRECT_HOST: &str = "localhost";
-const SUCCESS_HTML_RESPONSE: &str = "Go back to your app :)";
+const DEFAULT_BIND_ATTEMPTS: u32 = 3;
+const FRAGMENT_RELAY_HTML: &str = "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body>\
+<script>var f = location.hash.slice(1); if (f) { location.replace('/?' + f); } \
+else { document.body.textContent = 'No authorization response received.'; }</script>\
+</body></html>";
+const GRANTED_SCOPES_EVENT: &str = "google-auth://granted-scopes";
+const SIGN_IN_STARTED_EVENT: &str = "google-auth://sign-in-started";
+const SIGN_IN_STAGE_EVENT: &str = "google-auth://stage";
+const DEVICE_CODE_EVENT: &str = "google-auth://device-code";
+const REDIRECT_URI_NORMALIZED_EVENT: &str = "google-auth://redirect-uri-normalized";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Headers that `extra_headers` must not set, as they carry credentials, describe
+/// the request body, or are set by the plugin itself.
+const PROTECTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "host",
+    "content-type",
+    "content-length",
+    "transfer-encoding",
+    REQUEST_ID_HEADER,
+];
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Callbacks carry everything in the URL; larger bodies are left unread.
+const MAX_REQUEST_BODY: u64 = 64 * 1024;
+/// Bounds the request line and headers, far above what a browser sends.
+const MAX_REQUEST_HEAD: u64 = 32 * 1024;
+const SIGN_IN_FAILED_RESPONSE: &str = "Sign-in failed. Go back to your app for details.";
+const SIGN_IN_ALREADY_COMPLETED_RESPONSE: &str = "Sign-in already completed. Go back to your app.";
 
 #[allow(clippy::unnecessary_wraps)]
 pub fn init<R: Runtime, C: DeserializeOwned>(
     app: &AppHandle<R>,
     _api: PluginApi<R, C>,
+    success_page_renderer: Option<Box<dyn SuccessPageRenderer>>,
+    state_codec: Option<StateCodec>,
+    port_fallback: Option<Box<PortFallback>>,
+    token_store: Option<Box<dyn TokenStore>>,
 ) -> crate::Result<GoogleAuth<R>> {
-    Ok(GoogleAuth(app.clone()))
+    Ok(GoogleAuth {
+        app: app.clone(),
+        success_page_renderer: success_page_renderer.map(Arc::from),
+        state_codec: state_codec.map(Arc::new),
+        port_fallback: port_fallback.map(Arc::from),
+        id_token_keys: Arc::default(),
+        sessions: Sessions::default(),
+        accounts: Arc::new(Accounts {
+            current: Mutex::default(),
+            store: token_store,
+        }),
+    })
 }
 
 /// Access to the google-auth APIs.
-pub struct GoogleAuth<R: Runtime>(AppHandle<R>);
+pub struct GoogleAuth<R: Runtime> {
+    app: AppHandle<R>,
+    success_page_renderer: Option<Arc<dyn SuccessPageRenderer>>,
+    state_codec: Option<Arc<StateCodec>>,
+    port_fallback: Option<Arc<PortFallback>>,
+    id_token_keys: Arc<jwks::KeyCache>,
+    sessions: Sessions,
+    accounts: Arc<Accounts>,
+}
+
+/// Who is signed in, and where their sessions are persisted, if anywhere.
+struct Accounts {
+    current: Mutex<Option<UserProfile>>,
+    store: Option<Box<dyn TokenStore>>,
+}
+
+impl Accounts {
+    /// Remembers who `response` signed in, for [`GoogleAuth::get_current_user`], and
+    /// saves the session to the token store. A failed save is reported as a warning,
+    /// as the tokens themselves are fine.
+    fn remember(&self, response: &mut crate::TokenResponse) {
+        let Some(profile) = user_of(response) else {
+            return;
+        };
+        response
+            .account_id
+            .get_or_insert_with(|| profile.sub.clone());
+        if let Some(store) = &self.store
+            && let Err(e) = store.save(&profile.sub, response)
+        {
+            response
+                .warnings
+                .push(format!("Failed to save the session: {e}"));
+        }
+        *self.current.lock().unwrap_or_else(PoisonError::into_inner) = Some(profile);
+    }
+
+    /// Loads the stored session of `account_id`, or `None` without a token store.
+    fn load(&self, account_id: &str) -> crate::Result<Option<crate::TokenResponse>> {
+        self.store
+            .as_ref()
+            .map_or(Ok(None), |store| store.load(account_id))
+    }
+
+    /// Forgets the account `account_id`, or else the signed-in user, deleting their
+    /// stored session. The signed-in user stays when another account is forgotten.
+    fn forget(&self, account_id: Option<&str>) -> crate::Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        let signed_out = match account_id {
+            Some(account_id) => {
+                if current.as_ref().is_some_and(|user| user.sub == account_id) {
+                    *current = None;
+                }
+                Some(account_id.to_string())
+            }
+            None => current.take().map(|user| user.sub),
+        };
+        drop(current);
+        match (&self.store, signed_out) {
+            (Some(store), Some(account_id)) => store.delete(&account_id),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Called with the preferred and the chosen port when a sign-in falls back to a
+/// random port.
+pub type PortFallback = dyn Fn(u16, u16) + Send + Sync;
+
+/// Custom generation and validation of the OAuth `state` parameter.
+pub struct StateCodec {
+    pub provider: Box<dyn Fn() -> String + Send + Sync>,
+    pub validator: Box<dyn Fn(&str) -> bool + Send + Sync>,
+}
 
 impl<R: Runtime> GoogleAuth<R> {
-    #[allow(clippy::unused_self, clippy::too_many_lines)]
     pub fn sign_in(&self, payload: SignInRequest) -> crate::Result<crate::TokenResponse> {
-        // Validate that scopes are provided
-        let scopes = payload.scopes.ok_or_else(|| {
-            crate::Error::ConfigurationError(
-                "No scopes provided. At least one scope is required for authentication".to_string(),
-            )
-        })?;
+        self.sign_in_flow(payload)?.run()
+    }
+
+    /// Like [`sign_in`](Self::sign_in), but awaits the callback and the token exchange
+    /// instead of blocking the calling thread for the whole sign-in.
+    pub async fn sign_in_async(
+        &self,
+        payload: SignInRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        self.sign_in_flow(payload)?.run_async().await
+    }
+
+    /// Asks for `scopes` on top of those already granted, e.g. Drive access once the
+    /// user opens a Drive feature.
+    ///
+    /// Re-runs the sign-in of `payload` with the new scopes and
+    /// `include_granted_scopes=true`, so Google only asks consent for what is new and
+    /// the returned token covers the scopes granted before as well.
+    pub async fn request_additional_scopes(
+        &self,
+        scopes: Vec<String>,
+        payload: SignInRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        self.sign_in_async(payload.with_additional_scopes(scopes))
+            .await
+    }
+
+    /// Creates a [`SignInFlow`] for `payload` that can be driven step by step,
+    /// e.g. to show the authorization URL in the app instead of opening a browser.
+    pub fn sign_in_flow(&self, payload: SignInRequest) -> crate::Result<SignInFlow<R>> {
+        SignInFlow::new(self, payload)
+    }
 
+    /// Signs in with the device authorization grant, for machines that can't open
+    /// a browser, e.g. kiosks or SSH sessions. Emits the device code event with the
+    /// code to enter on another device, then polls Google at the interval it asks for
+    /// until the user approves or denies the sign-in or the code expires.
+    pub async fn sign_in_device_code(
+        &self,
+        payload: DeviceSignInRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        let scopes = payload.scopes.unwrap_or_else(SignInRequest::default_scopes);
         if scopes.is_empty() {
-            return Err(crate::Error::ConfigurationError(
-                "Empty scopes array. At least one scope is required for authentication".to_string(),
+            return Err(crate::Error::configuration(
+                Some("scopes"),
+                "Empty scopes array. At least one scope is required for authentication",
             ));
         }
+        let (scopes, warnings) = crate::scopes::normalize_scopes(scopes)?;
+        let request_id = payload
+            .request_id
+            .unwrap_or_else(|| Uuid::now_v7().to_string());
 
-        // Parse redirect URI and extract port if provided
-        let (redirect_host, port) = if let Some(redirect_uri) = &payload.redirect_uri {
-            let parsed_url = Url::parse(redirect_uri).map_err(|e| {
-                crate::Error::ConfigurationError(format!("Invalid redirect URI: {e}"))
-            })?;
+        let device_url = DeviceAuthorizationUrl::new(
+            payload
+                .device_code_url
+                .unwrap_or_else(|| GOOGLE_DEVICE_CODE_URL.to_string()),
+        )
+        .map_err(|_| {
+            crate::Error::configuration(Some("device_code_url"), "Invalid device code endpoint URL")
+        })?;
+        let token_url = TokenUrl::new(
+            payload
+                .token_url
+                .unwrap_or_else(|| GOOGLE_TOKEN_URL.to_string()),
+        )
+        .map_err(|_| {
+            crate::Error::configuration(Some("token_url"), "Invalid token endpoint URL")
+        })?;
+        let client = with_client_secret(
+            SpecialClient::new(ClientId::new(payload.client_id)),
+            payload.client_secret,
+        )
+        .set_device_authorization_url(device_url)
+        .set_token_uri(token_url);
 
-            let host = parsed_url.host_str().ok_or_else(|| {
-                crate::Error::ConfigurationError("Redirect URI must have a host".to_string())
+        let http = HttpSettings::new(
+            payload.proxy_url.as_deref(),
+            payload.danger_accept_invalid_certs,
+        )?
+        .with_timeouts(
+            payload.connect_timeout_seconds,
+            payload.request_timeout_seconds,
+        );
+        let http_client = http
+            .nonblocking(oauth2::reqwest::Client::builder())
+            .redirect(oauth2::reqwest::redirect::Policy::none())
+            .default_headers(outbound_headers(&request_id, None)?)
+            .build()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to build HTTP client: {e}")))?;
+        let http_client = RecordingClient::new(&http_client, http.retry);
+
+        let details: StandardDeviceAuthorizationResponse = client
+            .exchange_device_code()
+            .add_scopes(scopes.iter().cloned().map(Scope::new))
+            .request_async(&http_client)
+            .await
+            .map_err(|e| {
+                map_token_error(
+                    e,
+                    "Failed to request a device code",
+                    http_client.take_failed(),
+                )
             })?;
 
-            // Validate that it's localhost or 127.0.0.1
-            if host != DEFAULT_REDIRECT_HOST && host != LOCALHOST_ADDR {
-                return Err(crate::Error::ConfigurationError(
-                    "Redirect URI must use localhost or 127.0.0.1 for desktop authentication"
-                        .to_string(),
-                ));
+        // Failing to notify listeners must not fail the sign-in itself
+        let _ = self.app.emit(
+            DEVICE_CODE_EVENT,
+            DeviceCode {
+                request_id,
+                user_code: details.user_code().secret().clone(),
+                verification_url: details.verification_uri().to_string(),
+                expires_in: details.expires_in().as_secs(),
+            },
+        );
+
+        let token_response = poll_device_token(
+            &client,
+            &details,
+            &http_client,
+            tokio::time::sleep,
+            payload.timeout_seconds.map(Duration::from_secs),
+        )
+        .await?;
+
+        let mut response = convert_token_response(&token_response, &scopes);
+        response.warnings = warnings;
+        response.collect_warnings(&scopes);
+        self.accounts.remember(&mut response);
+        Ok(response)
+    }
+
+    /// Cancels the in-progress sign-in with `session_id`, as announced by the
+    /// sign-in started event. Its pending callback wait or token exchange fails with
+    /// [`crate::Error::UserCancelled`]. Returns whether such a sign-in existed.
+    pub fn cancel_sign_in(&self, session_id: &str) -> bool {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(session_id)
+            .is_some_and(|cancelled| !cancelled.swap(true, Ordering::SeqCst))
+    }
+
+    /// Signs in to link a Google account to an existing account known by `email`.
+    ///
+    /// Pre-fills the account chooser via `login_hint`, forces the consent screen with
+    /// `access_type=offline` so a refresh token is issued, and fails with
+    /// [`crate::Error::AuthenticationFailed`] if the user picks a different account.
+    /// The `openid` and `email` scopes are added when missing so the ID token carries
+    /// the email to compare against.
+    pub fn link_account(
+        &self,
+        email: &str,
+        mut payload: SignInRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        payload.prompt = Some("consent".to_string());
+        payload.offline_access = Some(true);
+        payload.login_hint = Some(email.to_string());
+        let scopes = payload
+            .scopes
+            .get_or_insert_with(SignInRequest::default_scopes);
+        for required in ["openid", "email"] {
+            if !scopes.iter().any(|s| s == required) {
+                scopes.push(required.to_string());
             }
+        }
 
-            (host.to_string(), parsed_url.port())
-        } else {
-            // Default to localhost with no specific port (will bind to random available port)
-            (DEFAULT_REDIRECT_HOST.to_string(), None)
-        };
+        let token_response = SignInFlow::new(self, payload)?.run()?;
 
-        let google_client_id = ClientId::new(payload.client_id);
-        let google_client_secret = payload.client_secret.ok_or_else(|| {
-            crate::Error::ConfigurationError(
-                "Client secret is required for desktop authentication".to_string(),
+        let id_token = token_response.id_token.as_deref().ok_or_else(|| {
+            crate::Error::AuthenticationFailed(
+                "No ID token returned; cannot verify the linked account".to_string(),
             )
         })?;
-        let google_client_secret = ClientSecret::new(google_client_secret);
-        let auth_url = AuthUrl::new(GOOGLE_AUTH_URL.to_string()).map_err(|_| {
-            crate::Error::ConfigurationError("Invalid authorization endpoint URL".to_string())
-        })?;
-        let token_url = TokenUrl::new(GOOGLE_TOKEN_URL.to_string()).map_err(|_| {
-            crate::Error::ConfigurationError("Invalid token endpoint URL".to_string())
-        })?;
+        let signed_in_email = crate::id_token::decode_id_token_claims(id_token)?
+            .email
+            .ok_or_else(|| {
+                crate::Error::AuthenticationFailed("ID token has no email claim".to_string())
+            })?;
+        if !signed_in_email.eq_ignore_ascii_case(email) {
+            return Err(crate::Error::AuthenticationFailed(format!(
+                "Signed in as {signed_in_email}, expected {email}"
+            )));
+        }
 
-        // Bind to the TCP listener first to get the actual port
-        let listener = if let Some(p) = port {
-            // Try to bind to the specific port
-            TcpListener::bind(format!("{LOCALHOST_ADDR}:{p}")).map_err(|e| {
-                crate::Error::NetworkError(format!("Failed to bind to port {p}: {e}"))
-            })?
-        } else {
-            // Bind to any available port (port 0 means OS assigns an available port)
-            TcpListener::bind(format!("{LOCALHOST_ADDR}:0")).map_err(|e| {
-                crate::Error::NetworkError(format!("Failed to bind to any available port: {e}"))
-            })?
-        };
+        Ok(token_response)
+    }
 
-        // Get the actual port that was bound
-        let actual_port = listener
-            .local_addr()
-            .map_err(|e| crate::Error::NetworkError(format!("Failed to get local address: {e}")))?
-            .port();
-
-        // Construct the redirect URL with the actual port
-        let redirect_url = format!("http://{redirect_host}:{actual_port}");
-
-        // Set up the config for the Google OAuth2 process.
-        let client = SpecialClient::new(google_client_id)
-            .set_client_secret(google_client_secret)
-            .set_auth_uri(auth_url)
-            .set_token_uri(token_url)
-            .set_redirect_uri(RedirectUrl::new(redirect_url).map_err(|_| {
-                crate::Error::ConfigurationError("Invalid redirect URL".to_string())
-            })?)
-            // Google supports OAuth 2.0 Token Revocation (RFC-7009)
-            .set_revocation_url(
-                RevocationUrl::new(GOOGLE_REVOCATION_URL.to_string()).map_err(|_| {
-                    crate::Error::ConfigurationError("Invalid revocation endpoint URL".to_string())
-                })?,
-            );
+    pub fn sign_out(&self, payload: SignOutRequest) -> crate::Result<SignOutResponse> {
+        let strict = payload.strict.unwrap_or_default();
+        let http = HttpSettings::new(
+            payload.proxy_url.as_deref(),
+            payload.danger_accept_invalid_certs,
+        )?
+        .with_timeouts(
+            payload.connect_timeout_seconds,
+            payload.request_timeout_seconds,
+        );
+        let revocation_url = payload
+            .revocation_url
+            .unwrap_or_else(|| GOOGLE_REVOCATION_URL.to_string());
+        Url::parse(&revocation_url).map_err(|e| {
+            crate::Error::configuration(
+                Some("revocation_url"),
+                format!("Invalid revocation endpoint URL: {e}"),
+            )
+        })?;
+        let (mut access_token, mut refresh_token) = (payload.access_token, payload.refresh_token);
+        // Without tokens, revoke those of the stored session being signed out of
+        if let (None, None, Some(account_id)) = (&access_token, &refresh_token, &payload.account_id)
+            && let Some(stored) = self.accounts.load(account_id)?
+        {
+            access_token = stored.access_token.filter(|token| !token.is_empty());
+            refresh_token = stored.refresh_token;
+        }
+        let (access_token, refresh_token) = match (payload.revoke_grant, refresh_token) {
+            // Revoking the refresh token ends the whole grant, access token included
+            (true, Some(refresh_token)) => (None, Some(refresh_token)),
+            (_, refresh_token) => (access_token, refresh_token),
+        };
 
-        // Google supports Proof Key for Code Exchange (PKCE - https://oauth.net/2/pkce/).
-        // Create a PKCE code verifier and SHA-256 encode it as a code challenge.
-        let (pkce_code_challenge, pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
+        // If no token provided, just return success (local sign out)
+        if access_token.is_none() && refresh_token.is_none() {
+            self.accounts.forget(payload.account_id.as_deref())?;
+            return Ok(SignOutResponse {
+                success: true,
+                ..SignOutResponse::default()
+            });
+        }
 
-        // Generate the authorization URL to which we'll redirect the user.
-        let mut auth_url_builder = client.authorize_url(CsrfToken::new_random);
+        // Revoke the tokens with Google
+        let (access_token_revoked, refresh_token_revoked) =
+            std::thread::spawn(move || -> crate::Result<_> {
+                // Create HTTP client
+                let http_client = http
+                    .blocking(oauth2::reqwest::blocking::Client::builder())
+                    .redirect(oauth2::reqwest::redirect::Policy::none())
+                    .build()
+                    .map_err(|e| {
+                        crate::Error::NetworkError(format!("Failed to build HTTP client: {e}"))
+                    })?;
 
-        // Add all the scopes from the payload
-        for scope in scopes {
-            auth_url_builder = auth_url_builder.add_scope(Scope::new(scope));
-        }
+                // Google's revocation endpoint accepts either kind of token
+                let revoke = |token: Option<String>| -> crate::Result<bool> {
+                    let Some(token) = token else {
+                        return Ok(false);
+                    };
+                    let response = http_client
+                        .post(revocation_url.as_str())
+                        .form(&[("token", token.as_str())])
+                        .send()
+                        .map_err(|e| network_error("Failed to revoke token", &e))?;
+                    let status = response.status();
+                    if strict && !status.is_success() {
+                        return Err(crate::Error::HttpStatus {
+                            status: status.as_u16(),
+                            body: response.text().unwrap_or_default(),
+                        });
+                    }
+                    Ok(status.is_success())
+                };
 
-        let (authorize_url, _csrf_state) = auth_url_builder
-            .set_pkce_challenge(pkce_code_challenge)
-            .url();
+                Ok((revoke(access_token)?, revoke(refresh_token)?))
+            })
+            .join()
+            .map_err(|_| {
+                crate::Error::AuthenticationFailed("Token revocation thread panicked".to_string())
+            })??;
 
-        // Open the authorization URL in the browser (detached to avoid blocking on some Linux systems)
-        open::that_detached(authorize_url.to_string())
-            .map_err(|e| crate::Error::NetworkError(format!("Failed to open browser: {e}")))?;
+        // A session left in the token store would be restored by the next silent sign-in
+        self.accounts.forget(payload.account_id.as_deref())?;
 
-        // Get the success HTML response message (use custom if provided, otherwise default)
-        let success_message = payload
-            .success_html_response
-            .as_deref()
-            .unwrap_or(SUCCESS_HTML_RESPONSE);
+        // Unless strict, always report success — the user-facing sign-out is complete
+        // regardless of the revocation HTTP status (the token may already be invalid or expired).
+        Ok(SignOutResponse {
+            success: true,
+            access_token_revoked,
+            refresh_token_revoked,
+        })
+    }
 
-        let (code, _state) = {
-            // The server will terminate itself after collecting the first code.
-            let mut stream = listener.incoming().flatten().next().ok_or_else(|| {
-                crate::Error::NetworkError(
-                    "Listener terminated without accepting a connection".to_string(),
-                )
-            })?;
+    /// Exchanges the refresh token of `payload` for a new access token.
+    ///
+    /// Google only sometimes rotates refresh tokens. A new one in the response
+    /// replaces the old one, which should then be discarded; otherwise the one that
+    /// was used stays valid and is returned in the response, so callers can always
+    /// store the response's refresh token.
+    #[allow(clippy::unused_self)]
+    pub fn refresh_token(
+        &self,
+        payload: RefreshTokenRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        let token_response = request_refresh(
+            payload.client_id,
+            payload.client_secret,
+            payload.refresh_token.clone(),
+            outbound_headers(
+                &payload
+                    .request_id
+                    .unwrap_or_else(|| Uuid::now_v7().to_string()),
+                payload.extra_headers.as_ref(),
+            )?,
+            payload.token_url.as_deref(),
+            &HttpSettings::new(
+                payload.proxy_url.as_deref(),
+                payload.danger_accept_invalid_certs,
+            )?
+            .with_timeouts(payload.connect_timeout_seconds, payload.timeout_seconds)
+            .with_retries(payload.max_retries, payload.retry_base_delay_ms),
+        )?
+        .map_err(|(e, failed)| map_token_error(e, "Failed to refresh token", failed))?;
 
-            let mut reader = BufReader::new(&stream);
+        let mut response = convert_token_response(
+            &token_response,
+            payload.scopes.as_deref().unwrap_or_default(),
+        );
+        if response.refresh_token.is_none() {
+            response.refresh_token = payload.refresh_token;
+            response.is_offline = response.refresh_token.is_some();
+        }
+        Ok(response)
+    }
 
-            let mut request_line = String::new();
-            reader.read_line(&mut request_line)?;
+    /// Refreshes like [`Self::refresh_token`], but rides out transient failures.
+    ///
+    /// When the refresh fails with a transient error (see
+    /// [`crate::Error::is_transient`]: a timeout, a network error or a 5xx status
+    /// from Google) and `token` has not expired yet, or expired less than `grace`
+    /// ago, `token` is returned unchanged so the app keeps working through a brief
+    /// outage. After that window, and for any other error (e.g. a revoked refresh
+    /// token), the error is returned.
+    /// A token with an unknown expiry is never kept.
+    pub fn refresh_with_grace(
+        &self,
+        token: &crate::TokenResponse,
+        payload: RefreshTokenRequest,
+        grace: Duration,
+    ) -> crate::Result<crate::TokenResponse> {
+        match self.refresh_token(payload) {
+            Err(e) if e.is_transient() && token.is_within_grace(grace) => Ok(token.clone()),
+            result => result,
+        }
+    }
 
-            let request_path = request_line.split_whitespace().nth(1).ok_or_else(|| {
-                crate::Error::NetworkError("Invalid HTTP request format".to_string())
+    /// Returns `token` unchanged while its access token is valid for at least
+    /// [`crate::TokenResponse::DEFAULT_REFRESH_SKEW`], otherwise refreshes it with `payload`.
+    /// Without a refresh token or scopes in `payload`, those of `token` are used;
+    /// Google often omits the scopes from its response, so they are carried over.
+    pub fn ensure_valid_token(
+        &self,
+        token: crate::TokenResponse,
+        mut payload: RefreshTokenRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        if !token.is_expired_with_skew(crate::TokenResponse::DEFAULT_REFRESH_SKEW) {
+            return Ok(token);
+        }
+        payload.scopes.get_or_insert(token.scopes);
+        let refresh_token = payload
+            .refresh_token
+            .take()
+            .or(token.refresh_token)
+            .ok_or_else(|| {
+                crate::Error::TokenRefreshFailed(
+                    "Access token expired and no refresh token is available".to_string(),
+                )
             })?;
-            let url = Url::parse(&(format!("http://{DEFAULT_REDIRECT_HOST}{request_path}")))
-                .map_err(|e| {
-                    crate::Error::NetworkError(format!("Failed to parse redirect URL: {e}"))
-                })?;
+        payload.refresh_token = Some(refresh_token);
 
-            let code = url
-                .query_pairs()
-                .find(|(key, _)| key == "code")
-                .map(|(_, code)| AuthorizationCode::new(code.into_owned()))
-                .ok_or_else(|| {
-                    crate::Error::AuthenticationFailed(
-                        "Authorization code not found in response".to_string(),
-                    )
-                })?;
+        self.refresh_token(payload)
+    }
 
-            let state = url
-                .query_pairs()
-                .find(|(key, _)| key == "state")
-                .map(|(_, state)| CsrfToken::new(state.into_owned()))
-                .ok_or_else(|| {
-                    crate::Error::AuthenticationFailed(
-                        "State parameter not found in response".to_string(),
-                    )
-                })?;
+    /// Restores a previous session without any UI by refreshing the refresh token of
+    /// `payload`, or else that of the session the token store holds for its
+    /// `account_id`. Fails with [`crate::Error::NoUserSignedIn`] without either; a
+    /// revoked refresh token fails like [`Self::refresh_token`].
+    pub fn silent_sign_in(
+        &self,
+        mut payload: RefreshTokenRequest,
+    ) -> crate::Result<crate::TokenResponse> {
+        let stored = match (&payload.refresh_token, &payload.account_id) {
+            (None, Some(account_id)) => self.accounts.load(account_id)?,
+            _ => None,
+        };
+        if let Some(stored) = &stored {
+            payload.refresh_token.clone_from(&stored.refresh_token);
+            payload.scopes.get_or_insert_with(|| stored.scopes.clone());
+        }
+        if payload.refresh_token.is_none() {
+            return Err(crate::Error::NoUserSignedIn);
+        }
 
-            let response = format!(
-                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
-                success_message.len(),
-                success_message
-            );
-            stream.write_all(response.as_bytes())?;
+        let mut response = self.refresh_token(payload)?;
+        // Refreshes don't always return an ID token, so the stored session tells who it is
+        if response.id_token.is_none() && response.profile.is_none() {
+            response.profile = stored.as_ref().and_then(user_of);
+        }
+        self.accounts.remember(&mut response);
+        Ok(response)
+    }
 
-            (code, state)
-        };
+    /// Returns the user of the last sign-in, or `None` once they signed out.
+    ///
+    /// This only knows sign-ins since the app started; a session persisted in the
+    /// token store is known again once `silent_sign_in` restored it. The profile is
+    /// the one fetched with `fetch_profile`, otherwise the claims of the ID token, so
+    /// the user stays unknown without the `openid` scope.
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn get_current_user(&self) -> crate::Result<Option<UserProfile>> {
+        Ok(self
+            .accounts
+            .current
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone())
+    }
 
-        let token_response = std::thread::spawn(move || -> crate::Result<_> {
-            // Create HTTP client with proper security settings
-            let http_client = oauth2::reqwest::blocking::Client::builder()
-                // Following redirects opens the client up to SSRF vulnerabilities
+    /// Checks that Google's OAuth endpoints are reachable by fetching the
+    /// `OpenID Connect` discovery document, so a setup screen can tell "can't reach Google"
+    /// apart from bad credentials before attempting a sign-in. The request goes through
+    /// the proxy and timeouts of `payload`, like the requests it stands in for.
+    #[allow(clippy::unused_self)]
+    pub fn check_connectivity(&self, payload: &ConnectivityCheckRequest) -> crate::Result<()> {
+        let http = HttpSettings::new(
+            payload.proxy_url.as_deref(),
+            payload.danger_accept_invalid_certs,
+        )?
+        .with_timeouts(
+            payload.connect_timeout_seconds,
+            Some(
+                payload
+                    .request_timeout_seconds
+                    .unwrap_or(CONNECTIVITY_TIMEOUT.as_secs()),
+            ),
+        );
+        std::thread::spawn(move || -> crate::Result<()> {
+            let http_client = http
+                .blocking(oauth2::reqwest::blocking::Client::builder())
                 .redirect(oauth2::reqwest::redirect::Policy::none())
                 .build()
                 .map_err(|e| {
                     crate::Error::NetworkError(format!("Failed to build HTTP client: {e}"))
                 })?;
 
-            // Exchange the code with a token.
-            let token_response = client
-                .exchange_code(code)
-                .set_pkce_verifier(pkce_code_verifier)
-                .request(&http_client)
-                .map_err(|e| {
-                    crate::Error::AuthenticationFailed(format!(
-                        "Failed to exchange code for token: {e}"
-                    ))
-                })?;
+            let response = http_client
+                .get(GOOGLE_DISCOVERY_URL)
+                .send()
+                .map_err(|e| network_error("Failed to reach Google", &e))?;
+            if !response.status().is_success() {
+                return Err(crate::Error::NetworkError(format!(
+                    "Google discovery endpoint returned HTTP {}",
+                    response.status()
+                )));
+            }
 
-            Ok(token_response)
+            Ok(())
         })
         .join()
-        .map_err(|_| {
-            crate::Error::AuthenticationFailed("Token exchange thread panicked".to_string())
-        })??;
+        .map_err(|_| crate::Error::NetworkError("Connectivity check thread panicked".to_string()))?
+    }
 
-        let id_token = token_response.extra_fields().id_token.clone();
+    /// Verifies an ID token's signature against Google's published keys, and that it
+    /// was issued by Google for one of `audiences`, usually the app's client ID, and
+    /// is within its lifetime (`exp`, `iat`, `nbf`), tolerating `leeway` of clock
+    /// skew ([`IdTokenClaims::DEFAULT_LEEWAY`] is a sensible default). Returns its
+    /// claims. Use this before trusting a token for authorization decisions.
+    pub fn verify_id_token(
+        &self,
+        id_token: &str,
+        audiences: &[&str],
+        leeway: Duration,
+    ) -> crate::Result<IdTokenClaims> {
+        self.verify_id_token_at(id_token, audiences, leeway, crate::id_token::unix_now())
+    }
 
-        // Return the token response
-        Ok(crate::TokenResponse {
+    /// Same as [`Self::verify_id_token`], checking the lifetime at `now` (Unix
+    /// seconds), e.g. against a trusted clock.
+    pub fn verify_id_token_at(
+        &self,
+        id_token: &str,
+        audiences: &[&str],
+        leeway: Duration,
+        now: i64,
+    ) -> crate::Result<IdTokenClaims> {
+        jwks::verify_id_token(
+            &self.id_token_keys,
             id_token,
-            access_token: token_response.access_token().secret().clone(),
-            scopes: token_response.scopes().map_or_else(Vec::new, |s| {
-                s.iter().map(|sc| sc.as_ref().to_string()).collect()
-            }),
-            refresh_token: token_response.refresh_token().map(|t| t.secret().clone()),
-            expires_at: token_response.expires_in().map(|d| {
-                let now = i64::try_from(
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs(),
-                )
-                .unwrap_or(i64::MAX);
-                now.saturating_add(i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
-            }),
-        })
+            audiences,
+            leeway,
+            now,
+            &HttpSettings::default(),
+        )
     }
 
-    #[allow(clippy::unused_self)]
-    pub fn sign_out(&self, payload: SignOutRequest) -> crate::Result<SignOutResponse> {
-        // If no access token provided, just return success (local sign out)
-        let Some(access_token) = payload.access_token else {
-            return Ok(SignOutResponse { success: true });
-        };
+    /// Clears the plugin's caches, so the next requests fetch fresh data, e.g. after
+    /// a configuration or network change. The only cache is that of Google's ID token
+    /// signing keys; HTTP clients, profiles and discovery data are never cached.
+    /// Safe to call from any thread, also while a verification is running.
+    pub fn clear_caches(&self) {
+        self.id_token_keys.clear();
+    }
 
-        // Revoke the token with Google
-        let _response = std::thread::spawn(move || -> crate::Result<_> {
-            // Create HTTP client
-            let http_client = oauth2::reqwest::blocking::Client::builder()
+    /// Looks up an access token at Google's tokeninfo endpoint, e.g. to check its
+    /// scopes before calling an API with it.
+    ///
+    /// A token Google rejects, such as an expired or revoked one, and a token issued
+    /// to a client other than `client_id` are reported with `active: false` rather
+    /// than as an error. The request goes through the proxy and timeouts of
+    /// `network`.
+    ///
+    /// There is no client secret parameter because tokeninfo, unlike an RFC 7662
+    /// introspection endpoint, doesn't authenticate the caller: it takes only the
+    /// token, and anyone holding the token may look it up. What ties the token to
+    /// the app is the `aud` check against `client_id`, so a secret would add nothing.
+    #[allow(clippy::unused_self)]
+    pub fn introspect_token(
+        &self,
+        token: &str,
+        client_id: &str,
+        network: &NetworkSettings,
+    ) -> crate::Result<TokenIntrospection> {
+        let token = token.to_string();
+        let client_id = client_id.to_string();
+        let http = HttpSettings::from_network(network)?;
+        std::thread::spawn(move || -> crate::Result<TokenIntrospection> {
+            let http_client = http
+                .blocking(oauth2::reqwest::blocking::Client::builder())
                 .redirect(oauth2::reqwest::redirect::Policy::none())
                 .build()
                 .map_err(|e| {
                     crate::Error::NetworkError(format!("Failed to build HTTP client: {e}"))
                 })?;
 
-            // Send revocation request
+            // Posted rather than sent in the query so the token stays out of logs
             let response = http_client
-                .post(GOOGLE_REVOCATION_URL)
-                .form(&[("token", access_token.as_str())])
+                .post(GOOGLE_TOKENINFO_URL)
+                .form(&[("access_token", token.as_str())])
                 .send()
-                .map_err(|e| crate::Error::NetworkError(format!("Failed to revoke token: {e}")))?;
-
-            Ok(response)
+                .map_err(|e| network_error("Failed to introspect token", &e))?;
+            let status = response.status();
+            let body = response
+                .bytes()
+                .map_err(|e| network_error("Failed to introspect token", &e))?;
+            parse_token_info(status, &body, &client_id)
         })
         .join()
         .map_err(|_| {
-            crate::Error::AuthenticationFailed("Token exchange thread panicked".to_string())
-        })??;
-
-        // Always report success — the user-facing sign-out is complete regardless
-        // of the revocation HTTP status (the token may already be invalid or expired).
-        Ok(SignOutResponse { success: true })
+            crate::Error::NetworkError("Token introspection thread panicked".to_string())
+        })?
     }
 
+    /// Checks whether a refresh token can still be exchanged for new tokens.
+    ///
+    /// Google has no introspection endpoint for refresh tokens, so this performs a
+    /// real refresh and discards the result. Returns `Ok(false)` when Google rejects
+    /// the token with `invalid_grant` (expired or revoked) and an error for network
+    /// or other failures, which lets apps decide whether to prompt for a new sign-in.
+    /// Other failures are reported like those of [`Self::refresh_token`], and the
+    /// request goes through the proxy and timeouts of `network`.
     #[allow(clippy::unused_self)]
-    pub fn refresh_token(
+    pub fn validate_refresh_token(
         &self,
-        payload: RefreshTokenRequest,
-    ) -> crate::Result<crate::TokenResponse> {
-        // Client secret is required for desktop authentication
-        let google_client_secret = payload.client_secret.ok_or_else(|| {
-            crate::Error::ConfigurationError(
-                "Client secret is required for desktop authentication".to_string(),
+        refresh_token: String,
+        client_id: String,
+        client_secret: String,
+        network: &NetworkSettings,
+    ) -> crate::Result<bool> {
+        let result = request_refresh(
+            client_id,
+            Some(client_secret),
+            Some(refresh_token),
+            outbound_headers(&Uuid::now_v7().to_string(), None)?,
+            None,
+            &HttpSettings::from_network(network)?,
+        )?;
+        refresh_validity(result)
+    }
+}
+
+/// Normalizes a space-delimited `response_type` so the order of values doesn't matter.
+fn normalize_response_type(response_type: &str) -> String {
+    let mut values: Vec<&str> = response_type.split_whitespace().collect();
+    values.sort_unstable();
+    values.join(" ")
+}
+
+/// Maps an `error` returned to the redirect URI by the authorization endpoint.
+fn map_authorization_error(error: &str, description: Option<&str>) -> crate::Error {
+    match error {
+        // Returned when the user clicks "Cancel" on the consent screen
+        "access_denied" => crate::Error::UserCancelled,
+        "interaction_required"
+        | "consent_required"
+        | "login_required"
+        | "account_selection_required" => crate::Error::InteractionRequired(error.to_string()),
+        _ => crate::Error::AuthenticationFailed(description.map_or_else(
+            || format!("Authorization failed: {error}"),
+            |description| format!("Authorization failed: {error} ({description})"),
+        )),
+    }
+}
+
+/// Accepts loopback connections until one carries the OAuth callback, returning
+/// the stream (to answer later) and the parsed request URL.
+///
+/// Hybrid response types (e.g. `code id_token`) return their values in the URL
+/// fragment, which browsers never send to the server. A bare request for `/` is
+/// therefore answered with a small script that re-requests the page with the
+/// fragment moved into the query string. The plain `code` flow never needs this.
+///
+/// Fails with [`crate::Error::UserCancelled`] once `cancelled` is set, and with
+/// [`crate::Error::Timeout`] once `deadline` passes.
+fn accept_callback<T: CallbackTransport>(
+    transport: &T,
+    cancelled: &AtomicBool,
+    deadline: Option<Instant>,
+) -> crate::Result<(T::Stream, Url)> {
+    loop {
+        check_waiting(cancelled, deadline)?;
+        if let Some(callback) = poll_callback(transport) {
+            return Ok(callback);
+        }
+        std::thread::sleep(ACCEPT_POLL_INTERVAL);
+    }
+}
+
+/// Like [`accept_callback`], but sleeps on the async runtime between polls and
+/// reads accepted connections on a blocking thread, so neither blocks the runtime.
+async fn accept_callback_async<T: CallbackTransport + Sync>(
+    transport: &T,
+    cancelled: &AtomicBool,
+    deadline: Option<Instant>,
+) -> crate::Result<(T::Stream, Url)>
+where
+    T::Stream: Send + 'static,
+{
+    loop {
+        check_waiting(cancelled, deadline)?;
+        // Accepting doesn't block, as the listeners are non-blocking
+        if let Ok(Some(stream)) = transport.accept() {
+            let handled =
+                tauri::async_runtime::spawn_blocking(move || handle_connection(stream)).await;
+            if let Ok(Some(callback)) = handled {
+                return Ok(callback);
+            }
+            continue;
+        }
+        tokio::time::sleep(ACCEPT_POLL_INTERVAL).await;
+    }
+}
+
+fn check_waiting(cancelled: &AtomicBool, deadline: Option<Instant>) -> crate::Result<()> {
+    if cancelled.load(Ordering::SeqCst) {
+        return Err(crate::Error::UserCancelled);
+    }
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(crate::Error::Timeout(
+            "waiting for the sign-in to complete".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Accepts one pending connection, returning `None` when there is none or it was
+/// answered without carrying OAuth parameters.
+fn poll_callback<T: CallbackTransport>(transport: &T) -> Option<(T::Stream, Url)> {
+    let stream = transport.accept().ok()??;
+    handle_connection(stream)
+}
+
+/// Reads the request of an accepted connection, returning it when it carries OAuth
+/// parameters. Anything else is answered and dropped: browsers fire requests of
+/// their own, e.g. for `/favicon.ico` or preconnected sockets that never send one,
+/// and port scanners may connect too, none of which may fail the sign-in.
+fn handle_connection<S: Read + Write>(mut stream: S) -> Option<(S, Url)> {
+    let Some(url) = read_request(&mut stream)
+        .ok()
+        .and_then(|request_line| parse_request_url(&request_line))
+    else {
+        let _ = write_page(
+            &mut stream,
+            &SuccessPage {
+                status: 400,
+                headers: Vec::new(),
+                body: String::new(),
+            },
+        );
+        return None;
+    };
+
+    if url.path() == "/" && url.query().is_none() {
+        let _ = write_page(
+            &mut stream,
+            &SuccessPage {
+                status: 200,
+                headers: vec![(
+                    "content-type".to_string(),
+                    "text/html; charset=utf-8".to_string(),
+                )],
+                body: FRAGMENT_RELAY_HTML.to_string(),
+            },
+        );
+        return None;
+    }
+
+    if !url
+        .query_pairs()
+        .any(|(key, _)| key == "code" || key == "error")
+    {
+        let _ = write_page(
+            &mut stream,
+            &SuccessPage {
+                status: 204,
+                headers: Vec::new(),
+                body: String::new(),
+            },
+        );
+        return None;
+    }
+
+    Some((stream, url))
+}
+
+/// The URL of an HTTP request line such as `GET /?code=... HTTP/1.1`.
+fn parse_request_url(request_line: &str) -> Option<Url> {
+    let request_path = request_line.split_whitespace().nth(1)?;
+    Url::parse(&format!("http://{DEFAULT_REDIRECT_HOST}{request_path}")).ok()
+}
+
+/// Reads a whole HTTP request, returning its request line. Answering before the
+/// headers and body are consumed makes some clients see a connection reset
+/// instead of the response.
+///
+/// Fails for a request that ends before its headers do or whose headers exceed
+/// [`MAX_REQUEST_HEAD`]; the body is read up to [`MAX_REQUEST_BODY`].
+fn read_request(stream: &mut impl Read) -> std::io::Result<String> {
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_REQUEST_HEAD);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if head.read_line(&mut header)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "HTTP request headers are incomplete or too large",
+            ));
+        }
+        if header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or_default();
+        }
+    }
+    std::io::copy(
+        &mut reader.take(content_length.min(MAX_REQUEST_BODY)),
+        &mut std::io::sink(),
+    )?;
+
+    Ok(request_line)
+}
+
+/// Writes `page` to `stream`, then answers repeat callbacks, on a blocking thread
+/// so the async runtime isn't held up by a slow client.
+async fn answer_async(
+    transport: TcpTransport,
+    mut stream: TcpStream,
+    page: SuccessPage,
+) -> crate::Result<()> {
+    tauri::async_runtime::spawn_blocking(move || {
+        write_page(&mut stream, &page)?;
+        answer_repeat_callbacks(&transport);
+        Ok(())
+    })
+    .await
+    .map_err(|e| crate::Error::NetworkError(format!("Failed to answer the browser: {e}")))?
+}
+
+/// Answers every pending connection with the "already completed" page, so a
+/// repeated callback never leads to a second code exchange.
+fn answer_repeat_callbacks<T: CallbackTransport>(transport: &T) {
+    while let Ok(Some(mut stream)) = transport.accept() {
+        let _ = read_request(&mut stream);
+        let _ = write_page(
+            &mut stream,
+            &SuccessPage {
+                status: 200,
+                headers: Vec::new(),
+                body: SIGN_IN_ALREADY_COMPLETED_RESPONSE.to_string(),
+            },
+        );
+    }
+}
+
+/// Maps an `oauth2` token response into the plugin's [`crate::TokenResponse`].
+/// A response without a `scope` field granted exactly the `requested` scopes
+/// (RFC 6749, section 5.1), as Google's refresh responses sometimes are.
+fn convert_token_response(
+    token_response: &SpecialTokenResponse,
+    requested: &[String],
+) -> crate::TokenResponse {
+    let refresh_token = token_response.refresh_token().map(|t| t.secret().clone());
+    let expires_in_seconds = token_response
+        .expires_in()
+        .map(|d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+    let mut response = crate::TokenResponse {
+        // Treat an empty ID token like a missing one
+        id_token: token_response
+            .extra_fields()
+            .id_token
+            .clone()
+            .filter(|id_token| !id_token.is_empty()),
+        access_token: Some(token_response.access_token().secret().clone()),
+        scopes: token_response.scopes().map_or_else(
+            || requested.to_vec(),
+            |s| s.iter().map(|sc| sc.as_ref().to_string()).collect(),
+        ),
+        is_offline: refresh_token.is_some(),
+        refresh_token,
+        expires_at: expires_in_seconds.map(|expires_in| {
+            let now = i64::try_from(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
             )
-        })?;
+            .unwrap_or(i64::MAX);
+            now.saturating_add(expires_in)
+        }),
+        expires_at_iso: None,
+        expires_in_seconds,
+        callback_params: None,
+        warnings: Vec::new(),
+        profile: None,
+        server_auth_code: None,
+        server_auth_state: None,
+        server_code_verifier: None,
+        account_id: None,
+        webview_cookies: None,
+    };
+    response.fill_expires_at_iso();
+    response.fill_account_id();
+    response
+}
+
+/// Maps a failed token request to a plugin error, recognizing rejected client
+/// credentials and grants so they aren't reported as a generic authentication failure.
+/// Transport failures become network errors, and other non-success responses
+/// keep the status and body recorded in `failed`.
+fn map_token_error(
+    error: TokenRequestError,
+    context: &str,
+    failed: Option<(u16, String)>,
+) -> crate::Error {
+    match (error, failed) {
+        (RequestTokenError::ServerResponse(e), _)
+            if *e.error() == BasicErrorResponseType::InvalidClient =>
+        {
+            crate::Error::InvalidClientId
+        }
+        (RequestTokenError::ServerResponse(e), _)
+            if *e.error() == BasicErrorResponseType::InvalidGrant =>
+        {
+            crate::Error::InvalidGrant(
+                e.error_description()
+                    .map_or_else(|| e.error().to_string(), Clone::clone),
+            )
+        }
+        (RequestTokenError::Request(e), _) => network_error(context, &e),
+        (_, Some((status, body))) => crate::Error::HttpStatus { status, body },
+        (e, None) => crate::Error::AuthenticationFailed(format!("{context}: {e}")),
+    }
+}
 
-        // Create OAuth2 client without needing redirect URI for refresh
-        let google_client_id = ClientId::new(payload.client_id);
-        let google_client_secret = ClientSecret::new(google_client_secret);
+/// Whether a refresh succeeded, with `invalid_grant` meaning the refresh token is
+/// no longer valid and any other failure mapped by [`map_token_error`].
+fn refresh_validity(result: RefreshResult) -> crate::Result<bool> {
+    match result.map_err(|(e, failed)| map_token_error(e, "Failed to refresh token", failed)) {
+        Ok(_) => Ok(true),
+        Err(crate::Error::InvalidGrant(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
 
-        let token_url = TokenUrl::new(GOOGLE_TOKEN_URL.to_string()).map_err(|_| {
-            crate::Error::ConfigurationError("Invalid token endpoint URL".to_string())
-        })?;
+/// Polls the token endpoint for the device code in `details`, waiting with `sleep`
+/// between polls. `authorization_pending` keeps polling at the interval Google
+/// asked for and `slow_down` adds five seconds to it, until the user approves or
+/// denies the sign-in, or the code expires (after `timeout`, if that is shorter).
+async fn poll_device_token<'c, D, C, S, SF>(
+    client: &'c SpecialClient<EndpointNotSet, D, EndpointNotSet, EndpointNotSet, EndpointSet>,
+    details: &'c StandardDeviceAuthorizationResponse,
+    http_client: &'c C,
+    sleep: S,
+    timeout: Option<Duration>,
+) -> crate::Result<SpecialTokenResponse>
+where
+    D: EndpointState + Sync,
+    C: AsyncHttpClient<'c, Error = HttpError, Future: Send> + Sync,
+    S: Fn(Duration) -> SF + Send + 'c,
+    SF: Future<Output = ()> + Send,
+{
+    client
+        .exchange_device_access_token(details)
+        .request_async(http_client, sleep, timeout)
+        .await
+        .map_err(map_device_error)
+}
 
-        // Create a basic client for token refresh
-        let client = SpecialClient::new(google_client_id)
-            .set_client_secret(google_client_secret)
-            .set_token_uri(token_url);
+/// Maps a failed device code poll: a denied sign-in counts as cancelled, and an
+/// expired code fails the sign-in.
+fn map_device_error(error: RequestTokenError<HttpError, DeviceCodeErrorResponse>) -> crate::Error {
+    match error {
+        RequestTokenError::ServerResponse(e) => match e.error() {
+            DeviceCodeErrorResponseType::AccessDenied => crate::Error::UserCancelled,
+            DeviceCodeErrorResponseType::ExpiredToken => crate::Error::AuthenticationFailed(
+                "The device code expired before the sign-in was approved".to_string(),
+            ),
+            DeviceCodeErrorResponseType::Basic(BasicErrorResponseType::InvalidClient) => {
+                crate::Error::InvalidClientId
+            }
+            _ => crate::Error::AuthenticationFailed(format!("Device sign-in failed: {e}")),
+        },
+        RequestTokenError::Request(e) => network_error("Device sign-in failed", &e),
+        e => crate::Error::AuthenticationFailed(format!("Device sign-in failed: {e}")),
+    }
+}
 
-        // Refresh token is required for desktop authentication
-        let refresh_token = payload.refresh_token.ok_or_else(|| {
-            crate::Error::ConfigurationError(
-                "Refresh token is required for desktop authentication".to_string(),
+/// Maps a failed HTTP request to [`crate::Error::Timeout`] when it ran out of
+/// time, or else to [`crate::Error::NetworkError`], calling out TLS certificate
+/// failures that would otherwise read as a generic connect error.
+fn network_error(context: &str, error: &(dyn std::error::Error + 'static)) -> crate::Error {
+    if timed_out(error) {
+        return crate::Error::Timeout(format!("{context}: {error}"));
+    }
+    crate::Error::NetworkError(tls_failure(error).map_or_else(
+        || format!("{context}: {error}"),
+        |chain| {
+            format!(
+                "{context}: TLS certificate verification failed \
+                 (the certificate may not match the host name): {chain}"
             )
+        },
+    ))
+}
+
+/// Whether any error in the chain is a `reqwest` connect or request timeout.
+/// `oauth2` boxes the `reqwest` error, and the box's `source()` skips past it, so
+/// the box is checked too.
+fn timed_out(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        if e.downcast_ref::<oauth2::reqwest::Error>()
+            .or_else(|| {
+                e.downcast_ref::<Box<oauth2::reqwest::Error>>()
+                    .map(AsRef::as_ref)
+            })
+            .is_some_and(oauth2::reqwest::Error::is_timeout)
+        {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Returns the full error chain if any error in it concerns the TLS certificate.
+/// Certificate verification is on unless `danger_accept_invalid_certs` is set.
+fn tls_failure(error: &(dyn std::error::Error + 'static)) -> Option<String> {
+    use std::fmt::Write as _;
+
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        let _ = write!(chain, ": {e}");
+        source = e.source();
+    }
+    chain
+        .to_ascii_lowercase()
+        .contains("certificate")
+        .then_some(chain)
+}
+
+/// Attaches `client_secret` when there is one. Public clients, such as Google's
+/// "Desktop app" clients, are identified by their client ID alone.
+fn with_client_secret<A, D, I, R, T>(
+    client: SpecialClient<A, D, I, R, T>,
+    client_secret: Option<String>,
+) -> SpecialClient<A, D, I, R, T>
+where
+    A: EndpointState,
+    D: EndpointState,
+    I: EndpointState,
+    R: EndpointState,
+    T: EndpointState,
+{
+    match client_secret {
+        Some(secret) => client.set_client_secret(ClientSecret::new(secret)),
+        None => client,
+    }
+}
+
+/// Exchanges a refresh token on a separate thread, returning the raw `oauth2`
+/// result so callers can inspect server error codes, along with the status and
+/// body of a non-success response.
+fn request_refresh(
+    client_id: String,
+    client_secret: Option<String>,
+    refresh_token: Option<String>,
+    headers: HeaderMap,
+    token_url: Option<&str>,
+    http: &HttpSettings,
+) -> crate::Result<RefreshResult> {
+    // Create OAuth2 client without needing redirect URI for refresh
+    let google_client_id = ClientId::new(client_id);
+
+    let token_url =
+        TokenUrl::new(token_url.unwrap_or(GOOGLE_TOKEN_URL).to_string()).map_err(|_| {
+            crate::Error::configuration(Some("token_url"), "Invalid token endpoint URL")
         })?;
 
-        // Execute the refresh token request in a thread
-        let token_response = std::thread::spawn(move || -> crate::Result<_> {
-            // Create HTTP client with proper security settings
-            let http_client = oauth2::reqwest::blocking::Client::builder()
-                .redirect(oauth2::reqwest::redirect::Policy::none())
-                .build()
-                .map_err(|e| {
-                    crate::Error::NetworkError(format!("Failed to build HTTP client: {e}"))
-                })?;
+    // Create a basic client for token refresh
+    let client = with_client_secret(SpecialClient::new(google_client_id), client_secret)
+        .set_token_uri(token_url);
 
-            // Exchange the refresh token for new tokens
-            let token_response = client
-                .exchange_refresh_token(&oauth2::RefreshToken::new(refresh_token))
-                .request(&http_client)
-                .map_err(|e| {
-                    crate::Error::AuthenticationFailed(format!("Failed to refresh token: {e}"))
-                })?;
+    // Refresh token is required for desktop authentication
+    let refresh_token = refresh_token.ok_or_else(|| {
+        crate::Error::configuration(
+            Some("refresh_token"),
+            "Refresh token is required for desktop authentication",
+        )
+    })?;
 
-            Ok(token_response)
-        })
-        .join()
-        .map_err(|_| {
-            crate::Error::AuthenticationFailed("Token refresh thread panicked".to_string())
-        })??;
+    // Execute the refresh token request in a thread
+    let http = http.clone();
+    std::thread::spawn(move || -> crate::Result<_> {
+        // Create HTTP client with proper security settings
+        let http_client = http
+            .blocking(oauth2::reqwest::blocking::Client::builder())
+            .redirect(oauth2::reqwest::redirect::Policy::none())
+            .default_headers(headers)
+            .build()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to build HTTP client: {e}")))?;
 
-        let id_token = token_response.extra_fields().id_token.clone();
+        // Exchange the refresh token for new tokens
+        let http_client = RecordingClient::new(&http_client, http.retry);
+        Ok(client
+            .exchange_refresh_token(&oauth2::RefreshToken::new(refresh_token))
+            .request(&http_client)
+            .map_err(|e| (e, http_client.take_failed())))
+    })
+    .join()
+    .map_err(|_| crate::Error::AuthenticationFailed("Token refresh thread panicked".to_string()))?
+}
 
-        // Return the refreshed token response
-        Ok(crate::TokenResponse {
-            id_token,
-            access_token: token_response.access_token().secret().clone(),
-            scopes: token_response.scopes().map_or_else(Vec::new, |s| {
-                s.iter().map(|sc| sc.as_ref().to_string()).collect()
-            }),
-            refresh_token: token_response.refresh_token().map(|t| t.secret().clone()),
-            expires_at: token_response.expires_in().map(|d| {
-                let now = i64::try_from(
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs(),
-                )
-                .unwrap_or(i64::MAX);
-                now.saturating_add(i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
-            }),
+/// The user `response` signed in: the fetched profile, else the ID token's claims.
+fn user_of(response: &crate::TokenResponse) -> Option<UserProfile> {
+    response.profile.clone().or_else(|| {
+        response
+            .id_token
+            .as_deref()
+            .and_then(|id_token| crate::id_token::decode_id_token_claims(id_token).ok())
+            .map(UserProfile::from)
+    })
+}
+
+/// Fetches the user's profile on a separate thread, as the blocking client can't
+/// run on the async runtime.
+fn fetch_profile(
+    access_token: &str,
+    headers: HeaderMap,
+    http: &HttpSettings,
+) -> crate::Result<UserProfile> {
+    let access_token = access_token.to_string();
+    let http = http.clone();
+    std::thread::spawn(move || -> crate::Result<UserProfile> {
+        let http_client = http
+            .blocking(oauth2::reqwest::blocking::Client::builder())
+            .redirect(oauth2::reqwest::redirect::Policy::none())
+            .default_headers(headers)
+            .build()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to build HTTP client: {e}")))?;
+
+        let response = http_client
+            .get(GOOGLE_USERINFO_URL)
+            .bearer_auth(access_token)
+            .send()
+            .map_err(|e| network_error("Failed to fetch the user profile", &e))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .map_err(|e| network_error("Failed to fetch the user profile", &e))?;
+        parse_profile(status, &body)
+    })
+    .join()
+    .map_err(|_| crate::Error::NetworkError("Profile fetch thread panicked".to_string()))?
+}
+
+async fn fetch_profile_async(
+    access_token: &str,
+    headers: HeaderMap,
+    http: &HttpSettings,
+) -> crate::Result<UserProfile> {
+    let http_client = http
+        .nonblocking(oauth2::reqwest::Client::builder())
+        .redirect(oauth2::reqwest::redirect::Policy::none())
+        .default_headers(headers)
+        .build()
+        .map_err(|e| crate::Error::NetworkError(format!("Failed to build HTTP client: {e}")))?;
+
+    let response = http_client
+        .get(GOOGLE_USERINFO_URL)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .map_err(|e| network_error("Failed to fetch the user profile", &e))?;
+    let status = response.status();
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| network_error("Failed to fetch the user profile", &e))?;
+    parse_profile(status, &body)
+}
+
+fn parse_profile(status: oauth2::http::StatusCode, body: &[u8]) -> crate::Result<UserProfile> {
+    if !status.is_success() {
+        return Err(crate::Error::HttpStatus {
+            status: status.as_u16(),
+            body: String::from_utf8_lossy(body).into_owned(),
+        });
+    }
+    serde_json::from_slice(body)
+        .map_err(|e| crate::Error::NetworkError(format!("Failed to parse the user profile: {e}")))
+}
+
+/// A tokeninfo response; Google sends `exp` as a string.
+#[derive(Deserialize)]
+struct TokenInfo {
+    scope: Option<String>,
+    exp: Option<String>,
+    aud: Option<String>,
+    email: Option<String>,
+}
+
+/// Google answers an invalid token with 400 `invalid_token`.
+fn parse_token_info(
+    status: oauth2::http::StatusCode,
+    body: &[u8],
+    client_id: &str,
+) -> crate::Result<TokenIntrospection> {
+    if status == oauth2::http::StatusCode::BAD_REQUEST {
+        return Ok(TokenIntrospection::default());
+    }
+    if !status.is_success() {
+        return Err(crate::Error::HttpStatus {
+            status: status.as_u16(),
+            body: String::from_utf8_lossy(body).into_owned(),
+        });
+    }
+    let info: TokenInfo = serde_json::from_slice(body).map_err(|e| {
+        crate::Error::NetworkError(format!("Failed to parse the tokeninfo response: {e}"))
+    })?;
+    Ok(TokenIntrospection {
+        active: info.aud.as_deref() == Some(client_id),
+        scope: info.scope,
+        exp: info.exp.and_then(|exp| exp.parse().ok()),
+        aud: info.aud,
+        email: info.email,
+    })
+}
+
+/// Default headers of outbound requests: the caller's `extra_headers` plus a
+/// correlation ID, so one sign-in or refresh can be traced across the app, its
+/// backend and Google.
+fn outbound_headers(
+    request_id: &str,
+    extra_headers: Option<&HashMap<String, String>>,
+) -> crate::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in extra_headers.into_iter().flatten() {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+            crate::Error::configuration(
+                Some("extra_headers"),
+                format!("Invalid header name `{name}`"),
+            )
+        })?;
+        if PROTECTED_HEADERS.contains(&name.as_str()) {
+            return Err(crate::Error::configuration(
+                Some("extra_headers"),
+                format!("Header `{name}` is set by the plugin and cannot be overridden"),
+            ));
+        }
+        let value = HeaderValue::from_str(value).map_err(|_| {
+            crate::Error::configuration(
+                Some("extra_headers"),
+                format!("Invalid value for header `{name}`"),
+            )
+        })?;
+        headers.insert(name, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(headers)
+}
+
+/// Proxy, certificate and timeout settings shared by the HTTP clients of one
+/// operation.
+#[derive(Clone)]
+struct HttpSettings {
+    proxy: Option<oauth2::reqwest::Proxy>,
+    accept_invalid_certs: bool,
+    connect_timeout: Duration,
+    timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            accept_invalid_certs: false,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl HttpSettings {
+    fn new(proxy_url: Option<&str>, accept_invalid_certs: bool) -> crate::Result<Self> {
+        let proxy = proxy_url
+            .map(oauth2::reqwest::Proxy::all)
+            .transpose()
+            .map_err(|e| {
+                crate::Error::configuration(Some("proxy_url"), format!("Invalid proxy URL: {e}"))
+            })?;
+        Ok(Self {
+            proxy,
+            accept_invalid_certs,
+            ..Self::default()
         })
     }
+
+    /// The proxy, certificate and timeout settings of `network`.
+    fn from_network(network: &NetworkSettings) -> crate::Result<Self> {
+        Ok(Self::new(
+            network.proxy_url.as_deref(),
+            network.danger_accept_invalid_certs,
+        )?
+        .with_timeouts(
+            network.connect_timeout_seconds,
+            network.request_timeout_seconds,
+        ))
+    }
+
+    /// Overrides the connect and overall request timeouts, in seconds.
+    #[must_use]
+    const fn with_timeouts(
+        mut self,
+        connect_seconds: Option<u64>,
+        request_seconds: Option<u64>,
+    ) -> Self {
+        if let Some(seconds) = connect_seconds {
+            self.connect_timeout = Duration::from_secs(seconds);
+        }
+        if let Some(seconds) = request_seconds {
+            self.timeout = Duration::from_secs(seconds);
+        }
+        self
+    }
+
+    /// Retries token requests up to `max_retries` times, backing off from
+    /// `base_delay_ms`.
+    #[must_use]
+    fn with_retries(mut self, max_retries: Option<u32>, base_delay_ms: Option<u64>) -> Self {
+        self.retry = RetryPolicy {
+            max_retries: max_retries.unwrap_or_default(),
+            base_delay: base_delay_ms.map_or(DEFAULT_RETRY_DELAY, Duration::from_millis),
+        };
+        self
+    }
+
+    fn blocking(
+        &self,
+        builder: oauth2::reqwest::blocking::ClientBuilder,
+    ) -> oauth2::reqwest::blocking::ClientBuilder {
+        let builder = builder
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout);
+        match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder,
+        }
+    }
+
+    fn nonblocking(
+        &self,
+        builder: oauth2::reqwest::ClientBuilder,
+    ) -> oauth2::reqwest::ClientBuilder {
+        let builder = builder
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout);
+        match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder,
+        }
+    }
+}
+
+/// Writes a rendered page as a complete HTTP/1.1 response.
+fn write_page(stream: &mut impl Write, page: &SuccessPage) -> std::io::Result<()> {
+    use std::fmt::Write as _;
+
+    let reason = tauri::http::StatusCode::from_u16(page.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or_default();
+    let mut headers = String::new();
+    for (name, value) in &page.headers {
+        let _ = write!(headers, "{name}: {value}\r\n");
+    }
+    // A 204 must not declare a body, not even an empty one
+    if page.status != 204 {
+        let _ = write!(headers, "content-length: {}\r\n", page.body.len());
+    }
+    let response = format!(
+        "HTTP/1.1 {} {reason}\r\n{headers}\r\n{}",
+        page.status, page.body
+    );
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::Cursor;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::test_support::{
+        MockServer, google_auth, refresh_request, sign_out_request, token_response,
+    };
+    use super::*;
+    use crate::success_page::DefaultSuccessPageRenderer;
+
+    /// A connection that replays `request` and records the response.
+    struct FakeStream {
+        request: Cursor<Vec<u8>>,
+        response: Vec<u8>,
+    }
+
+    impl FakeStream {
+        fn new(request: &str) -> Self {
+            Self {
+                request: Cursor::new(request.as_bytes().to_vec()),
+                response: Vec::new(),
+            }
+        }
+
+        fn response(&self) -> String {
+            String::from_utf8_lossy(&self.response).into_owned()
+        }
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.request.read(buf)
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.response.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Hands out the queued connections in order.
+    struct FakeTransport(Mutex<VecDeque<FakeStream>>);
+
+    impl FakeTransport {
+        fn new(requests: &[&str]) -> Self {
+            Self(Mutex::new(
+                requests.iter().copied().map(FakeStream::new).collect(),
+            ))
+        }
+    }
+
+    impl CallbackTransport for FakeTransport {
+        type Stream = FakeStream;
+
+        fn port(&self) -> std::io::Result<u16> {
+            Ok(8080)
+        }
+
+        fn accept(&self) -> std::io::Result<Option<FakeStream>> {
+            Ok(self.0.lock().expect("transport lock poisoned").pop_front())
+        }
+    }
+
+    fn accept(transport: &FakeTransport) -> crate::Result<(FakeStream, Url)> {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        accept_callback(transport, &AtomicBool::new(false), Some(deadline))
+    }
+
+    #[test]
+    fn favicon_request_before_the_callback_is_ignored() {
+        let transport = FakeTransport::new(&[
+            "GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "GET /?code=abc&state=xyz HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ]);
+
+        let (_, url) = accept(&transport).expect("callback was not accepted");
+
+        assert_eq!(url.path(), "/");
+        assert!(
+            url.query_pairs()
+                .any(|(key, value)| key == "code" && value == "abc")
+        );
+    }
+
+    #[test]
+    fn noise_connections_do_not_fail_the_sign_in() {
+        let transport = FakeTransport::new(&[
+            "",
+            "NOT-HTTP\r\n\r\n",
+            "GET /?error=access_denied HTTP/1.1\r\n\r\n",
+        ]);
+
+        let (_, url) = accept(&transport).expect("callback was not accepted");
+
+        assert_eq!(url.query(), Some("error=access_denied"));
+    }
+
+    #[test]
+    fn requests_without_oauth_params_are_answered_with_204() {
+        let mut stream = FakeStream::new("GET /favicon.ico HTTP/1.1\r\n\r\n");
+
+        assert!(handle_connection(&mut stream).is_none());
+        assert!(stream.response().starts_with("HTTP/1.1 204 No Content\r\n"));
+    }
+
+    #[test]
+    fn request_with_several_headers_is_drained_before_answering() {
+        let request = "POST /?code=abc&state=xyz HTTP/1.1\r\n\
+            Host: localhost:8080\r\n\
+            User-Agent: Mozilla/5.0\r\n\
+            Accept: text/html,application/xhtml+xml\r\n\
+            Accept-Language: en-US,en;q=0.9\r\n\
+            Content-Length: 11\r\n\
+            Connection: keep-alive\r\n\
+            \r\n\
+            hello=world";
+        let transport = FakeTransport::new(&[request]);
+
+        let (mut stream, url) = accept(&transport).expect("callback was not accepted");
+        let page = DefaultSuccessPageRenderer::default().render(None);
+        write_page(&mut stream, &page).expect("page was not written");
+
+        assert_eq!(url.query(), Some("code=abc&state=xyz"));
+        assert_eq!(stream.request.position(), request.len() as u64);
+        let response = stream.response();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(&format!("content-length: {}\r\n", page.body.len())));
+        assert!(response.ends_with(&format!("\r\n\r\n{}", page.body)));
+    }
+
+    #[test]
+    fn oversized_request_headers_are_rejected() {
+        let request = format!(
+            "GET /?code=abc HTTP/1.1\r\nCookie: {}\r\n\r\n",
+            "a".repeat(64 * 1024)
+        );
+
+        assert!(read_request(&mut Cursor::new(request)).is_err());
+    }
+
+    #[test]
+    fn request_ending_before_its_headers_is_rejected() {
+        assert!(read_request(&mut Cursor::new("GET /?code=abc HTTP/1.1\r\nHost: x")).is_err());
+    }
+
+    #[test]
+    fn malformed_requests_are_answered_with_400() {
+        let mut stream = FakeStream::new("\r\n");
+
+        assert!(handle_connection(&mut stream).is_none());
+        assert!(
+            stream
+                .response()
+                .starts_with("HTTP/1.1 400 Bad Request\r\n")
+        );
+    }
+
+    #[test]
+    fn access_denied_is_a_cancellation() {
+        assert!(matches!(
+            map_authorization_error("access_denied", Some("The user denied access")),
+            crate::Error::UserCancelled
+        ));
+    }
+
+    #[test]
+    fn other_authorization_errors_keep_their_description() {
+        let error = map_authorization_error("invalid_scope", Some("Unknown scope"));
+        assert!(
+            matches!(error, crate::Error::AuthenticationFailed(message) if message == "Authorization failed: invalid_scope (Unknown scope)")
+        );
+
+        let error = map_authorization_error("server_error", None);
+        assert!(
+            matches!(error, crate::Error::AuthenticationFailed(message) if message == "Authorization failed: server_error")
+        );
+    }
+
+    fn loopback_transport() -> TcpTransport {
+        TcpTransport::bind(
+            &[IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            transport::BindPort::Random(1),
+        )
+        .expect("failed to bind the loopback transport")
+    }
+
+    #[test]
+    fn waiting_without_a_connection_times_out() {
+        let transport = loopback_transport();
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(200);
+
+        let result = accept_callback(&transport, &AtomicBool::new(false), Some(deadline));
+
+        assert!(matches!(result, Err(crate::Error::Timeout(_))));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn waiting_asynchronously_without_a_connection_times_out() {
+        let transport = loopback_transport();
+        let deadline = Instant::now() + Duration::from_millis(200);
+
+        let result = tauri::async_runtime::block_on(accept_callback_async(
+            &transport,
+            &AtomicBool::new(false),
+            Some(deadline),
+        ));
+
+        assert!(matches!(result, Err(crate::Error::Timeout(_))));
+    }
+
+    #[test]
+    fn repeated_callback_gets_the_already_completed_page() {
+        let transport = loopback_transport();
+        let port = transport.port().expect("transport has no port");
+        let callback = "GET /?code=abc&state=xyz HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut browser_tabs = [0, 1].map(|_| {
+            let mut tab =
+                TcpStream::connect((Ipv4Addr::LOCALHOST, port)).expect("failed to connect");
+            tab.write_all(callback.as_bytes())
+                .expect("failed to send the callback");
+            tab
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        let (mut stream, url) =
+            accept_callback(&transport, &AtomicBool::new(false), Some(deadline))
+                .expect("the first callback was not accepted");
+        assert_eq!(url.query(), Some("code=abc&state=xyz"));
+        write_page(
+            &mut stream,
+            &DefaultSuccessPageRenderer::default().render(None),
+        )
+        .expect("failed to answer the first callback");
+        drop(stream);
+        answer_repeat_callbacks(&transport);
+
+        let mut first = String::new();
+        browser_tabs[0]
+            .read_to_string(&mut first)
+            .expect("failed to read the first response");
+        let mut second = String::new();
+        browser_tabs[1]
+            .read_to_string(&mut second)
+            .expect("failed to read the second response");
+        assert!(!first.contains(SIGN_IN_ALREADY_COMPLETED_RESPONSE));
+        assert!(second.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(second.contains(SIGN_IN_ALREADY_COMPLETED_RESPONSE));
+    }
+
+    #[test]
+    fn empty_id_token_is_treated_as_missing() {
+        let response = token_response(
+            r#"{"access_token":"access","token_type":"Bearer","expires_in":3599,"id_token":""}"#,
+        );
+
+        assert_eq!(convert_token_response(&response, &[]).id_token, None);
+    }
+
+    #[test]
+    fn response_without_an_id_token_has_none() {
+        let response = token_response(r#"{"access_token":"access","token_type":"Bearer"}"#);
+
+        assert_eq!(convert_token_response(&response, &[]).id_token, None);
+    }
+
+    #[test]
+    fn id_token_is_passed_through() {
+        let response = token_response(
+            r#"{"access_token":"access","token_type":"Bearer","id_token":"header.payload.signature"}"#,
+        );
+
+        assert_eq!(
+            convert_token_response(&response, &[]).id_token.as_deref(),
+            Some("header.payload.signature")
+        );
+    }
+
+    #[test]
+    fn account_id_is_the_subject_of_the_id_token() {
+        let id_token = crate::id_token::tests::unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "exp": 1_700_003_600,
+            "iat": 1_700_000_000,
+        }));
+        let response = token_response(&format!(
+            r#"{{"access_token":"access","token_type":"Bearer","id_token":"{id_token}"}}"#
+        ));
+
+        assert_eq!(
+            convert_token_response(&response, &[]).account_id.as_deref(),
+            Some("1234567890")
+        );
+    }
+
+    #[test]
+    fn response_without_an_id_token_has_no_account_id() {
+        let response = token_response(r#"{"access_token":"access","token_type":"Bearer"}"#);
+
+        assert_eq!(convert_token_response(&response, &[]).account_id, None);
+    }
+
+    #[test]
+    fn malformed_id_token_leaves_the_account_id_unset() {
+        let response = token_response(
+            r#"{"access_token":"access","token_type":"Bearer","id_token":"header.payload.signature"}"#,
+        );
+
+        assert_eq!(convert_token_response(&response, &[]).account_id, None);
+    }
+
+    /// A [`TokenStore`] keeping sessions in memory, shared with the test through clones.
+    #[derive(Clone, Default)]
+    struct InMemoryStore(Arc<Mutex<HashMap<String, crate::TokenResponse>>>);
+
+    impl InMemoryStore {
+        fn get(&self, account_id: &str) -> Option<crate::TokenResponse> {
+            self.0
+                .lock()
+                .expect("store poisoned")
+                .get(account_id)
+                .cloned()
+        }
+    }
+
+    impl TokenStore for InMemoryStore {
+        fn save(&self, account_id: &str, token: &crate::TokenResponse) -> crate::Result<()> {
+            self.0
+                .lock()
+                .expect("store poisoned")
+                .insert(account_id.to_string(), token.clone());
+            Ok(())
+        }
+
+        fn load(&self, account_id: &str) -> crate::Result<Option<crate::TokenResponse>> {
+            Ok(self.get(account_id))
+        }
+
+        fn delete(&self, account_id: &str) -> crate::Result<()> {
+            self.0.lock().expect("store poisoned").remove(account_id);
+            Ok(())
+        }
+    }
+
+    struct FailingStore;
+
+    impl TokenStore for FailingStore {
+        fn save(&self, _: &str, _: &crate::TokenResponse) -> crate::Result<()> {
+            Err(std::io::Error::other("keychain locked").into())
+        }
+
+        fn load(&self, _: &str) -> crate::Result<Option<crate::TokenResponse>> {
+            Ok(None)
+        }
+
+        fn delete(&self, _: &str) -> crate::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn accounts(store: impl TokenStore + 'static) -> Accounts {
+        Accounts {
+            current: Mutex::default(),
+            store: Some(Box::new(store)),
+        }
+    }
+
+    fn session(sub: &str) -> crate::TokenResponse {
+        serde_json::from_value(serde_json::json!({
+            "accessToken": format!("access-{sub}"),
+            "refreshToken": format!("refresh-{sub}"),
+            "scopes": ["openid", "email"],
+            "isOffline": true,
+            "profile": { "sub": sub, "email": format!("{sub}@example.com") },
+        }))
+        .expect("invalid test session")
+    }
+
+    fn current_sub(accounts: &Accounts) -> Option<String> {
+        accounts
+            .current
+            .lock()
+            .expect("accounts poisoned")
+            .as_ref()
+            .map(|user| user.sub.clone())
+    }
+
+    #[test]
+    fn signed_in_sessions_are_saved_under_their_account_id() {
+        let store = InMemoryStore::default();
+        let accounts = accounts(store.clone());
+        let mut response = session("alice");
+
+        accounts.remember(&mut response);
+
+        assert_eq!(response.account_id.as_deref(), Some("alice"));
+        let saved = store.get("alice").expect("session was not saved");
+        assert_eq!(saved.refresh_token.as_deref(), Some("refresh-alice"));
+        assert_eq!(current_sub(&accounts).as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn saved_sessions_are_loaded_back() {
+        let store = InMemoryStore::default();
+        accounts(store.clone()).remember(&mut session("alice"));
+
+        // A new instance, as after an app restart
+        let loaded = accounts(store)
+            .load("alice")
+            .expect("load failed")
+            .expect("session was not found");
+
+        assert_eq!(loaded.refresh_token.as_deref(), Some("refresh-alice"));
+        assert_eq!(loaded.scopes, ["openid", "email"]);
+        assert_eq!(
+            loaded.profile.map(|user| user.sub).as_deref(),
+            Some("alice")
+        );
+    }
+
+    #[test]
+    fn unknown_accounts_load_nothing() {
+        let accounts = accounts(InMemoryStore::default());
+
+        assert!(accounts.load("bob").expect("load failed").is_none());
+    }
+
+    #[test]
+    fn signing_out_deletes_the_stored_session() {
+        let store = InMemoryStore::default();
+        let accounts = accounts(store.clone());
+        accounts.remember(&mut session("alice"));
+
+        accounts.forget(None).expect("forget failed");
+
+        assert!(store.get("alice").is_none());
+        assert!(current_sub(&accounts).is_none());
+    }
+
+    #[test]
+    fn forgetting_another_account_keeps_the_signed_in_user() {
+        let store = InMemoryStore::default();
+        let accounts = accounts(store.clone());
+        accounts.remember(&mut session("bob"));
+        accounts.remember(&mut session("alice"));
+
+        accounts.forget(Some("bob")).expect("forget failed");
+
+        assert!(store.get("bob").is_none());
+        assert!(store.get("alice").is_some());
+        assert_eq!(current_sub(&accounts).as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn failed_saves_are_reported_as_warnings() {
+        let accounts = accounts(FailingStore);
+        let mut response = session("alice");
+
+        accounts.remember(&mut response);
+
+        assert_eq!(response.warnings.len(), 1);
+        assert!(response.warnings[0].contains("keychain locked"));
+        assert_eq!(current_sub(&accounts).as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn stored_sessions_round_trip_through_json() {
+        let mut response = session("alice");
+        response.account_id = Some("alice".to_string());
+
+        let json = serde_json::to_string(&response).expect("serialization failed");
+        let restored: crate::TokenResponse =
+            serde_json::from_str(&json).expect("deserialization failed");
+
+        assert_eq!(restored.refresh_token, response.refresh_token);
+        assert_eq!(restored.account_id, response.account_id);
+        assert_eq!(restored.profile, response.profile);
+    }
+
+    /// A token endpoint answering with the queued statuses and JSON bodies in order,
+    /// recording the requested URLs.
+    struct FakeEndpoint {
+        responses: Mutex<VecDeque<(u16, &'static str)>>,
+        requested: Mutex<Vec<String>>,
+    }
+
+    impl FakeEndpoint {
+        fn new(responses: &[(u16, &'static str)]) -> Self {
+            Self {
+                responses: Mutex::new(responses.iter().copied().collect()),
+                requested: Mutex::default(),
+            }
+        }
+
+        fn respond(
+            &self,
+            request: &HttpRequest,
+        ) -> std::future::Ready<Result<HttpResponse, HttpError>> {
+            self.requested
+                .lock()
+                .expect("endpoint poisoned")
+                .push(request.uri().to_string());
+            let (status, body) = self
+                .responses
+                .lock()
+                .expect("endpoint poisoned")
+                .pop_front()
+                .expect("no response left");
+            std::future::ready(Ok(oauth2::http::Response::builder()
+                .status(status)
+                .header("content-type", "application/json")
+                .body(body.as_bytes().to_vec())
+                .expect("invalid test response")))
+        }
+    }
+
+    const DEVICE_CODE: (u16, &str) = (
+        200,
+        r#"{"device_code":"dc","user_code":"ABCD-EFGH","verification_url":"https://www.google.com/device","expires_in":1800,"interval":5}"#,
+    );
+    const PENDING: (u16, &str) = (428, r#"{"error":"authorization_pending"}"#);
+    const SLOW_DOWN: (u16, &str) = (403, r#"{"error":"slow_down"}"#);
+    const TOKENS: (u16, &str) = (
+        200,
+        r#"{"access_token":"at","token_type":"Bearer","expires_in":3599,"refresh_token":"rt"}"#,
+    );
+
+    /// Requests a device code from `endpoint`, then polls it for tokens, returning
+    /// the result and the waits between the polls.
+    fn device_sign_in(
+        endpoint: &FakeEndpoint,
+    ) -> (crate::Result<SpecialTokenResponse>, Vec<Duration>) {
+        let client = SpecialClient::new(ClientId::new("client-id".to_string()))
+            .set_device_authorization_url(
+                DeviceAuthorizationUrl::new(GOOGLE_DEVICE_CODE_URL.to_string())
+                    .expect("invalid device code URL"),
+            )
+            .set_token_uri(TokenUrl::new(GOOGLE_TOKEN_URL.to_string()).expect("invalid token URL"));
+        let http_client = |request: HttpRequest| endpoint.respond(&request);
+        let slept = Mutex::new(Vec::new());
+        let sleep = |interval| {
+            slept.lock().expect("sleep poisoned").push(interval);
+            std::future::ready(())
+        };
+
+        let result = tauri::async_runtime::block_on(async {
+            let details: StandardDeviceAuthorizationResponse = client
+                .exchange_device_code()
+                .request_async(&http_client)
+                .await
+                .expect("device code was not issued");
+            assert_eq!(details.user_code().secret(), "ABCD-EFGH");
+            assert_eq!(
+                details.verification_uri().as_str(),
+                "https://www.google.com/device"
+            );
+            poll_device_token(&client, &details, &http_client, sleep, None).await
+        });
+        (result, slept.into_inner().expect("sleep poisoned"))
+    }
+
+    #[test]
+    fn device_sign_in_polls_until_approved() {
+        let endpoint = FakeEndpoint::new(&[DEVICE_CODE, PENDING, PENDING, TOKENS]);
+
+        let (result, slept) = device_sign_in(&endpoint);
+
+        let tokens = result.expect("sign-in failed");
+        assert_eq!(tokens.access_token().secret(), "at");
+        assert_eq!(slept, [Duration::from_secs(5); 2]);
+        let requested = endpoint.requested.into_inner().expect("endpoint poisoned");
+        assert_eq!(requested[0], GOOGLE_DEVICE_CODE_URL);
+        assert!(requested[1..].iter().all(|url| url == GOOGLE_TOKEN_URL));
+    }
+
+    #[test]
+    fn slow_down_increases_the_poll_interval() {
+        let endpoint = FakeEndpoint::new(&[DEVICE_CODE, PENDING, SLOW_DOWN, PENDING, TOKENS]);
+
+        let (result, slept) = device_sign_in(&endpoint);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            slept,
+            [5, 10, 10].map(Duration::from_secs),
+            "slow_down must add five seconds to this and later polls"
+        );
+    }
+
+    #[test]
+    fn expired_device_code_fails_the_sign_in() {
+        let endpoint =
+            FakeEndpoint::new(&[DEVICE_CODE, PENDING, (400, r#"{"error":"expired_token"}"#)]);
+
+        let (result, _) = device_sign_in(&endpoint);
+
+        assert!(
+            matches!(result, Err(crate::Error::AuthenticationFailed(message)) if message.contains("expired"))
+        );
+    }
+
+    #[test]
+    fn denied_device_sign_in_is_cancelled() {
+        let endpoint =
+            FakeEndpoint::new(&[DEVICE_CODE, PENDING, (403, r#"{"error":"access_denied"}"#)]);
+
+        let (result, _) = device_sign_in(&endpoint);
+
+        assert!(matches!(result, Err(crate::Error::UserCancelled)));
+    }
+
+    #[test]
+    fn polling_keeps_going_through_pending_and_slow_down_until_denied() {
+        let endpoint = FakeEndpoint::new(&[
+            DEVICE_CODE,
+            PENDING,
+            SLOW_DOWN,
+            PENDING,
+            (403, r#"{"error":"access_denied"}"#),
+        ]);
+
+        let (result, slept) = device_sign_in(&endpoint);
+
+        assert!(matches!(result, Err(crate::Error::UserCancelled)));
+        assert_eq!(slept, [5, 10, 10].map(Duration::from_secs));
+    }
+
+    #[test]
+    fn other_poll_errors_end_the_sign_in() {
+        let endpoint = FakeEndpoint::new(&[DEVICE_CODE, (400, r#"{"error":"invalid_grant"}"#)]);
+
+        let (result, slept) = device_sign_in(&endpoint);
+
+        assert!(
+            matches!(result, Err(crate::Error::AuthenticationFailed(message)) if message.contains("invalid_grant"))
+        );
+        assert!(slept.is_empty());
+    }
+
+    #[test]
+    fn rejected_client_fails_device_polling() {
+        let endpoint = FakeEndpoint::new(&[DEVICE_CODE, (401, r#"{"error":"invalid_client"}"#)]);
+
+        let (result, _) = device_sign_in(&endpoint);
+
+        assert!(matches!(result, Err(crate::Error::InvalidClientId)));
+    }
+
+    #[test]
+    fn client_secret_authenticates_the_refresh() {
+        let server = MockServer::start(&[TOKENS]);
+        let (_app, auth) = google_auth();
+
+        auth.refresh_token(refresh_request(
+            &server.url("/token"),
+            &serde_json::json!({ "clientSecret": "secret" }),
+        ))
+        .expect("refresh failed");
+
+        let requests = server.requests();
+        assert!(requests[0].line.starts_with("POST /token "));
+        assert!(
+            requests[0]
+                .header("authorization")
+                .is_some_and(|value| value.starts_with("Basic "))
+        );
+        assert_eq!(requests[0].form_param("client_secret"), None);
+    }
+
+    #[test]
+    fn public_client_refreshes_without_a_secret() {
+        let server = MockServer::start(&[TOKENS]);
+        let (_app, auth) = google_auth();
+
+        auth.refresh_token(refresh_request(
+            &server.url("/token"),
+            &serde_json::json!({}),
+        ))
+        .expect("refresh failed");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("authorization"), None);
+        assert_eq!(
+            requests[0].form_param("client_id").as_deref(),
+            Some("client-id")
+        );
+        assert_eq!(
+            requests[0].form_param("refresh_token").as_deref(),
+            Some("refresh-token")
+        );
+    }
+
+    const REVOKED: (u16, &str) = (200, "{}");
+
+    /// Signs out with `tokens` against a revocation endpoint answering `responses`,
+    /// returning the result and the revoked tokens.
+    fn sign_out(
+        tokens: &serde_json::Value,
+        responses: &[(u16, &str)],
+    ) -> (crate::Result<SignOutResponse>, Vec<String>) {
+        let server = MockServer::start(responses);
+        let (_app, auth) = google_auth();
+
+        let result = auth.sign_out(sign_out_request(&server.url("/revoke"), tokens));
+
+        let revoked = server
+            .requests()
+            .iter()
+            .filter_map(|request| request.form_param("token"))
+            .collect();
+        (result, revoked)
+    }
+
+    #[test]
+    fn sign_out_revokes_the_access_token() {
+        let (result, revoked) = sign_out(&serde_json::json!({ "accessToken": "at" }), &[REVOKED]);
+
+        let response = result.expect("sign-out failed");
+        assert!(response.access_token_revoked);
+        assert!(!response.refresh_token_revoked);
+        assert_eq!(revoked, ["at"]);
+    }
+
+    #[test]
+    fn sign_out_revokes_the_refresh_token() {
+        let (result, revoked) = sign_out(&serde_json::json!({ "refreshToken": "rt" }), &[REVOKED]);
+
+        let response = result.expect("sign-out failed");
+        assert!(!response.access_token_revoked);
+        assert!(response.refresh_token_revoked);
+        assert_eq!(revoked, ["rt"]);
+    }
+
+    #[test]
+    fn sign_out_revokes_both_tokens() {
+        let (result, revoked) = sign_out(
+            &serde_json::json!({ "accessToken": "at", "refreshToken": "rt" }),
+            &[REVOKED, REVOKED],
+        );
+
+        let response = result.expect("sign-out failed");
+        assert!(response.access_token_revoked);
+        assert!(response.refresh_token_revoked);
+        assert_eq!(revoked, ["at", "rt"]);
+    }
+
+    #[test]
+    fn sign_out_without_tokens_is_local() {
+        let (result, revoked) = sign_out(&serde_json::json!({}), &[]);
+
+        let response = result.expect("sign-out failed");
+        assert!(response.success);
+        assert!(!response.access_token_revoked);
+        assert!(!response.refresh_token_revoked);
+        assert!(revoked.is_empty());
+    }
+
+    const INVALID_TOKEN: (u16, &str) = (400, r#"{"error":"invalid_token"}"#);
+
+    #[test]
+    fn lenient_sign_out_succeeds_despite_a_failed_revocation() {
+        let (result, _) = sign_out(
+            &serde_json::json!({ "accessToken": "at" }),
+            &[INVALID_TOKEN],
+        );
+
+        let response = result.expect("sign-out failed");
+        assert!(response.success);
+        assert!(!response.access_token_revoked);
+    }
+
+    #[test]
+    fn strict_sign_out_reports_a_failed_revocation() {
+        let (result, _) = sign_out(
+            &serde_json::json!({ "accessToken": "at", "strict": true }),
+            &[INVALID_TOKEN],
+        );
+
+        assert!(
+            matches!(result, Err(crate::Error::HttpStatus { status: 400, body }) if body.contains("invalid_token"))
+        );
+    }
+
+    fn refresh(responses: &[(u16, &str)]) -> crate::Result<crate::TokenResponse> {
+        let server = MockServer::start(responses);
+        let (_app, auth) = google_auth();
+        auth.refresh_token(refresh_request(
+            &server.url("/token"),
+            &serde_json::json!({}),
+        ))
+    }
+
+    #[test]
+    fn unauthorized_token_response_keeps_its_status() {
+        let result = refresh(&[(401, r#"{"error":"unauthorized_client"}"#)]);
+
+        assert!(
+            matches!(result, Err(crate::Error::HttpStatus { status: 401, body }) if body.contains("unauthorized_client"))
+        );
+    }
+
+    #[test]
+    fn non_json_error_response_keeps_its_status() {
+        let result = refresh(&[(401, "<html>Unauthorized</html>")]);
+
+        assert!(matches!(
+            result,
+            Err(crate::Error::HttpStatus { status: 401, .. })
+        ));
+    }
+
+    #[test]
+    fn unreachable_token_endpoint_is_a_network_error() {
+        let result = refresh(&[]);
+
+        assert!(matches!(result, Err(crate::Error::NetworkError(_))));
+    }
+
+    #[test]
+    fn userinfo_is_parsed_into_a_profile() {
+        let body = br#"{
+            "sub": "1234567890",
+            "name": "Jane Doe",
+            "given_name": "Jane",
+            "family_name": "Doe",
+            "picture": "https://lh3.googleusercontent.com/a/photo",
+            "email": "jane@example.com",
+            "email_verified": true,
+            "locale": "en"
+        }"#;
+
+        let profile =
+            parse_profile(oauth2::http::StatusCode::OK, body).expect("profile was not parsed");
+
+        assert_eq!(
+            profile,
+            UserProfile {
+                sub: "1234567890".to_string(),
+                email: Some("jane@example.com".to_string()),
+                email_verified: Some(true),
+                name: Some("Jane Doe".to_string()),
+                given_name: Some("Jane".to_string()),
+                family_name: Some("Doe".to_string()),
+                picture: Some("https://lh3.googleusercontent.com/a/photo".to_string()),
+                locale: Some("en".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn failed_userinfo_request_keeps_its_status() {
+        let result = parse_profile(
+            oauth2::http::StatusCode::UNAUTHORIZED,
+            br#"{"error":"invalid_request"}"#,
+        );
+
+        assert!(matches!(
+            result,
+            Err(crate::Error::HttpStatus { status: 401, .. })
+        ));
+    }
+
+    /// A session of `sub` whose access token expires `expires_in` seconds from now.
+    fn session_expiring_in(sub: &str, expires_in: i64) -> crate::TokenResponse {
+        let mut token = session(sub);
+        token.expires_at = Some(
+            i64::try_from(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .expect("clock before 1970")
+                    .as_secs(),
+            )
+            .expect("clock out of range")
+                + expires_in,
+        );
+        token
+    }
+
+    #[test]
+    fn valid_token_is_returned_unchanged() {
+        let server = MockServer::start(&[]);
+        let (_app, auth) = google_auth();
+
+        let token = auth
+            .ensure_valid_token(
+                session_expiring_in("alice", 3600),
+                refresh_request(&server.url("/token"), &serde_json::json!({})),
+            )
+            .expect("valid token was rejected");
+
+        assert_eq!(token.access_token.as_deref(), Some("access-alice"));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn expired_token_is_refreshed_with_its_refresh_token() {
+        let server = MockServer::start(&[TOKENS]);
+        let (_app, auth) = google_auth();
+
+        let token = auth
+            .ensure_valid_token(
+                session_expiring_in("alice", 30),
+                refresh_request(
+                    &server.url("/token"),
+                    &serde_json::json!({ "refreshToken": null }),
+                ),
+            )
+            .expect("refresh failed");
+
+        assert_eq!(token.access_token.as_deref(), Some("at"));
+        assert_eq!(token.scopes, ["openid", "email"]);
+        assert_eq!(
+            server.requests()[0].form_param("refresh_token").as_deref(),
+            Some("refresh-alice")
+        );
+    }
+
+    #[test]
+    fn expired_token_without_a_refresh_token_fails() {
+        let server = MockServer::start(&[]);
+        let (_app, auth) = google_auth();
+        let mut token = session_expiring_in("alice", -60);
+        token.refresh_token = None;
+
+        let result = auth.ensure_valid_token(
+            token,
+            refresh_request(
+                &server.url("/token"),
+                &serde_json::json!({ "refreshToken": null }),
+            ),
+        );
+
+        assert!(matches!(result, Err(crate::Error::TokenRefreshFailed(_))));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn clients_are_built_with_a_proxy() {
+        let http = HttpSettings::new(Some("http://proxy.example.com:3128"), true)
+            .expect("proxy URL was rejected");
+
+        assert!(
+            http.blocking(oauth2::reqwest::blocking::Client::builder())
+                .build()
+                .is_ok()
+        );
+        assert!(
+            http.nonblocking(oauth2::reqwest::Client::builder())
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn invalid_proxy_url_is_a_configuration_error() {
+        let result = HttpSettings::new(Some("not a proxy"), false);
+
+        assert!(
+            matches!(result, Err(crate::Error::ConfigurationError { field: Some(field), .. }) if field == "proxy_url")
+        );
+    }
+
+    #[test]
+    fn token_requests_go_through_the_proxy() {
+        let proxy = MockServer::start(&[TOKENS]);
+        let (_app, auth) = google_auth();
+
+        auth.refresh_token(refresh_request(
+            "http://oauth2.example.invalid/token",
+            &serde_json::json!({ "proxyUrl": proxy.url("") }),
+        ))
+        .expect("refresh through the proxy failed");
+
+        assert!(
+            proxy.requests()[0]
+                .line
+                .starts_with("POST http://oauth2.example.invalid/token ")
+        );
+    }
+
+    #[test]
+    fn stalled_token_endpoint_times_out() {
+        // Connections are queued by the OS but never answered
+        let stalled =
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("failed to bind");
+        let url = format!(
+            "http://{}/token",
+            stalled.local_addr().expect("no local address")
+        );
+        let (_app, auth) = google_auth();
+        let started = Instant::now();
+
+        let result = auth.refresh_token(refresh_request(
+            &url,
+            &serde_json::json!({ "timeoutSeconds": 1 }),
+        ));
+
+        assert!(matches!(result, Err(crate::Error::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn unroutable_token_endpoint_fails_within_the_connect_timeout() {
+        let (_app, auth) = google_auth();
+        let started = Instant::now();
+
+        let result = auth.refresh_token(refresh_request(
+            "http://10.255.255.1/token",
+            &serde_json::json!({ "connectTimeoutSeconds": 1 }),
+        ));
+
+        // Depending on the network the connection times out or is refused outright
+        assert!(result.as_ref().is_err_and(crate::Error::is_transient));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    const UNAVAILABLE: (u16, &str) = (503, r#"{"error":"temporarily_unavailable"}"#);
+
+    /// Refreshes with up to `max_retries` retries against an endpoint answering
+    /// `responses`, returning the result and how many requests were made.
+    fn refresh_with_retries(
+        max_retries: u32,
+        responses: &[(u16, &str)],
+    ) -> (crate::Result<crate::TokenResponse>, usize) {
+        let server = MockServer::start(responses);
+        let (_app, auth) = google_auth();
+        let result = auth.refresh_token(refresh_request(
+            &server.url("/token"),
+            &serde_json::json!({ "maxRetries": max_retries, "retryBaseDelayMs": 1 }),
+        ));
+        (result, server.requests().len())
+    }
+
+    #[test]
+    fn server_errors_are_retried_until_the_refresh_succeeds() {
+        let (result, requests) = refresh_with_retries(2, &[UNAVAILABLE, UNAVAILABLE, TOKENS]);
+
+        assert_eq!(
+            result.expect("refresh failed").access_token.as_deref(),
+            Some("at")
+        );
+        assert_eq!(requests, 3);
+    }
+
+    #[test]
+    fn retries_give_up_after_max_retries() {
+        let (result, requests) = refresh_with_retries(1, &[UNAVAILABLE, UNAVAILABLE, TOKENS]);
+
+        assert!(matches!(
+            result,
+            Err(crate::Error::HttpStatus { status: 503, .. })
+        ));
+        assert_eq!(requests, 2);
+    }
+
+    #[test]
+    fn client_errors_are_never_retried() {
+        let (result, requests) =
+            refresh_with_retries(2, &[(400, r#"{"error":"invalid_grant"}"#), TOKENS]);
+
+        assert!(matches!(result, Err(crate::Error::InvalidGrant(_))));
+        assert_eq!(requests, 1);
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially_with_jitter() {
+        let retry = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+        };
+
+        for (attempt, backoff) in [(0, 100), (1, 200), (2, 400)] {
+            let delay = retry.delay(attempt);
+            assert!(delay >= Duration::from_millis(backoff));
+            assert!(delay <= Duration::from_millis(backoff * 3 / 2));
+        }
+    }
+
+    #[test]
+    fn granted_scopes_come_from_the_scope_field() {
+        let response = token_response(
+            r#"{"access_token":"at","token_type":"Bearer","scope":"openid https://www.googleapis.com/auth/drive.file"}"#,
+        );
+
+        let token = convert_token_response(&response, &["openid".to_string()]);
+
+        assert_eq!(
+            token.scopes,
+            ["openid", "https://www.googleapis.com/auth/drive.file"]
+        );
+    }
+
+    #[test]
+    fn response_without_a_scope_field_granted_the_requested_scopes() {
+        let response = token_response(r#"{"access_token":"at","token_type":"Bearer"}"#);
+
+        let token = convert_token_response(&response, &["openid".to_string(), "email".to_string()]);
+
+        assert_eq!(token.scopes, ["openid", "email"]);
+    }
+
+    #[test]
+    fn refresh_carries_the_scopes_forward() {
+        let server = MockServer::start(&[TOKENS]);
+        let (_app, auth) = google_auth();
+
+        let token = auth
+            .refresh_token(refresh_request(
+                &server.url("/token"),
+                &serde_json::json!({ "scopes": ["openid", "email"] }),
+            ))
+            .expect("refresh failed");
+
+        assert_eq!(token.scopes, ["openid", "email"]);
+    }
+
+    const TOKEN_INFO: &[u8] = br#"{
+        "azp": "client-id",
+        "aud": "client-id",
+        "sub": "1234567890",
+        "scope": "openid https://www.googleapis.com/auth/userinfo.email",
+        "exp": "1700003600",
+        "expires_in": "3599",
+        "email": "jane@example.com",
+        "email_verified": "true",
+        "access_type": "offline"
+    }"#;
+
+    #[test]
+    fn tokeninfo_is_parsed_into_an_introspection() {
+        let info = parse_token_info(oauth2::http::StatusCode::OK, TOKEN_INFO, "client-id")
+            .expect("tokeninfo was not parsed");
+
+        assert_eq!(
+            info,
+            TokenIntrospection {
+                active: true,
+                scope: Some("openid https://www.googleapis.com/auth/userinfo.email".to_string()),
+                exp: Some(1_700_003_600),
+                aud: Some("client-id".to_string()),
+                email: Some("jane@example.com".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn token_of_another_client_is_inactive() {
+        let info = parse_token_info(oauth2::http::StatusCode::OK, TOKEN_INFO, "other-client")
+            .expect("tokeninfo was not parsed");
+
+        assert!(!info.active);
+    }
+
+    #[test]
+    fn invalid_token_is_inactive_rather_than_an_error() {
+        let info = parse_token_info(
+            oauth2::http::StatusCode::BAD_REQUEST,
+            br#"{"error":"invalid_token","error_description":"Invalid Value"}"#,
+            "client-id",
+        )
+        .expect("invalid token was an error");
+
+        assert_eq!(info, TokenIntrospection::default());
+    }
+
+    #[test]
+    fn expires_in_is_kept_next_to_the_computed_expiry() {
+        let before = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock before 1970")
+            .as_secs();
+        let response =
+            token_response(r#"{"access_token":"at","token_type":"Bearer","expires_in":3599}"#);
+
+        let token = convert_token_response(&response, &[]);
+
+        assert_eq!(token.expires_in_seconds, Some(3599));
+        let expires_at = token.expires_at.expect("no expiry was computed");
+        let before = i64::try_from(before).expect("clock out of range");
+        assert!((before + 3599..=before + 3601).contains(&expires_at));
+        assert!(token.expires_at_iso.is_some());
+    }
+
+    #[test]
+    fn response_without_expires_in_has_no_expiry() {
+        let response = token_response(r#"{"access_token":"at","token_type":"Bearer"}"#);
+
+        let token = convert_token_response(&response, &[]);
+
+        assert_eq!(token.expires_in_seconds, None);
+        assert_eq!(token.expires_at, None);
+        assert_eq!(token.expires_at_iso, None);
+    }
+
+    #[test]
+    fn refresh_reports_expires_in() {
+        let token = refresh(&[TOKENS]).expect("refresh failed");
+
+        assert_eq!(token.expires_in_seconds, Some(3599));
+    }
+
+    #[test]
+    fn rotated_refresh_token_replaces_the_old_one() {
+        let token = refresh(&[TOKENS]).expect("refresh failed");
+
+        assert_eq!(token.refresh_token.as_deref(), Some("rt"));
+        assert!(token.is_offline);
+    }
+
+    #[test]
+    fn refresh_token_is_kept_when_not_rotated() {
+        let token = refresh(&[(
+            200,
+            r#"{"access_token":"at","token_type":"Bearer","expires_in":3599}"#,
+        )])
+        .expect("refresh failed");
+
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh-token"));
+        assert!(token.is_offline);
+    }
+
+    #[test]
+    fn revoked_refresh_token_is_an_invalid_grant() {
+        let result = refresh(&[(
+            400,
+            r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#,
+        )]);
+
+        assert!(
+            matches!(result, Err(crate::Error::InvalidGrant(description)) if description == "Token has been expired or revoked.")
+        );
+    }
+
+    #[test]
+    fn rejected_client_fails_the_refresh() {
+        let result = refresh(&[(
+            401,
+            r#"{"error":"invalid_client","error_description":"The OAuth client was not found."}"#,
+        )]);
+
+        assert!(matches!(result, Err(crate::Error::InvalidClientId)));
+    }
+
+    fn token_error(error: BasicErrorResponseType, failed: Option<(u16, String)>) -> RefreshResult {
+        Err((
+            RequestTokenError::ServerResponse(BasicErrorResponse::new(error, None, None)),
+            failed,
+        ))
+    }
+
+    #[test]
+    fn refresh_token_is_valid_when_the_refresh_succeeds() {
+        let result = refresh_validity(Ok(token_response(
+            r#"{"access_token":"access","token_type":"Bearer"}"#,
+        )));
+
+        assert!(result.expect("validation failed"));
+    }
+
+    #[test]
+    fn rejected_grant_means_the_refresh_token_is_invalid() {
+        let result = refresh_validity(token_error(
+            BasicErrorResponseType::InvalidGrant,
+            Some((400, r#"{"error":"invalid_grant"}"#.to_string())),
+        ));
+
+        assert!(!result.expect("validation failed"));
+    }
+
+    #[test]
+    fn other_refresh_failures_are_mapped_like_a_refresh() {
+        let rejected_client = refresh_validity(token_error(
+            BasicErrorResponseType::InvalidClient,
+            Some((401, r#"{"error":"invalid_client"}"#.to_string())),
+        ));
+        let unauthorized = refresh_validity(token_error(
+            BasicErrorResponseType::UnauthorizedClient,
+            Some((400, r#"{"error":"unauthorized_client"}"#.to_string())),
+        ));
+
+        assert!(matches!(
+            rejected_client,
+            Err(crate::Error::InvalidClientId)
+        ));
+        assert!(matches!(
+            unauthorized,
+            Err(crate::Error::HttpStatus { status: 400, .. })
+        ));
+    }
+
+    #[test]
+    fn refresh_token_validation_goes_through_the_proxy() {
+        let proxy = MockServer::start(&[(502, "")]);
+        let (_app, auth) = google_auth();
+
+        let result = auth.validate_refresh_token(
+            "refresh-token".to_string(),
+            "client-id".to_string(),
+            "client-secret".to_string(),
+            &NetworkSettings {
+                proxy_url: Some(proxy.url("")),
+                ..NetworkSettings::default()
+            },
+        );
+
+        assert!(matches!(result, Err(crate::Error::NetworkError(_))));
+        assert!(
+            proxy.requests()[0]
+                .line
+                .starts_with("CONNECT oauth2.googleapis.com:443 ")
+        );
+    }
+
+    #[test]
+    fn token_introspection_goes_through_the_proxy() {
+        let proxy = MockServer::start(&[(502, "")]);
+        let (_app, auth) = google_auth();
+
+        let result = auth.introspect_token(
+            "access-token",
+            "client-id",
+            &NetworkSettings {
+                proxy_url: Some(proxy.url("")),
+                ..NetworkSettings::default()
+            },
+        );
+
+        assert!(matches!(result, Err(crate::Error::NetworkError(_))));
+        assert!(
+            proxy.requests()[0]
+                .line
+                .starts_with("CONNECT oauth2.googleapis.com:443 ")
+        );
+    }
+
+    #[test]
+    fn token_introspection_rejects_an_invalid_proxy() {
+        let (_app, auth) = google_auth();
+
+        let result = auth.introspect_token(
+            "access-token",
+            "client-id",
+            &NetworkSettings {
+                proxy_url: Some("not a proxy".to_string()),
+                ..NetworkSettings::default()
+            },
+        );
+
+        assert!(matches!(
+            result,
+            Err(crate::Error::ConfigurationError { field: Some(field), .. }) if field == "proxy_url"
+        ));
+    }
+
+    fn device_request(overrides: &serde_json::Value) -> DeviceSignInRequest {
+        let mut request = serde_json::json!({
+            "clientId": "client-id",
+            "clientSecret": "client-secret",
+        });
+        request
+            .as_object_mut()
+            .expect("not an object")
+            .extend(overrides.as_object().expect("not an object").clone());
+        serde_json::from_value(request).expect("invalid device sign-in request")
+    }
+
+    #[test]
+    fn device_sign_in_uses_the_given_endpoints() {
+        let server = MockServer::start(&[
+            (
+                200,
+                r#"{"device_code":"dc","user_code":"ABCD-EFGH","verification_url":"https://www.google.com/device","expires_in":1800,"interval":1}"#,
+            ),
+            TOKENS,
+        ]);
+        let (_app, auth) = google_auth();
+
+        let response = tauri::async_runtime::block_on(auth.sign_in_device_code(device_request(
+            &serde_json::json!({
+                "deviceCodeUrl": server.url("/device/code"),
+                "tokenUrl": server.url("/token"),
+            }),
+        )))
+        .expect("sign-in failed");
+
+        assert_eq!(response.access_token.as_deref(), Some("at"));
+        let requests = server.requests();
+        assert!(requests[0].line.starts_with("POST /device/code "));
+        assert!(requests[1].line.starts_with("POST /token "));
+    }
+
+    #[test]
+    fn invalid_device_endpoints_are_configuration_errors() {
+        let (_app, auth) = google_auth();
+
+        for field in ["device_code_url", "token_url"] {
+            let overrides = if field == "token_url" {
+                serde_json::json!({ "tokenUrl": "not a url" })
+            } else {
+                serde_json::json!({ "deviceCodeUrl": "not a url" })
+            };
+            let result = tauri::async_runtime::block_on(
+                auth.sign_in_device_code(device_request(&overrides)),
+            );
+
+            assert!(
+                matches!(&result, Err(crate::Error::ConfigurationError { field: Some(f), .. }) if f == field),
+                "{field}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn device_sign_in_goes_through_the_proxy() {
+        let proxy = MockServer::start(&[(502, "")]);
+        let (_app, auth) = google_auth();
+
+        let result = tauri::async_runtime::block_on(auth.sign_in_device_code(device_request(
+            &serde_json::json!({ "proxyUrl": proxy.url("") }),
+        )));
+
+        assert!(matches!(result, Err(crate::Error::NetworkError(_))));
+        assert!(
+            proxy.requests()[0]
+                .line
+                .starts_with("CONNECT oauth2.googleapis.com:443 ")
+        );
+    }
 }
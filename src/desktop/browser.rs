@@ -0,0 +1,236 @@
+//! Opening the authorization URL in a private (incognito) browser window.
+//!
+//! Supported browsers are Chrome, Chromium, Brave and Edge (`--incognito` /
+//! `--inprivate`) and Firefox (`--private-window`). The default browser is
+//! detected through `xdg-settings` on Linux, the Launch Services preferences on
+//! macOS and the `https` URL association on Windows. Safari and other browsers
+//! have no command-line switch for private browsing and are reported as unknown.
+
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Browser {
+    Chrome,
+    Chromium,
+    Brave,
+    Edge,
+    Firefox,
+}
+
+impl Browser {
+    const fn private_flag(self) -> &'static str {
+        match self {
+            Self::Chrome | Self::Chromium | Self::Brave => "--incognito",
+            Self::Edge => "--inprivate",
+            Self::Firefox => "--private-window",
+        }
+    }
+}
+
+/// Opens `url` in a private window of the default browser.
+///
+/// Fails when the default browser can't be detected, has no known private mode,
+/// or could not be launched, so the caller can fall back to a normal window.
+pub fn open_private(url: &str) -> std::io::Result<()> {
+    let browser = default_browser().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Default browser does not support a private window",
+        )
+    })?;
+    launch(browser, url)
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn default_browser() -> Option<Browser> {
+    let desktop_entry = command_output("xdg-settings", &["get", "default-web-browser"])?;
+    let entry = desktop_entry.trim().to_ascii_lowercase();
+    if entry.contains("firefox") {
+        Some(Browser::Firefox)
+    } else if entry.contains("brave") {
+        Some(Browser::Brave)
+    } else if entry.contains("edge") {
+        Some(Browser::Edge)
+    } else if entry.contains("chromium") {
+        Some(Browser::Chromium)
+    } else if entry.contains("chrome") {
+        Some(Browser::Chrome)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn launch(browser: Browser, url: &str) -> std::io::Result<()> {
+    let candidates: &[&str] = match browser {
+        Browser::Chrome => &["google-chrome", "google-chrome-stable"],
+        Browser::Chromium => &["chromium", "chromium-browser"],
+        Browser::Brave => &["brave-browser", "brave"],
+        Browser::Edge => &["microsoft-edge", "microsoft-edge-stable"],
+        Browser::Firefox => &["firefox"],
+    };
+
+    let mut last_error = std::io::Error::from(std::io::ErrorKind::NotFound);
+    for program in candidates {
+        match spawn_detached(Command::new(program).arg(browser.private_flag()).arg(url)) {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(target_os = "macos")]
+const BUNDLE_IDS: &[(&str, Browser)] = &[
+    ("com.google.chrome", Browser::Chrome),
+    ("org.chromium.chromium", Browser::Chromium),
+    ("com.brave.browser", Browser::Brave),
+    ("com.microsoft.edgemac", Browser::Edge),
+    ("org.mozilla.firefox", Browser::Firefox),
+];
+
+#[cfg(target_os = "macos")]
+fn default_browser() -> Option<Browser> {
+    let handlers = command_output(
+        "defaults",
+        &[
+            "read",
+            "com.apple.LaunchServices/com.apple.launchservices.secure",
+            "LSHandlers",
+        ],
+    )?;
+
+    // Each handler is printed as a `{ ... }` block; find the one for https.
+    let bundle_id = handlers
+        .split('}')
+        .find(|block| block.contains("LSHandlerURLScheme = https;"))?
+        .lines()
+        .find_map(|line| {
+            line.trim()
+                .strip_prefix("LSHandlerRoleAll = ")
+                .map(|value| {
+                    value
+                        .trim_end_matches(';')
+                        .trim_matches('"')
+                        .to_ascii_lowercase()
+                })
+        })?;
+
+    BUNDLE_IDS
+        .iter()
+        .find(|(id, _)| *id == bundle_id)
+        .map(|(_, browser)| *browser)
+}
+
+#[cfg(target_os = "macos")]
+fn launch(browser: Browser, url: &str) -> std::io::Result<()> {
+    let bundle_id = BUNDLE_IDS
+        .iter()
+        .find(|(_, b)| *b == browser)
+        .map(|(id, _)| *id)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+    spawn_detached(
+        Command::new("open")
+            .args(["-n", "-b", bundle_id, "--args", browser.private_flag()])
+            .arg(url),
+    )
+}
+
+#[cfg(target_os = "windows")]
+const EXECUTABLES: &[(Browser, &str)] = &[
+    (Browser::Chrome, "chrome.exe"),
+    (Browser::Chromium, "chrome.exe"),
+    (Browser::Brave, "brave.exe"),
+    (Browser::Edge, "msedge.exe"),
+    (Browser::Firefox, "firefox.exe"),
+];
+
+#[cfg(target_os = "windows")]
+fn default_browser() -> Option<Browser> {
+    let output = command_output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\Shell\Associations\UrlAssociations\https\UserChoice",
+            "/v",
+            "ProgId",
+        ],
+    )?;
+    let prog_id = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("ProgId"))?
+        .split_whitespace()
+        .last()?
+        .to_ascii_lowercase();
+
+    if prog_id.starts_with("chromehtml") {
+        Some(Browser::Chrome)
+    } else if prog_id.starts_with("chromiumhtm") {
+        Some(Browser::Chromium)
+    } else if prog_id.starts_with("bravehtml") {
+        Some(Browser::Brave)
+    } else if prog_id.starts_with("msedgehtm") {
+        Some(Browser::Edge)
+    } else if prog_id.starts_with("firefoxurl") {
+        Some(Browser::Firefox)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn launch(browser: Browser, url: &str) -> std::io::Result<()> {
+    let executable = EXECUTABLES
+        .iter()
+        .find(|(b, _)| *b == browser)
+        .map(|(_, exe)| *exe)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+
+    // Resolve the full path through "App Paths" rather than going through
+    // `cmd /C start`, which would interpret `&` in the URL.
+    let path = ["HKCU", "HKLM"]
+        .iter()
+        .find_map(|hive| {
+            let key =
+                format!(r"{hive}\Software\Microsoft\Windows\CurrentVersion\App Paths\{executable}");
+            let output = command_output("reg", &["query", &key, "/ve"])?;
+            output
+                .lines()
+                .find_map(|line| line.split_once("REG_SZ"))
+                .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        })
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+
+    spawn_detached(Command::new(path).arg(browser.private_flag()).arg(url))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const fn default_browser() -> Option<Browser> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn launch(_browser: Browser, _url: &str) -> std::io::Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+fn spawn_detached(command: &mut Command) -> std::io::Result<()> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
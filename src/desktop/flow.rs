@@ -0,0 +1,2333 @@
+//! The desktop sign-in flow, modelled as explicit states that can be driven
+//! step by step.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+use oauth2::reqwest::header::HeaderMap;
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, CsrfToken, EndpointNotSet, EndpointSet,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, ResponseType, RevocationUrl, Scope, TokenUrl,
+};
+use tauri::{AppHandle, Emitter, Runtime, WebviewWindow};
+use url::Url;
+use uuid::Uuid;
+
+use super::jwks::KeyCache;
+use super::transport::{BindPort, CallbackTransport, TcpTransport};
+use super::{
+    ACCEPT_POLL_INTERVAL, Accounts, DEFAULT_BIND_ATTEMPTS, DEFAULT_REDIRECT_HOST, GOOGLE_AUTH_URL,
+    GOOGLE_REVOCATION_URL, GOOGLE_TOKEN_URL, GRANTED_SCOPES_EVENT, GoogleAuth, HttpSettings,
+    LOCALHOST_ADDR, LOCALHOST_V6_HOST, PortFallback, REDIRECT_URI_NORMALIZED_EVENT,
+    RecordingClient, SIGN_IN_FAILED_RESPONSE, SIGN_IN_STAGE_EVENT, SIGN_IN_STARTED_EVENT, Sessions,
+    SpecialClient, SpecialTokenResponse, StateCodec, accept_callback, accept_callback_async,
+    answer_async, answer_repeat_callbacks, browser, convert_token_response, fetch_profile,
+    fetch_profile_async, map_authorization_error, map_token_error, normalize_response_type,
+    outbound_headers, webview, with_client_secret, write_page,
+};
+use crate::models::{
+    AuthMode, ClientType, IdTokenClaims, IpVersion, PkceMethod, RedirectUriNormalized,
+    SignInProgress, SignInRequest, SignInStage, SignInStarted,
+};
+use crate::success_page::{DefaultSuccessPageRenderer, SuccessPage, SuccessPageRenderer};
+
+type ConfiguredClient =
+    SpecialClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointSet, EndpointSet>;
+
+/// The observable state of a [`SignInFlow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignInState {
+    /// The request is validated; nothing is bound or opened yet.
+    Building,
+    /// The loopback server is listening and the authorization URL is ready.
+    AwaitingCallback,
+    /// The callback arrived; the authorization code is ready to be exchanged.
+    Exchanging,
+    /// Tokens were obtained.
+    Complete,
+    /// A step failed; see [`SignInFlow::error`].
+    Failed,
+}
+
+/// Cancels a [`SignInFlow`] from elsewhere, e.g. while it is being run.
+///
+/// The pending callback wait or token exchange then fails with
+/// [`crate::Error::UserCancelled`], dropping the loopback server.
+#[derive(Clone)]
+pub struct SignInCancelHandle(Arc<AtomicBool>);
+
+impl SignInCancelHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the flow was cancelled, by this or any other handle or its session ID.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+struct Pending {
+    transport: TcpTransport,
+    client: ConfiguredClient,
+    authorize_url: Url,
+    csrf_state: CsrfToken,
+    pkce_verifier: Option<PkceCodeVerifier>,
+    nonce: String,
+}
+
+struct Redirect {
+    stream: TcpStream,
+    code: AuthorizationCode,
+    /// The state the callback was validated with.
+    state: String,
+    params: Option<HashMap<String, String>>,
+    fragment_id_token: Option<String>,
+}
+
+enum Stage {
+    Building,
+    AwaitingCallback(Box<Pending>),
+    Exchanging(Box<Pending>, Redirect),
+    Complete(Box<crate::TokenResponse>),
+    Failed(String),
+}
+
+/// A desktop sign-in driven one step at a time.
+///
+/// [`GoogleAuth::sign_in`](super::GoogleAuth::sign_in) runs all steps in order;
+/// driving them individually lets an app show its own UI at each stage:
+///
+/// 1. [`start`](Self::start) binds the loopback server and builds the authorization URL.
+/// 2. [`open_browser`](Self::open_browser) opens that URL (or show it to the user instead).
+/// 3. [`wait_for_callback`](Self::wait_for_callback) blocks until Google redirects back.
+/// 4. [`exchange`](Self::exchange) trades the code for tokens and answers the browser.
+///
+/// Steps 3 and 4 have async variants that don't block the thread, as used by
+/// [`run_async`](Self::run_async).
+///
+/// Any failing step moves the flow to [`SignInState::Failed`].
+pub struct SignInFlow<R: Runtime> {
+    app: AppHandle<R>,
+    session_id: String,
+    cancelled: Arc<AtomicBool>,
+    sessions: Sessions,
+    accounts: Arc<Accounts>,
+    request_id: String,
+    headers: HeaderMap,
+    http: HttpSettings,
+    normalized_from: Option<String>,
+    request: SignInRequest,
+    client_secret: Option<String>,
+    scopes: Vec<String>,
+    hybrid: bool,
+    redirect_host: String,
+    bind_addrs: Vec<IpAddr>,
+    port: Option<u16>,
+    success_page_renderer: Option<Arc<dyn SuccessPageRenderer>>,
+    state_codec: Option<Arc<StateCodec>>,
+    port_fallback: Option<Arc<PortFallback>>,
+    id_token_keys: Arc<KeyCache>,
+    webview: Option<WebviewWindow<R>>,
+    warnings: Vec<String>,
+    stage: Stage,
+}
+
+impl<R: Runtime> SignInFlow<R> {
+    pub(super) fn new(auth: &GoogleAuth<R>, mut request: SignInRequest) -> crate::Result<Self> {
+        // Fall back to the identity scopes when none are given
+        let scopes = request
+            .scopes
+            .take()
+            .unwrap_or_else(SignInRequest::default_scopes);
+
+        if scopes.is_empty() {
+            return Err(crate::Error::configuration(
+                Some("scopes"),
+                "Empty scopes array. At least one scope is required for authentication",
+            ));
+        }
+        let (mut scopes, warnings) = crate::scopes::normalize_scopes(scopes)?;
+
+        // Only the code flow and the hybrid `code id_token` flow are supported
+        let hybrid = match request
+            .response_type
+            .as_deref()
+            .map(normalize_response_type)
+            .as_deref()
+        {
+            None | Some("code") => false,
+            Some("code id_token") => true,
+            Some(other) => {
+                return Err(crate::Error::configuration(
+                    Some("response_type"),
+                    format!("Unsupported response type `{other}`. Use `code` or `code id_token`"),
+                ));
+            }
+        };
+
+        // Google only issues an ID token for the `openid` scope
+        if (hybrid || request.require_id_token || request.verify_id_token)
+            && !scopes.iter().any(|scope| scope == "openid")
+        {
+            scopes.push("openid".to_string());
+        }
+
+        validate_options(&request, &scopes)?;
+
+        let (redirect_host, port, normalized_from) =
+            parse_redirect_uri(request.redirect_uri.as_deref())?;
+        let (redirect_host, bind_addrs) = listen_addresses(&request, redirect_host)?;
+        if let Some(range) = request.port_range {
+            validate_port_range(range, port)?;
+        }
+
+        validate_client_type(request.client_type, port)?;
+        validate_server_client(&request)?;
+
+        // Desktop app clients are public and rely on PKCE alone; web clients can't
+        // exchange a code without their secret
+        let client_secret = request.client_secret.take();
+        if client_secret.is_none() && request.client_type == Some(ClientType::Web) {
+            return Err(crate::Error::configuration(
+                Some("client_secret"),
+                "Client secret is required for web application clients",
+            ));
+        }
+
+        let request_id = request
+            .request_id
+            .take()
+            .unwrap_or_else(|| Uuid::now_v7().to_string());
+        let headers = outbound_headers(&request_id, request.extra_headers.as_ref())?;
+        let http = HttpSettings::new(
+            request.proxy_url.as_deref(),
+            request.danger_accept_invalid_certs,
+        )?
+        .with_timeouts(
+            request.connect_timeout_seconds,
+            request.request_timeout_seconds,
+        )
+        .with_retries(request.max_retries, request.retry_base_delay_ms);
+
+        let session_id = Uuid::now_v7().to_string();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        auth.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(session_id.clone(), cancelled.clone());
+
+        Ok(Self {
+            app: auth.app.clone(),
+            session_id,
+            cancelled,
+            sessions: auth.sessions.clone(),
+            accounts: auth.accounts.clone(),
+            normalized_from,
+            request_id,
+            headers,
+            http,
+            request,
+            client_secret,
+            scopes,
+            hybrid,
+            redirect_host,
+            bind_addrs,
+            port,
+            success_page_renderer: auth.success_page_renderer.clone(),
+            state_codec: auth.state_codec.clone(),
+            port_fallback: auth.port_fallback.clone(),
+            id_token_keys: auth.id_token_keys.clone(),
+            webview: None,
+            warnings,
+            stage: Stage::Building,
+        })
+    }
+
+    /// The ID that [`GoogleAuth::cancel_sign_in`] cancels this flow by.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// A handle that cancels this flow without going through its session ID.
+    #[must_use]
+    pub fn cancel_handle(&self) -> SignInCancelHandle {
+        SignInCancelHandle(self.cancelled.clone())
+    }
+
+    /// The correlation ID sent as `X-Request-Id` on requests to Google.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// The current state of the flow.
+    pub const fn state(&self) -> SignInState {
+        match self.stage {
+            Stage::Building => SignInState::Building,
+            Stage::AwaitingCallback(_) => SignInState::AwaitingCallback,
+            Stage::Exchanging(..) => SignInState::Exchanging,
+            Stage::Complete(_) => SignInState::Complete,
+            Stage::Failed(_) => SignInState::Failed,
+        }
+    }
+
+    /// The authorization URL, once the flow has started and until it completes.
+    pub fn authorize_url(&self) -> Option<&Url> {
+        match &self.stage {
+            Stage::AwaitingCallback(pending) | Stage::Exchanging(pending, _) => {
+                Some(&pending.authorize_url)
+            }
+            _ => None,
+        }
+    }
+
+    /// The loopback redirect URL Google will call back, once the flow has started.
+    pub fn redirect_url(&self) -> Option<&str> {
+        match &self.stage {
+            Stage::AwaitingCallback(pending) | Stage::Exchanging(pending, _) => pending
+                .client
+                .redirect_uri()
+                .map(|redirect_url| redirect_url.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The tokens obtained by a completed flow.
+    pub fn token_response(&self) -> Option<&crate::TokenResponse> {
+        match &self.stage {
+            Stage::Complete(response) => Some(response.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// The error message of a failed flow.
+    pub fn error(&self) -> Option<&str> {
+        match &self.stage {
+            Stage::Failed(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Runs every remaining step, opening the system browser.
+    pub fn run(mut self) -> crate::Result<crate::TokenResponse> {
+        self.start()?;
+        self.open_browser()?;
+        self.wait_for_callback()?;
+        self.exchange()
+    }
+
+    /// Binds the loopback server and builds the authorization URL.
+    pub fn start(&mut self) -> crate::Result<Url> {
+        if !matches!(self.stage, Stage::Building) {
+            return Err(self.unexpected_state(SignInState::Building));
+        }
+
+        match self.prepare() {
+            Ok(pending) => {
+                let authorize_url = pending.authorize_url.clone();
+                self.stage = Stage::AwaitingCallback(Box::new(pending));
+                // Failing to notify listeners must not fail the sign-in itself
+                let _ = self.app.emit(
+                    SIGN_IN_STARTED_EVENT,
+                    SignInStarted {
+                        session_id: self.session_id.clone(),
+                        request_id: self.request_id.clone(),
+                        authorize_url: authorize_url.to_string(),
+                    },
+                );
+                Ok(authorize_url)
+            }
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    /// Opens the authorization URL in the system browser, or in a window of the app
+    /// with [`AuthMode::Webview`]. A window that can't be created falls back to the
+    /// browser with a warning; closing it cancels the sign-in.
+    pub fn open_browser(&mut self) -> crate::Result<()> {
+        let Stage::AwaitingCallback(pending) = &self.stage else {
+            return Err(self.unexpected_state(SignInState::AwaitingCallback));
+        };
+        let authorize_url = pending.authorize_url.clone();
+        let redirect_uri = pending.client.redirect_uri().map(|uri| uri.url().clone());
+        self.emit_stage(SignInStage::OpeningBrowser, None);
+
+        if self.request.auth_mode == AuthMode::Webview
+            && let Some(redirect_uri) = redirect_uri
+        {
+            match webview::open(
+                &self.app,
+                &self.session_id,
+                &authorize_url,
+                &redirect_uri,
+                self.request.private_window,
+                self.request.webview_data_directory.as_deref(),
+                self.cancelled.clone(),
+            ) {
+                Ok(window) => {
+                    self.webview = Some(window);
+                    return Ok(());
+                }
+                Err(e) => self.warnings.push(format!(
+                    "Failed to open the sign-in window, used the system browser: {e}"
+                )),
+            }
+        }
+        let authorize_url = authorize_url.as_str();
+
+        // Open the authorization URL in the browser (detached to avoid blocking on some Linux systems),
+        // preferring a private window when requested and supported by the default browser
+        if !(self.request.private_window && browser::open_private(authorize_url).is_ok()) {
+            let result = open::that_detached(authorize_url)
+                .map_err(|e| crate::Error::NetworkError(format!("Failed to open browser: {e}")));
+            if let Err(e) = result {
+                return Err(self.fail(e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Blocks until the browser is redirected back to the loopback server.
+    pub fn wait_for_callback(&mut self) -> crate::Result<()> {
+        let pending = self.take_awaiting()?;
+        self.emit_stage(SignInStage::AwaitingCallback, None);
+        let result = accept_callback(&pending.transport, &self.cancelled, self.deadline())
+            .and_then(|(stream, url)| self.receive_callback(&pending, stream, &url));
+        self.advance_to_exchange(pending, result)
+    }
+
+    /// Exchanges the authorization code for tokens and answers the browser with
+    /// the success (or failure) page.
+    pub fn exchange(&mut self) -> crate::Result<crate::TokenResponse> {
+        let (pending, redirect) = self.take_exchanging()?;
+        let Pending {
+            transport,
+            client,
+            pkce_verifier,
+            nonce,
+            ..
+        } = *pending;
+        if self.request.server_client_id.is_some() {
+            let result = self.hand_off_code(&transport, redirect, pkce_verifier);
+            return self.advance_to_complete(result);
+        }
+        let exchanged =
+            self.exchange_code(&transport, client, redirect.code.clone(), pkce_verifier);
+        let result = self
+            .complete(&transport, &nonce, redirect, exchanged)
+            .and_then(|mut response| {
+                if self.request.fetch_profile {
+                    response.profile = Some(fetch_profile(
+                        response.access_token.as_deref().unwrap_or_default(),
+                        self.headers.clone(),
+                        &self.http,
+                    )?);
+                }
+                Ok(response)
+            });
+        self.advance_to_complete(result)
+    }
+
+    /// Runs every remaining step like [`run`](Self::run), but awaits the callback
+    /// and the token exchange on the async runtime instead of blocking the thread.
+    pub async fn run_async(mut self) -> crate::Result<crate::TokenResponse> {
+        self.start()?;
+        self.open_browser()?;
+        self.wait_for_callback_async().await?;
+        self.exchange_async().await
+    }
+
+    /// Like [`wait_for_callback`](Self::wait_for_callback), but polls the loopback
+    /// server without blocking the thread.
+    pub async fn wait_for_callback_async(&mut self) -> crate::Result<()> {
+        let pending = self.take_awaiting()?;
+        self.emit_stage(SignInStage::AwaitingCallback, None);
+        let result = accept_callback_async(&pending.transport, &self.cancelled, self.deadline())
+            .await
+            .and_then(|(stream, url)| self.receive_callback(&pending, stream, &url));
+        self.advance_to_exchange(pending, result)
+    }
+
+    /// Like [`exchange`](Self::exchange), but sends the token request with the async
+    /// HTTP client and answers the browser on a blocking thread.
+    pub async fn exchange_async(&mut self) -> crate::Result<crate::TokenResponse> {
+        let (pending, redirect) = self.take_exchanging()?;
+        let Pending {
+            transport,
+            client,
+            pkce_verifier,
+            nonce,
+            ..
+        } = *pending;
+        if self.request.server_client_id.is_some() {
+            let result = self
+                .hand_off_code_async(transport, redirect, pkce_verifier)
+                .await;
+            return self.advance_to_complete(result);
+        }
+        let exchanged = self
+            .exchange_code_async(client, redirect.code.clone(), pkce_verifier)
+            .await;
+        let mut result = self
+            .complete_async(transport, &nonce, redirect, exchanged)
+            .await;
+        if let Ok(response) = &mut result
+            && self.request.fetch_profile
+        {
+            match fetch_profile_async(
+                response.access_token.as_deref().unwrap_or_default(),
+                self.headers.clone(),
+                &self.http,
+            )
+            .await
+            {
+                Ok(profile) => response.profile = Some(profile),
+                Err(e) => result = Err(e),
+            }
+        }
+        self.advance_to_complete(result)
+    }
+
+    fn take_awaiting(&mut self) -> crate::Result<Box<Pending>> {
+        match std::mem::replace(&mut self.stage, Stage::Building) {
+            Stage::AwaitingCallback(pending) => Ok(pending),
+            stage => {
+                self.stage = stage;
+                Err(self.unexpected_state(SignInState::AwaitingCallback))
+            }
+        }
+    }
+
+    fn take_exchanging(&mut self) -> crate::Result<(Box<Pending>, Redirect)> {
+        match std::mem::replace(&mut self.stage, Stage::Building) {
+            Stage::Exchanging(pending, redirect) => Ok((pending, redirect)),
+            stage => {
+                self.stage = stage;
+                Err(self.unexpected_state(SignInState::Exchanging))
+            }
+        }
+    }
+
+    fn advance_to_exchange(
+        &mut self,
+        pending: Box<Pending>,
+        result: crate::Result<Redirect>,
+    ) -> crate::Result<()> {
+        match result {
+            Ok(redirect) => {
+                self.stage = Stage::Exchanging(pending, redirect);
+                self.emit_stage(SignInStage::ExchangingCode, None);
+                Ok(())
+            }
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    fn advance_to_complete(
+        &mut self,
+        result: crate::Result<crate::TokenResponse>,
+    ) -> crate::Result<crate::TokenResponse> {
+        match result {
+            Ok(mut response) => {
+                let cookies = self.webview_cookies();
+                self.close_webview();
+                response.warnings.append(&mut self.warnings);
+                self.accounts.remember(&mut response);
+                // Attached after the session was stored, so they never persist
+                response.webview_cookies = cookies;
+                self.stage = Stage::Complete(Box::new(response.clone()));
+                self.emit_stage(SignInStage::Complete, None);
+                Ok(response)
+            }
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    /// When to stop waiting for the callback, per `timeout_seconds`.
+    fn deadline(&self) -> Option<Instant> {
+        self.request
+            .timeout_seconds
+            .map(|seconds| Instant::now() + Duration::from_secs(seconds))
+    }
+
+    /// Binds the redirect URI's port, a port of `port_range` or a random one, plus
+    /// the preferred port when a busy one was swapped for a random one.
+    fn bind_transport(&self) -> crate::Result<(TcpTransport, Option<u16>)> {
+        let random = BindPort::Random(self.request.bind_attempts.unwrap_or(DEFAULT_BIND_ATTEMPTS));
+        match (self.port, self.request.port_range) {
+            (Some(preferred), _) => {
+                match TcpTransport::bind(&self.bind_addrs, BindPort::Fixed(preferred)) {
+                    // With a fallback hook, a busy fixed port is swapped for a random one
+                    Err(_) if self.port_fallback.is_some() => Ok((
+                        TcpTransport::bind(&self.bind_addrs, random)?,
+                        Some(preferred),
+                    )),
+                    result => Ok((result?, None)),
+                }
+            }
+            (None, Some((start, end))) => Ok((
+                TcpTransport::bind(&self.bind_addrs, BindPort::Range(start..=end))?,
+                None,
+            )),
+            (None, None) => Ok((TcpTransport::bind(&self.bind_addrs, random)?, None)),
+        }
+    }
+
+    fn prepare(&self) -> crate::Result<Pending> {
+        // A code for a backend is issued to its client, which redeems it there
+        let google_client_id = ClientId::new(
+            self.request
+                .server_client_id
+                .clone()
+                .unwrap_or_else(|| self.request.client_id.clone()),
+        );
+        let (auth_url, token_url, revocation_url) = endpoints(&self.request)?;
+
+        // Bind the transport first to get the actual port
+        let (transport, fallback_from) = self.bind_transport()?;
+
+        // Get the actual port that was bound
+        let actual_port = transport
+            .port()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to get local address: {e}")))?;
+
+        if let (Some(preferred), Some(on_fallback)) = (fallback_from, &self.port_fallback) {
+            on_fallback(preferred, actual_port);
+        }
+
+        // Construct the redirect URL with the actual port
+        let redirect_url = format!("http://{}:{actual_port}", self.redirect_host);
+
+        if let Some(original) = &self.normalized_from {
+            // Failing to notify listeners must not fail the sign-in itself
+            let _ = self.app.emit(
+                REDIRECT_URI_NORMALIZED_EVENT,
+                RedirectUriNormalized {
+                    original: original.clone(),
+                    normalized: redirect_url.clone(),
+                },
+            );
+        }
+
+        // Set up the config for the Google OAuth2 process.
+        let client = with_client_secret(
+            SpecialClient::new(google_client_id),
+            self.client_secret.clone(),
+        )
+        .set_auth_uri(auth_url)
+        .set_token_uri(token_url)
+        .set_redirect_uri(RedirectUrl::new(redirect_url).map_err(|_| {
+            crate::Error::configuration(Some("redirect_uri"), "Invalid redirect URL")
+        })?)
+        // Google supports OAuth 2.0 Token Revocation (RFC-7009)
+        .set_revocation_url(revocation_url);
+
+        // Google supports Proof Key for Code Exchange (PKCE - https://oauth.net/2/pkce/).
+        // Create a PKCE code verifier and, by default, SHA-256 encode it as a code challenge.
+        // A backend redeeming a server auth code gets the verifier along with it.
+        let (pkce_code_challenge, pkce_verifier) = match self.request.pkce_method {
+            None | Some(PkceMethod::S256) => Some(PkceCodeChallenge::new_random_sha256()),
+            Some(PkceMethod::Plain) => Some(PkceCodeChallenge::new_random_plain()),
+            Some(PkceMethod::None) => None,
+        }
+        .unzip();
+
+        // Generate the authorization URL to which we'll redirect the user.
+        let state = self
+            .state_codec
+            .as_ref()
+            .map_or_else(CsrfToken::new_random, |codec| {
+                CsrfToken::new((codec.provider)())
+            });
+        let mut auth_url_builder = client.authorize_url(|| state);
+
+        // Add all the scopes from the payload
+        for scope in &self.scopes {
+            auth_url_builder = auth_url_builder.add_scope(Scope::new(scope.clone()));
+        }
+
+        // Google only issues a refresh token on consent, so offline access forces the
+        // consent screen unless the caller chose a prompt
+        let offline_access = self.request.offline_access == Some(true);
+        if let Some(prompt) = self
+            .request
+            .prompt
+            .as_deref()
+            .or_else(|| offline_access.then_some("consent"))
+        {
+            auth_url_builder = auth_url_builder.add_extra_param("prompt", prompt);
+        }
+        if offline_access {
+            auth_url_builder = auth_url_builder.add_extra_param("access_type", "offline");
+        }
+
+        if let Some(hosted_domain) = &self.request.hosted_domain {
+            auth_url_builder = auth_url_builder.add_extra_param("hd", hosted_domain);
+        }
+
+        // Pre-fills the account chooser, e.g. when re-authenticating a known user
+        if let Some(login_hint) = &self.request.login_hint {
+            auth_url_builder = auth_url_builder.add_extra_param("login_hint", login_hint);
+        }
+
+        // Incremental authorization: the token then also covers the scopes granted before
+        if self.request.include_granted_scopes {
+            auth_url_builder = auth_url_builder.add_extra_param("include_granted_scopes", "true");
+        }
+
+        // The nonce is echoed in the ID token, binding it to this request against
+        // replays. Hybrid flows return that token from the authorization endpoint,
+        // where Google requires one.
+        let nonce = self
+            .request
+            .nonce
+            .clone()
+            .unwrap_or_else(|| CsrfToken::new_random().secret().clone());
+        auth_url_builder = auth_url_builder.add_extra_param("nonce", &nonce);
+        if self.hybrid {
+            auth_url_builder =
+                auth_url_builder.set_response_type(&ResponseType::new("code id_token".to_string()));
+        }
+
+        if let Some(pkce_code_challenge) = pkce_code_challenge {
+            auth_url_builder = auth_url_builder.set_pkce_challenge(pkce_code_challenge);
+        }
+        let (authorize_url, csrf_state) = auth_url_builder.url();
+
+        Ok(Pending {
+            transport,
+            client,
+            authorize_url,
+            csrf_state,
+            pkce_verifier,
+            nonce,
+        })
+    }
+
+    /// Reads the code and state out of the callback, the first request carrying
+    /// OAuth parameters; later ones are answered as repeats.
+    fn receive_callback(
+        &self,
+        pending: &Pending,
+        stream: TcpStream,
+        url: &Url,
+    ) -> crate::Result<Redirect> {
+        let (code, state) = read_callback(url, &pending.csrf_state, self.state_codec.as_deref())?;
+
+        let callback_params = self.request.return_callback_params.then(|| {
+            url.query_pairs()
+                .filter(|(key, _)| key != "code")
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect()
+        });
+
+        // Hybrid response types deliver the ID token alongside the code
+        let fragment_id_token = url
+            .query_pairs()
+            .find(|(key, _)| key == "id_token")
+            .map(|(_, id_token)| id_token.into_owned());
+
+        Ok(Redirect {
+            stream,
+            code,
+            state,
+            params: callback_params,
+            fragment_id_token,
+        })
+    }
+
+    /// Exchanges `code` on a separate thread, as the blocking client can't run on
+    /// the async runtime.
+    fn exchange_code(
+        &self,
+        transport: &TcpTransport,
+        client: ConfiguredClient,
+        code: AuthorizationCode,
+        pkce_verifier: Option<PkceCodeVerifier>,
+    ) -> crate::Result<SpecialTokenResponse> {
+        let headers = self.headers.clone();
+        let http = self.http.clone();
+        let exchange = std::thread::spawn(move || -> crate::Result<_> {
+            // Create HTTP client with proper security settings
+            let http_client = http
+                .blocking(oauth2::reqwest::blocking::Client::builder())
+                // Following redirects opens the client up to SSRF vulnerabilities
+                .redirect(oauth2::reqwest::redirect::Policy::none())
+                .default_headers(headers)
+                .build()
+                .map_err(|e| {
+                    crate::Error::NetworkError(format!("Failed to build HTTP client: {e}"))
+                })?;
+
+            // Exchange the code with a token.
+            let http_client = RecordingClient::new(&http_client, http.retry);
+            let mut request = client.exchange_code(code);
+            if let Some(pkce_verifier) = pkce_verifier {
+                request = request.set_pkce_verifier(pkce_verifier);
+            }
+            let token_response = request.request(&http_client).map_err(|e| {
+                map_token_error(
+                    e,
+                    "Failed to exchange code for token",
+                    http_client.take_failed(),
+                )
+            })?;
+
+            Ok(token_response)
+        });
+
+        // Only the first callback is exchanged; repeats (e.g. a double submit)
+        // are answered while the exchange runs. A cancelled flow stops waiting and
+        // leaves the thread to finish the request and drop the response.
+        while !exchange.is_finished() {
+            if self.cancelled.load(Ordering::SeqCst) {
+                return Err(crate::Error::UserCancelled);
+            }
+            answer_repeat_callbacks(transport);
+            std::thread::sleep(ACCEPT_POLL_INTERVAL);
+        }
+
+        exchange
+            .join()
+            .map_err(|_| {
+                crate::Error::AuthenticationFailed("Token exchange thread panicked".to_string())
+            })
+            .and_then(|result| result)
+    }
+
+    async fn exchange_code_async(
+        &self,
+        client: ConfiguredClient,
+        code: AuthorizationCode,
+        pkce_verifier: Option<PkceCodeVerifier>,
+    ) -> crate::Result<SpecialTokenResponse> {
+        let http_client = self
+            .http
+            .nonblocking(oauth2::reqwest::Client::builder())
+            // Following redirects opens the client up to SSRF vulnerabilities
+            .redirect(oauth2::reqwest::redirect::Policy::none())
+            .default_headers(self.headers.clone())
+            .build()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to build HTTP client: {e}")))?;
+
+        let http_client = RecordingClient::new(&http_client, self.http.retry);
+        let mut request = client.exchange_code(code);
+        if let Some(pkce_verifier) = pkce_verifier {
+            request = request.set_pkce_verifier(pkce_verifier);
+        }
+        let exchange = request.request_async(&http_client);
+        let mut exchange = std::pin::pin!(exchange);
+        // Dropping the exchange of a cancelled flow aborts its request
+        let result = loop {
+            if let Ok(result) = tokio::time::timeout(ACCEPT_POLL_INTERVAL, &mut exchange).await {
+                break result;
+            }
+            if self.cancelled.load(Ordering::SeqCst) {
+                return Err(crate::Error::UserCancelled);
+            }
+        };
+        result.map_err(|e| {
+            map_token_error(
+                e,
+                "Failed to exchange code for token",
+                http_client.take_failed(),
+            )
+        })
+    }
+
+    /// Verifies the exchanged tokens and answers the browser. The code is exchanged
+    /// before answering so the success page can be rendered from the ID token claims.
+    fn complete(
+        &self,
+        transport: &TcpTransport,
+        nonce: &str,
+        mut redirect: Redirect,
+        exchanged: crate::Result<SpecialTokenResponse>,
+    ) -> crate::Result<crate::TokenResponse> {
+        let (exchange_result, page) =
+            self.settle(nonce, redirect.fragment_id_token.as_deref(), exchanged);
+        write_page(&mut redirect.stream, &page)?;
+        answer_repeat_callbacks(transport);
+
+        Ok(self.token_response_for(
+            &exchange_result?,
+            redirect.params,
+            redirect.fragment_id_token,
+        ))
+    }
+
+    /// Like [`complete`](Self::complete), but answers the browser on a blocking
+    /// thread and fetches the keys to verify the ID token with the async client.
+    async fn complete_async(
+        &self,
+        transport: TcpTransport,
+        nonce: &str,
+        redirect: Redirect,
+        mut exchanged: crate::Result<SpecialTokenResponse>,
+    ) -> crate::Result<crate::TokenResponse> {
+        if self.request.verify_id_token
+            && let Ok(token_response) = &exchanged
+            && let Some(id_token) = token_response
+                .extra_fields()
+                .id_token
+                .as_deref()
+                .or(redirect.fragment_id_token.as_deref())
+            && let Err(e) = self.id_token_keys.preload_async(id_token, &self.http).await
+        {
+            exchanged = Err(e);
+        }
+        let (exchange_result, page) =
+            self.settle(nonce, redirect.fragment_id_token.as_deref(), exchanged);
+        answer_async(transport, redirect.stream, page).await?;
+
+        Ok(self.token_response_for(
+            &exchange_result?,
+            redirect.params,
+            redirect.fragment_id_token,
+        ))
+    }
+
+    /// Verifies the exchanged tokens, returning them with the page to answer the
+    /// browser with.
+    fn settle(
+        &self,
+        nonce: &str,
+        fragment_id_token: Option<&str>,
+        exchanged: crate::Result<SpecialTokenResponse>,
+    ) -> (crate::Result<SpecialTokenResponse>, SuccessPage) {
+        let exchange_result = exchanged.and_then(|token_response| {
+            self.verify(&token_response, nonce, fragment_id_token)?;
+            Ok(token_response)
+        });
+
+        let page = exchange_result.as_ref().map_or_else(
+            |_| SuccessPage {
+                status: 500,
+                headers: Vec::new(),
+                body: SIGN_IN_FAILED_RESPONSE.to_string(),
+            },
+            |token_response| {
+                let claims = token_response
+                    .extra_fields()
+                    .id_token
+                    .as_deref()
+                    .and_then(|id_token| crate::id_token::decode_id_token_claims(id_token).ok());
+                self.success_page(claims.as_ref())
+            },
+        );
+        (exchange_result, page)
+    }
+
+    /// The plugin's response for verified tokens.
+    fn token_response_for(
+        &self,
+        token_response: &SpecialTokenResponse,
+        callback_params: Option<HashMap<String, String>>,
+        fragment_id_token: Option<String>,
+    ) -> crate::TokenResponse {
+        let mut response = convert_token_response(token_response, &self.scopes);
+        response.callback_params = callback_params;
+        if response.id_token.is_none() {
+            response.id_token = fragment_id_token;
+        }
+        response.collect_warnings(&self.scopes);
+        if let Some(original) = &self.normalized_from {
+            response.warnings.push(format!(
+                "redirect_uri `{original}` was sent without its trailing slash"
+            ));
+        }
+
+        // Failing to notify listeners must not fail the sign-in itself
+        let _ = self.app.emit(GRANTED_SCOPES_EVENT, &response.scopes);
+
+        response
+    }
+
+    /// Answers the browser and returns the authorization code unexchanged, as the
+    /// `server_auth_code` a backend redeems with `server_client_id`, along with the
+    /// validated state and the PKCE verifier.
+    fn hand_off_code(
+        &self,
+        transport: &TcpTransport,
+        mut redirect: Redirect,
+        pkce_verifier: Option<PkceCodeVerifier>,
+    ) -> crate::Result<crate::TokenResponse> {
+        write_page(&mut redirect.stream, &self.success_page(None))?;
+        answer_repeat_callbacks(transport);
+        Ok(self.code_response(
+            redirect.code,
+            redirect.state,
+            redirect.params,
+            pkce_verifier,
+        ))
+    }
+
+    /// Like [`hand_off_code`](Self::hand_off_code), but answers the browser on a
+    /// blocking thread.
+    async fn hand_off_code_async(
+        &self,
+        transport: TcpTransport,
+        redirect: Redirect,
+        pkce_verifier: Option<PkceCodeVerifier>,
+    ) -> crate::Result<crate::TokenResponse> {
+        let Redirect {
+            stream,
+            code,
+            state,
+            params,
+            ..
+        } = redirect;
+        answer_async(transport, stream, self.success_page(None)).await?;
+        Ok(self.code_response(code, state, params, pkce_verifier))
+    }
+
+    fn code_response(
+        &self,
+        code: AuthorizationCode,
+        state: String,
+        callback_params: Option<HashMap<String, String>>,
+        pkce_verifier: Option<PkceCodeVerifier>,
+    ) -> crate::TokenResponse {
+        crate::TokenResponse {
+            id_token: None,
+            access_token: None,
+            scopes: self.scopes.clone(),
+            refresh_token: None,
+            expires_at: None,
+            expires_at_iso: None,
+            expires_in_seconds: None,
+            is_offline: false,
+            callback_params,
+            warnings: Vec::new(),
+            profile: None,
+            server_auth_code: Some(code.into_secret()),
+            server_auth_state: Some(state),
+            server_code_verifier: pkce_verifier.map(PkceCodeVerifier::into_secret),
+            account_id: None,
+            webview_cookies: None,
+        }
+    }
+
+    /// The page the browser is answered with after a successful sign-in.
+    fn success_page(&self, claims: Option<&IdTokenClaims>) -> SuccessPage {
+        if let Some(url) = &self.request.success_redirect_url {
+            return SuccessPage {
+                status: 302,
+                headers: vec![("location".to_string(), url.clone())],
+                body: String::new(),
+            };
+        }
+        // Get the success HTML response message (use custom if provided, otherwise default)
+        let default_renderer = DefaultSuccessPageRenderer {
+            message: self
+                .request
+                .success_html_response
+                .clone()
+                .unwrap_or_else(|| DefaultSuccessPageRenderer::default().message),
+            wrap_html: self.request.wrap_success_html,
+        };
+        self.success_page_renderer
+            .as_deref()
+            .unwrap_or(&default_renderer)
+            .render(claims)
+    }
+
+    fn verify(
+        &self,
+        token_response: &SpecialTokenResponse,
+        nonce: &str,
+        fragment_id_token: Option<&str>,
+    ) -> crate::Result<()> {
+        let id_token = token_response
+            .extra_fields()
+            .id_token
+            .as_deref()
+            .or(fragment_id_token)
+            .filter(|id_token| !id_token.is_empty());
+
+        if self.request.require_id_token && id_token.is_none() {
+            return Err(crate::Error::AuthenticationFailed(
+                "No ID token returned; request the `openid` scope to get one".to_string(),
+            ));
+        }
+
+        if let Some(id_token) = id_token {
+            if crate::id_token::decode_id_token_claims(id_token)?
+                .nonce
+                .as_deref()
+                != Some(nonce)
+            {
+                return Err(crate::Error::AuthenticationFailed(
+                    "ID token nonce mismatch".to_string(),
+                ));
+            }
+        } else if self.hybrid {
+            return Err(crate::Error::AuthenticationFailed(
+                "No ID token returned for hybrid response type".to_string(),
+            ));
+        }
+        if self.request.verify_id_token {
+            let id_token = id_token.ok_or_else(|| {
+                crate::Error::AuthenticationFailed(
+                    "No ID token returned; cannot verify it".to_string(),
+                )
+            })?;
+            let client_id = [self.request.client_id.as_str()];
+            let audiences: Vec<&str> = self.request.accepted_audiences.as_ref().map_or_else(
+                || client_id.to_vec(),
+                |audiences| audiences.iter().map(String::as_str).collect(),
+            );
+            super::jwks::verify_id_token(
+                &self.id_token_keys,
+                id_token,
+                &audiences,
+                self.request
+                    .id_token_leeway_seconds
+                    .map_or(IdTokenClaims::DEFAULT_LEEWAY, Duration::from_secs),
+                crate::id_token::unix_now(),
+                &self.http,
+            )?;
+        }
+        if let Some(required) = &self.request.required_scopes {
+            convert_token_response(token_response, &self.scopes).ensure_scopes(required)?;
+        }
+        if self.request.require_verified_email {
+            crate::id_token::require_verified_email(id_token)?;
+        }
+        if let Some(hosted_domain) = &self.request.hosted_domain {
+            crate::id_token::require_hosted_domain(id_token, hosted_domain)?;
+        }
+        Ok(())
+    }
+
+    /// The Google cookies of the sign-in window, with `return_webview_cookies`. A
+    /// window that couldn't be opened or read leaves a warning instead.
+    fn webview_cookies(&mut self) -> Option<Vec<crate::WebviewCookie>> {
+        if !self.request.return_webview_cookies {
+            return None;
+        }
+        let Some(window) = &self.webview else {
+            self.warnings
+                .push("No sign-in window was used, so no webview cookies are returned".to_string());
+            return None;
+        };
+        match webview::google_cookies(window) {
+            Ok(cookies) => Some(cookies),
+            Err(e) => {
+                self.warnings
+                    .push(format!("Failed to read the webview cookies: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Closes the sign-in window, if the flow opened one.
+    fn close_webview(&mut self) {
+        if let Some(window) = self.webview.take() {
+            // The user may have closed it already
+            let _ = window.destroy();
+        }
+    }
+
+    fn fail(&mut self, error: crate::Error) -> crate::Error {
+        self.close_webview();
+        self.stage = Stage::Failed(error.to_string());
+        self.emit_stage(SignInStage::Failed, Some(error.to_string()));
+        error
+    }
+
+    /// Reports progress to listeners; failing to notify them must not fail the
+    /// sign-in itself.
+    fn emit_stage(&self, stage: SignInStage, error: Option<String>) {
+        let _ = self.app.emit(
+            SIGN_IN_STAGE_EVENT,
+            SignInProgress {
+                session_id: self.session_id.clone(),
+                request_id: self.request_id.clone(),
+                stage,
+                error,
+            },
+        );
+    }
+
+    fn unexpected_state(&self, expected: SignInState) -> crate::Error {
+        crate::Error::AuthenticationFailed(format!(
+            "Sign-in flow is in the {:?} state, expected {expected:?}",
+            self.state()
+        ))
+    }
+}
+
+impl<R: Runtime> Drop for SignInFlow<R> {
+    fn drop(&mut self) {
+        self.close_webview();
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.session_id);
+    }
+}
+
+/// Reads the authorization code and the validated state out of the callback `url`,
+/// or the error Google redirected with.
+///
+/// The state is checked first: any local page can send a request to the loopback
+/// port, and a forged `?error=access_denied` must not end the sign-in. A state we
+/// didn't issue means the redirect belongs to another flow, e.g. a forged one
+/// injecting an attacker's code.
+fn read_callback(
+    url: &Url,
+    expected_state: &CsrfToken,
+    state_codec: Option<&StateCodec>,
+) -> crate::Result<(AuthorizationCode, String)> {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    let state = param("state").ok_or_else(|| {
+        crate::Error::AuthenticationFailed("State parameter not found in response".to_string())
+    })?;
+    if let Some(codec) = state_codec {
+        if !(codec.validator)(&state) {
+            return Err(crate::Error::AuthenticationFailed(
+                "State parameter failed validation".to_string(),
+            ));
+        }
+    } else if !constant_time_eq(state.as_bytes(), expected_state.secret().as_bytes()) {
+        return Err(crate::Error::AuthenticationFailed(
+            "CSRF state mismatch".to_string(),
+        ));
+    }
+
+    if let Some(error) = param("error") {
+        return Err(map_authorization_error(
+            &error,
+            param("error_description").as_deref(),
+        ));
+    }
+
+    let code = param("code").map(AuthorizationCode::new).ok_or_else(|| {
+        crate::Error::AuthenticationFailed("Authorization code not found in response".to_string())
+    })?;
+    Ok((code, state))
+}
+
+/// Google's OAuth endpoints, or the request's overrides, e.g. for a mock server in tests.
+fn endpoints(request: &SignInRequest) -> crate::Result<(AuthUrl, TokenUrl, RevocationUrl)> {
+    let auth_url = AuthUrl::new(
+        request
+            .auth_url
+            .clone()
+            .unwrap_or_else(|| GOOGLE_AUTH_URL.to_string()),
+    )
+    .map_err(|_| {
+        crate::Error::configuration(Some("auth_url"), "Invalid authorization endpoint URL")
+    })?;
+    let token_url = TokenUrl::new(
+        request
+            .token_url
+            .clone()
+            .unwrap_or_else(|| GOOGLE_TOKEN_URL.to_string()),
+    )
+    .map_err(|_| crate::Error::configuration(Some("token_url"), "Invalid token endpoint URL"))?;
+    let revocation_url = RevocationUrl::new(
+        request
+            .revocation_url
+            .clone()
+            .unwrap_or_else(|| GOOGLE_REVOCATION_URL.to_string()),
+    )
+    .map_err(|_| {
+        crate::Error::configuration(Some("revocation_url"), "Invalid revocation endpoint URL")
+    })?;
+    Ok((auth_url, token_url, revocation_url))
+}
+
+/// Splits a desktop redirect URI into host and port, plus the original spelling
+/// when a trailing slash was dropped. Without one, a random localhost port is used.
+fn parse_redirect_uri(
+    redirect_uri: Option<&str>,
+) -> crate::Result<(String, Option<u16>, Option<String>)> {
+    let Some(redirect_uri) = redirect_uri else {
+        // Default to localhost with no specific port (will bind to random available port)
+        return Ok((DEFAULT_REDIRECT_HOST.to_string(), None, None));
+    };
+
+    let parsed_url = Url::parse(redirect_uri).map_err(|e| {
+        crate::Error::configuration(Some("redirect_uri"), format!("Invalid redirect URI: {e}"))
+    })?;
+
+    let host = parsed_url.host_str().ok_or_else(|| {
+        crate::Error::configuration(Some("redirect_uri"), "Redirect URI must have a host")
+    })?;
+
+    // Validate that it's localhost or a loopback address
+    if ![DEFAULT_REDIRECT_HOST, LOCALHOST_ADDR, LOCALHOST_V6_HOST].contains(&host) {
+        return Err(crate::Error::configuration(
+            Some("redirect_uri"),
+            "Redirect URI must use localhost, 127.0.0.1 or [::1] for desktop authentication",
+        ));
+    }
+
+    // The redirect URI is always sent without a trailing slash for the root;
+    // Google treats both spellings as different registered URIs
+    let normalized_from =
+        (parsed_url.path() == "/" && redirect_uri.ends_with('/')).then(|| redirect_uri.to_string());
+
+    Ok((host.to_string(), parsed_url.port(), normalized_from))
+}
+
+/// Compares without short-circuiting, so timing doesn't reveal how much of a
+/// guessed state matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The userinfo endpoint only returns what the granted scopes cover, so a profile
+/// needs all of the identity scopes.
+fn require_profile_scopes(scopes: &[String]) -> crate::Result<()> {
+    let has = |names: &[&str]| scopes.iter().any(|scope| names.contains(&scope.as_str()));
+    if has(&["openid"])
+        && has(&[
+            "profile",
+            "https://www.googleapis.com/auth/userinfo.profile",
+        ])
+        && has(&["email", "https://www.googleapis.com/auth/userinfo.email"])
+    {
+        return Ok(());
+    }
+    Err(crate::Error::configuration(
+        Some("fetch_profile"),
+        "fetch_profile requires the openid, profile and email scopes",
+    ))
+}
+
+/// Checks the options that stand on their own, given the normalized `scopes`.
+fn validate_options(request: &SignInRequest, scopes: &[String]) -> crate::Result<()> {
+    if let Some(prompt) = &request.prompt {
+        validate_prompt(prompt)?;
+    }
+    if let Some(url) = &request.success_redirect_url {
+        validate_success_redirect_url(url)?;
+    }
+    if request
+        .accepted_audiences
+        .as_ref()
+        .is_some_and(Vec::is_empty)
+    {
+        return Err(crate::Error::configuration(
+            Some("accepted_audiences"),
+            "accepted_audiences must list at least one audience",
+        ));
+    }
+    if request.fetch_profile {
+        require_profile_scopes(scopes)?;
+    }
+    if request.return_webview_cookies && request.auth_mode != AuthMode::Webview {
+        return Err(crate::Error::configuration(
+            Some("return_webview_cookies"),
+            "return_webview_cookies only applies to the webview auth mode",
+        ));
+    }
+    if request.webview_data_directory.is_some() {
+        if request.auth_mode != AuthMode::Webview {
+            return Err(crate::Error::configuration(
+                Some("webview_data_directory"),
+                "webview_data_directory only applies to the webview auth mode",
+            ));
+        }
+        if request.private_window {
+            return Err(crate::Error::configuration(
+                Some("webview_data_directory"),
+                "A private window keeps no data, so it can't have a webview_data_directory",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Only web pages are accepted, so the redirect can't run a `javascript:` URL or
+/// open another app.
+fn validate_success_redirect_url(url: &str) -> crate::Result<()> {
+    let parsed = Url::parse(url).map_err(|e| {
+        crate::Error::configuration(
+            Some("success_redirect_url"),
+            format!("Invalid success redirect URL: {e}"),
+        )
+    })?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(crate::Error::configuration(
+            Some("success_redirect_url"),
+            "Success redirect URL must use http or https",
+        ));
+    }
+    Ok(())
+}
+
+/// A code handed to a backend is redeemed there with the same redirect URI, so the
+/// URI must be fixed and nothing that needs the tokens can run on the desktop.
+fn validate_server_client(request: &SignInRequest) -> crate::Result<()> {
+    if request.server_client_id.is_none() {
+        return Ok(());
+    }
+    if request.redirect_uri.is_none() {
+        return Err(crate::Error::configuration(
+            Some("redirect_uri"),
+            "server_client_id requires a fixed redirect_uri for the backend to redeem the code with",
+        ));
+    }
+    let needs_tokens = [
+        ("fetch_profile", request.fetch_profile),
+        ("verify_id_token", request.verify_id_token),
+        ("require_id_token", request.require_id_token),
+    ];
+    if let Some((field, _)) = needs_tokens.into_iter().find(|(_, set)| *set) {
+        return Err(crate::Error::configuration(
+            Some(field),
+            format!("{field} needs the tokens, which server_client_id leaves to the backend"),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects client types that can't work with `port`. Without an explicit client type,
+/// a fixed port is taken to mean a web client and anything else a desktop client,
+/// both of which work as configured.
+fn validate_client_type(client_type: Option<ClientType>, port: Option<u16>) -> crate::Result<()> {
+    match client_type {
+        Some(ClientType::Web) if port.is_none() => Err(crate::Error::configuration(
+            Some("redirect_uri"),
+            "Web application clients only accept registered redirect URIs. \
+             Set redirect_uri to one registered in the Google Cloud Console, including its port",
+        )),
+        Some(ClientType::LimitedInput) => Err(crate::Error::configuration(
+            Some("client_type"),
+            "TVs and Limited Input devices clients require the device flow. \
+             Use sign_in_device_code instead, or a Desktop app client",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Accepts a space-separated combination of Google's `prompt` values; `none` must
+/// stand alone, since it forbids any UI.
+fn validate_prompt(prompt: &str) -> crate::Result<()> {
+    let values: Vec<&str> = prompt.split_whitespace().collect();
+    if let Some(unknown) = values
+        .iter()
+        .find(|value| !["none", "consent", "select_account", "login"].contains(value))
+    {
+        return Err(crate::Error::configuration(
+            Some("prompt"),
+            format!("Unknown prompt value `{unknown}`. Use none, consent, select_account or login"),
+        ));
+    }
+    if values.is_empty() || (values.contains(&"none") && values.len() > 1) {
+        return Err(crate::Error::configuration(
+            Some("prompt"),
+            "prompt must be `none` alone or a combination of consent, select_account and login",
+        ));
+    }
+    Ok(())
+}
+
+/// The redirect host and the addresses to listen on. A `bind_host` replaces the
+/// default loopback addresses, and the default redirect host along with them.
+fn listen_addresses(
+    request: &SignInRequest,
+    redirect_host: String,
+) -> crate::Result<(String, Vec<IpAddr>)> {
+    let Some(bind_host) = &request.bind_host else {
+        let addrs = bind_addresses(&redirect_host, request.ip_version)?;
+        return Ok((redirect_host, addrs));
+    };
+
+    let addr = parse_bind_host(bind_host, request.ip_version)?;
+    // The browser has to be sent to the address actually listened on
+    let redirect_host = match addr {
+        _ if request.redirect_uri.is_some() => redirect_host,
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{v6}]"),
+    };
+    Ok((redirect_host, vec![addr]))
+}
+
+fn validate_port_range((start, end): (u16, u16), port: Option<u16>) -> crate::Result<()> {
+    if port.is_some() {
+        return Err(crate::Error::configuration(
+            Some("port_range"),
+            "port_range cannot be combined with a redirect URI that has a port",
+        ));
+    }
+    if start == 0 || start > end {
+        return Err(crate::Error::configuration(
+            Some("port_range"),
+            format!("Invalid port range {start}-{end}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Parses `bind_host` as the single address to listen on. Google only redirects to
+/// loopback addresses, and listening anywhere else would expose the callback to
+/// the network, so other addresses are rejected.
+fn parse_bind_host(bind_host: &str, ip_version: Option<IpVersion>) -> crate::Result<IpAddr> {
+    let addr: IpAddr = bind_host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| {
+            crate::Error::configuration(
+                Some("bind_host"),
+                format!("Invalid bind host `{bind_host}`. Use an IP address"),
+            )
+        })?;
+    if !addr.is_loopback() {
+        return Err(crate::Error::configuration(
+            Some("bind_host"),
+            format!("Bind host {addr} is not a loopback address"),
+        ));
+    }
+    match (addr, ip_version) {
+        (IpAddr::V4(_), Some(IpVersion::V6)) | (IpAddr::V6(_), Some(IpVersion::V4)) => {
+            Err(crate::Error::configuration(
+                Some("ip_version"),
+                format!("ip_version conflicts with the bind host {addr}"),
+            ))
+        }
+        _ => Ok(addr),
+    }
+}
+
+/// The loopback addresses to listen on for callbacks to `host`. The browser may
+/// resolve `localhost` to either family, so both are used unless `ip_version`
+/// picks one.
+fn bind_addresses(host: &str, ip_version: Option<IpVersion>) -> crate::Result<Vec<IpAddr>> {
+    let v4 = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+    match (host, ip_version) {
+        (LOCALHOST_ADDR, Some(IpVersion::V6)) | (LOCALHOST_V6_HOST, Some(IpVersion::V4)) => {
+            Err(crate::Error::configuration(
+                Some("ip_version"),
+                format!("ip_version conflicts with the redirect URI host {host}"),
+            ))
+        }
+        (LOCALHOST_ADDR, _) | (_, Some(IpVersion::V4)) => Ok(vec![v4]),
+        (LOCALHOST_V6_HOST, _) | (_, Some(IpVersion::V6)) => Ok(vec![v6]),
+        _ => Ok(vec![v4, v6]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::super::test_support::{
+        MockServer, authorize_url, google_auth, query_param, send_callback, sign_in_request,
+        token_response,
+    };
+    use super::*;
+    use crate::id_token::tests::unsigned_jwt;
+
+    fn callback(query: &str) -> Url {
+        Url::parse(&format!("http://127.0.0.1:8080/?{query}")).expect("invalid test URL")
+    }
+
+    fn state() -> CsrfToken {
+        CsrfToken::new("expected".to_string())
+    }
+
+    #[test]
+    fn code_with_matching_state_is_read() {
+        let (code, validated) = read_callback(&callback("code=abc&state=expected"), &state(), None)
+            .expect("callback was rejected");
+
+        assert_eq!(code.secret(), "abc");
+        assert_eq!(validated, "expected");
+    }
+
+    #[test]
+    fn forged_error_without_state_does_not_end_as_cancelled() {
+        let result = read_callback(&callback("error=access_denied"), &state(), None);
+
+        assert!(
+            matches!(result, Err(crate::Error::AuthenticationFailed(message)) if message.contains("State"))
+        );
+    }
+
+    #[test]
+    fn forged_error_with_wrong_state_is_a_state_mismatch() {
+        let result = read_callback(
+            &callback("error=access_denied&state=forged"),
+            &state(),
+            None,
+        );
+
+        assert!(
+            matches!(result, Err(crate::Error::AuthenticationFailed(message)) if message == "CSRF state mismatch")
+        );
+    }
+
+    #[test]
+    fn code_with_wrong_state_is_a_state_mismatch() {
+        let result = read_callback(&callback("code=injected&state=forged"), &state(), None);
+
+        assert!(
+            matches!(result, Err(crate::Error::AuthenticationFailed(message)) if message == "CSRF state mismatch")
+        );
+    }
+
+    #[test]
+    fn error_with_matching_state_is_mapped() {
+        let result = read_callback(
+            &callback("error=access_denied&state=expected"),
+            &state(),
+            None,
+        );
+
+        assert!(matches!(result, Err(crate::Error::UserCancelled)));
+    }
+
+    #[test]
+    fn state_codec_replaces_the_built_in_check() {
+        let codec = StateCodec {
+            provider: Box::new(|| "signed".to_string()),
+            validator: Box::new(|state| state == "signed"),
+        };
+
+        let (_, validated) =
+            read_callback(&callback("code=abc&state=signed"), &state(), Some(&codec))
+                .expect("callback was rejected");
+        assert_eq!(validated, "signed");
+        assert!(
+            read_callback(&callback("code=abc&state=expected"), &state(), Some(&codec)).is_err()
+        );
+    }
+
+    #[test]
+    fn prompt_is_sent_to_the_authorization_endpoint() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "prompt": "consent select_account",
+        })))
+        .expect("sign-in did not start");
+
+        assert_eq!(
+            query_param(&url, "prompt").as_deref(),
+            Some("consent select_account")
+        );
+    }
+
+    #[test]
+    fn prompt_is_left_out_unless_requested() {
+        let url =
+            authorize_url(sign_in_request(&serde_json::json!({}))).expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "prompt"), None);
+    }
+
+    #[test]
+    fn prompt_values_are_validated() {
+        for prompt in ["none", "consent", "select_account login"] {
+            assert!(validate_prompt(prompt).is_ok(), "{prompt} was rejected");
+        }
+        for prompt in ["", "always", "none consent"] {
+            assert!(
+                matches!(
+                    validate_prompt(prompt),
+                    Err(crate::Error::ConfigurationError { .. })
+                ),
+                "{prompt:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn offline_access_asks_for_a_refresh_token() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "offlineAccess": true,
+        })))
+        .expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "access_type").as_deref(), Some("offline"));
+        assert_eq!(query_param(&url, "prompt").as_deref(), Some("consent"));
+    }
+
+    #[test]
+    fn offline_access_keeps_the_chosen_prompt() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "offlineAccess": true,
+            "prompt": "select_account",
+        })))
+        .expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "access_type").as_deref(), Some("offline"));
+        assert_eq!(
+            query_param(&url, "prompt").as_deref(),
+            Some("select_account")
+        );
+    }
+
+    #[test]
+    fn online_access_is_the_default() {
+        let url =
+            authorize_url(sign_in_request(&serde_json::json!({}))).expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "access_type"), None);
+    }
+
+    #[test]
+    fn hosted_domain_is_sent_as_hd() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "hostedDomain": "example.com",
+        })))
+        .expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "hd").as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn login_hint_is_sent_only_when_given() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "loginHint": "jane@example.com",
+        })))
+        .expect("sign-in did not start");
+        assert_eq!(
+            query_param(&url, "login_hint").as_deref(),
+            Some("jane@example.com")
+        );
+
+        let url =
+            authorize_url(sign_in_request(&serde_json::json!({}))).expect("sign-in did not start");
+        assert_eq!(query_param(&url, "login_hint"), None);
+    }
+
+    #[test]
+    fn a_fresh_nonce_is_generated_per_sign_in() {
+        let nonce = || {
+            let url = authorize_url(sign_in_request(&serde_json::json!({})))
+                .expect("sign-in did not start");
+            query_param(&url, "nonce").expect("no nonce was sent")
+        };
+
+        let (first, second) = (nonce(), nonce());
+
+        assert!(!first.is_empty());
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn given_nonce_is_sent() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "nonce": "app-session-nonce",
+        })))
+        .expect("sign-in did not start");
+
+        assert_eq!(
+            query_param(&url, "nonce").as_deref(),
+            Some("app-session-nonce")
+        );
+    }
+
+    fn verify_nonce(nonce: Option<&str>) -> crate::Result<()> {
+        let (_app, auth) = google_auth();
+        let flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({})))
+            .expect("invalid sign-in");
+        let id_token = unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "nonce": nonce,
+            "exp": 4_102_444_800_i64,
+            "iat": 1_700_000_000,
+        }));
+        let response = token_response(
+            &serde_json::json!({
+                "access_token": "access",
+                "token_type": "Bearer",
+                "id_token": id_token,
+            })
+            .to_string(),
+        );
+        flow.verify(&response, "sent-nonce", None)
+    }
+
+    #[test]
+    fn id_token_with_the_sent_nonce_is_accepted() {
+        assert!(verify_nonce(Some("sent-nonce")).is_ok());
+    }
+
+    #[test]
+    fn id_token_with_another_or_no_nonce_is_rejected() {
+        for nonce in [Some("replayed-nonce"), None] {
+            assert!(
+                matches!(verify_nonce(nonce), Err(crate::Error::AuthenticationFailed(message)) if message == "ID token nonce mismatch")
+            );
+        }
+    }
+
+    #[test]
+    fn expired_id_token_is_only_rejected_when_verifying() {
+        let (_app, auth) = google_auth();
+        let id_token = unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "nonce": "sent-nonce",
+            "exp": 1_700_003_600,
+            "iat": 1_700_000_000,
+        }));
+        let response = token_response(
+            &serde_json::json!({
+                "access_token": "access",
+                "token_type": "Bearer",
+                "id_token": id_token,
+            })
+            .to_string(),
+        );
+        let flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({})))
+            .expect("invalid sign-in");
+
+        assert!(flow.verify(&response, "sent-nonce", None).is_ok());
+
+        let flow = auth
+            .sign_in_flow(sign_in_request(
+                &serde_json::json!({ "verifyIdToken": true }),
+            ))
+            .expect("invalid sign-in");
+
+        assert!(matches!(
+            flow.verify(&response, "sent-nonce", None),
+            Err(crate::Error::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn ipv6_preference_listens_on_the_ipv6_loopback() {
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({ "ipVersion": "v6" })))
+            .expect("invalid sign-in");
+        let url = flow.start().expect("sign-in did not start");
+        let redirect_uri =
+            Url::parse(&query_param(&url, "redirect_uri").expect("no redirect URI was sent"))
+                .expect("invalid redirect URI");
+        let port = redirect_uri.port().expect("redirect URI has no port");
+
+        assert_eq!(redirect_uri.host_str(), Some("localhost"));
+        assert!(TcpStream::connect((Ipv6Addr::LOCALHOST, port)).is_ok());
+    }
+
+    #[test]
+    fn ipv6_loopback_bind_host_is_redirected_to() {
+        let url = authorize_url(sign_in_request(&serde_json::json!({
+            "bindHost": "::1",
+        })))
+        .expect("sign-in did not start");
+        let redirect_uri =
+            Url::parse(&query_param(&url, "redirect_uri").expect("no redirect URI was sent"))
+                .expect("invalid redirect URI");
+
+        assert_eq!(redirect_uri.host_str(), Some("[::1]"));
+    }
+
+    #[test]
+    fn localhost_listens_on_both_families_unless_one_is_picked() {
+        let v4 = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+        assert_eq!(bind_addresses("localhost", None).ok(), Some(vec![v4, v6]));
+        assert_eq!(
+            bind_addresses("localhost", Some(IpVersion::V6)).ok(),
+            Some(vec![v6])
+        );
+        assert_eq!(bind_addresses("[::1]", None).ok(), Some(vec![v6]));
+        assert!(bind_addresses("127.0.0.1", Some(IpVersion::V6)).is_err());
+    }
+
+    #[test]
+    fn success_redirect_is_answered_with_a_302() {
+        let (_app, auth) = google_auth();
+        let flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({
+                "successRedirectUrl": "https://example.com/signed-in",
+            })))
+            .expect("invalid sign-in");
+        let mut response = Vec::new();
+
+        write_page(&mut response, &flow.success_page(None)).expect("failed to write the page");
+
+        let response = String::from_utf8(response).expect("response is not UTF-8");
+        assert!(response.starts_with("HTTP/1.1 302 Found\r\n"));
+        assert!(response.contains("\r\nlocation: https://example.com/signed-in\r\n"));
+    }
+
+    #[test]
+    fn success_redirect_must_be_a_web_page() {
+        assert!(validate_success_redirect_url("https://example.com/signed-in").is_ok());
+        for url in ["not a url", "javascript:alert(1)", "myapp://signed-in"] {
+            assert!(
+                matches!(
+                    validate_success_redirect_url(url),
+                    Err(crate::Error::ConfigurationError { .. })
+                ),
+                "{url} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn desktop_client_needs_no_secret() {
+        let (_app, auth) = google_auth();
+
+        assert!(
+            auth.sign_in_flow(sign_in_request(
+                &serde_json::json!({ "clientType": "desktop" })
+            ))
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn web_client_needs_its_secret() {
+        let (_app, auth) = google_auth();
+        let web_client = serde_json::json!({
+            "clientType": "web",
+            "redirectUri": "http://localhost:8080",
+        });
+
+        let result = auth.sign_in_flow(sign_in_request(&web_client));
+        assert!(
+            matches!(result, Err(crate::Error::ConfigurationError { field: Some(field), .. }) if field == "client_secret")
+        );
+
+        let mut with_secret = web_client;
+        with_secret["clientSecret"] = "secret".into();
+        assert!(auth.sign_in_flow(sign_in_request(&with_secret)).is_ok());
+    }
+
+    #[test]
+    fn fetching_the_profile_needs_the_profile_scopes() {
+        let (_app, auth) = google_auth();
+
+        assert!(
+            auth.sign_in_flow(sign_in_request(
+                &serde_json::json!({ "fetchProfile": true })
+            ))
+            .is_ok()
+        );
+        let result = auth.sign_in_flow(sign_in_request(&serde_json::json!({
+            "fetchProfile": true,
+            "scopes": ["openid", "email"],
+        })));
+        assert!(
+            matches!(result, Err(crate::Error::ConfigurationError { field: Some(field), .. }) if field == "fetch_profile")
+        );
+    }
+
+    /// A token endpoint response for a sign-in with the nonce `test-nonce`.
+    fn signed_in_tokens() -> String {
+        let id_token = unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "email": "jane@example.com",
+            "email_verified": true,
+            "nonce": "test-nonce",
+            "exp": 4_102_444_800_i64,
+            "iat": 1_700_000_000,
+        }));
+        serde_json::json!({
+            "access_token": "access",
+            "token_type": "Bearer",
+            "expires_in": 3599,
+            "refresh_token": "refresh",
+            "scope": "openid https://www.googleapis.com/auth/userinfo.email https://www.googleapis.com/auth/userinfo.profile",
+            "id_token": id_token,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn sign_in_completes_against_a_mock_token_endpoint() {
+        let tokens = signed_in_tokens();
+        let server = MockServer::start(&[(200, &tokens)]);
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({
+                "tokenUrl": server.url("/token"),
+                "nonce": "test-nonce",
+            })))
+            .expect("invalid sign-in");
+
+        let url = flow.start().expect("sign-in did not start");
+        let browser = send_callback(&url, "code=auth-code");
+        flow.wait_for_callback().expect("callback was rejected");
+        let token = flow.exchange().expect("code exchange failed");
+
+        assert_eq!(token.access_token.as_deref(), Some("access"));
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(token.account_id.as_deref(), Some("1234567890"));
+        assert!(matches!(flow.state(), SignInState::Complete));
+        let page = browser.join().expect("browser thread panicked");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        let requests = server.requests();
+        let exchange = &requests[0];
+        assert!(exchange.line.starts_with("POST /token "));
+        assert_eq!(
+            exchange.form_param("grant_type").as_deref(),
+            Some("authorization_code")
+        );
+        assert_eq!(exchange.form_param("code").as_deref(), Some("auth-code"));
+        assert_eq!(
+            exchange.form_param("redirect_uri"),
+            query_param(&url, "redirect_uri")
+        );
+    }
+
+    /// Records the started and stage events `app` emits, as `(stage, request ID)`.
+    fn record_stages(
+        app: &tauri::App<tauri::test::MockRuntime>,
+    ) -> Arc<Mutex<Vec<(String, String)>>> {
+        use tauri::Listener;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        for name in [SIGN_IN_STARTED_EVENT, SIGN_IN_STAGE_EVENT] {
+            let events = events.clone();
+            app.listen_any(name, move |event| {
+                let payload: serde_json::Value =
+                    serde_json::from_str(event.payload()).expect("invalid event payload");
+                let stage = payload["stage"].as_str().unwrap_or("started").to_string();
+                let request_id = payload["requestId"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                events
+                    .lock()
+                    .expect("events poisoned")
+                    .push((stage, request_id));
+            });
+        }
+        events
+    }
+
+    fn stages(events: &Mutex<Vec<(String, String)>>, request_id: &str) -> Vec<String> {
+        let events = events.lock().expect("events poisoned");
+        assert!(events.iter().all(|(_, id)| id == request_id));
+        events.iter().map(|(stage, _)| stage.clone()).collect()
+    }
+
+    #[test]
+    fn each_stage_of_a_sign_in_is_emitted() {
+        let tokens = signed_in_tokens();
+        let server = MockServer::start(&[(200, &tokens)]);
+        let (app, auth) = google_auth();
+        let events = record_stages(&app);
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({
+                "tokenUrl": server.url("/token"),
+                "nonce": "test-nonce",
+                "requestId": "request-1",
+            })))
+            .expect("invalid sign-in");
+
+        let url = flow.start().expect("sign-in did not start");
+        let browser = send_callback(&url, "code=auth-code");
+        flow.wait_for_callback().expect("callback was rejected");
+        flow.exchange().expect("code exchange failed");
+        browser.join().expect("browser thread panicked");
+
+        assert_eq!(
+            stages(&events, "request-1"),
+            ["started", "awaitingCallback", "exchangingCode", "complete"]
+        );
+    }
+
+    #[test]
+    fn failed_sign_in_ends_with_the_failed_stage() {
+        let (app, auth) = google_auth();
+        let events = record_stages(&app);
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(
+                &serde_json::json!({ "requestId": "request-2" }),
+            ))
+            .expect("invalid sign-in");
+
+        let url = flow.start().expect("sign-in did not start");
+        let browser = send_callback(&url, "error=access_denied");
+        assert!(matches!(
+            flow.wait_for_callback(),
+            Err(crate::Error::UserCancelled)
+        ));
+        browser.join().expect("browser thread panicked");
+
+        assert_eq!(
+            stages(&events, "request-2"),
+            ["started", "awaitingCallback", "failed"]
+        );
+    }
+
+    /// The loopback port the redirect URI of `authorize_url` points at.
+    fn redirect_port(authorize_url: &Url) -> u16 {
+        query_param(authorize_url, "redirect_uri")
+            .and_then(|uri| Url::parse(&uri).ok())
+            .and_then(|uri| uri.port())
+            .expect("redirect URI has no port")
+    }
+
+    #[test]
+    fn cancelling_before_the_callback_ends_the_wait() {
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({})))
+            .expect("invalid sign-in");
+        let url = flow.start().expect("sign-in did not start");
+        let handle = flow.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            handle.cancel();
+        });
+
+        let result = flow.wait_for_callback();
+        canceller.join().expect("canceller panicked");
+
+        assert!(matches!(result, Err(crate::Error::UserCancelled)));
+        assert!(matches!(flow.state(), SignInState::Failed));
+        // The loopback server was dropped with the wait
+        assert!(TcpStream::connect((Ipv4Addr::LOCALHOST, redirect_port(&url))).is_err());
+    }
+
+    #[test]
+    fn sign_in_is_cancelled_by_its_session_id() {
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({})))
+            .expect("invalid sign-in");
+        flow.start().expect("sign-in did not start");
+
+        assert!(auth.cancel_sign_in(flow.session_id()));
+        assert!(!auth.cancel_sign_in(flow.session_id()));
+        assert!(matches!(
+            flow.wait_for_callback(),
+            Err(crate::Error::UserCancelled)
+        ));
+    }
+
+    #[test]
+    fn code_challenge_method_follows_the_pkce_method() {
+        for (pkce_method, expected) in [
+            (serde_json::Value::Null, Some("S256")),
+            ("S256".into(), Some("S256")),
+            ("plain".into(), Some("plain")),
+            ("none".into(), None),
+        ] {
+            let url = authorize_url(sign_in_request(&serde_json::json!({
+                "pkceMethod": pkce_method,
+            })))
+            .expect("sign-in did not start");
+
+            assert_eq!(
+                query_param(&url, "code_challenge_method").as_deref(),
+                expected,
+                "for {pkce_method}"
+            );
+            assert_eq!(
+                query_param(&url, "code_challenge").is_some(),
+                expected.is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn additional_scopes_are_requested_incrementally() {
+        let drive = "https://www.googleapis.com/auth/drive.file";
+        let request = sign_in_request(&serde_json::json!({ "scopes": ["openid", "email"] }))
+            .with_additional_scopes(vec![drive.to_string(), "email".to_string()]);
+
+        let url = authorize_url(request).expect("sign-in did not start");
+
+        assert_eq!(
+            query_param(&url, "include_granted_scopes").as_deref(),
+            Some("true")
+        );
+        assert_eq!(
+            query_param(&url, "scope").as_deref(),
+            Some(format!("openid email {drive}").as_str())
+        );
+    }
+
+    #[test]
+    fn granted_scopes_are_not_included_by_default() {
+        let url =
+            authorize_url(sign_in_request(&serde_json::json!({}))).expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "include_granted_scopes"), None);
+    }
+
+    #[test]
+    fn rejected_code_fails_the_exchange_with_invalid_grant() {
+        let server = MockServer::start(&[(
+            400,
+            r#"{"error":"invalid_grant","error_description":"Malformed auth code."}"#,
+        )]);
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({
+                "tokenUrl": server.url("/token"),
+            })))
+            .expect("invalid sign-in");
+
+        let url = flow.start().expect("sign-in did not start");
+        let browser = send_callback(&url, "code=reused-code");
+        flow.wait_for_callback().expect("callback was rejected");
+        let result = flow.exchange();
+        let page = browser.join().expect("browser thread panicked");
+
+        assert!(
+            matches!(result, Err(crate::Error::InvalidGrant(description)) if description == "Malformed auth code.")
+        );
+        assert!(page.contains(SIGN_IN_FAILED_RESPONSE));
+    }
+
+    #[test]
+    fn authorize_url_requests_the_normalized_scopes() {
+        let request = sign_in_request(&serde_json::json!({
+            "scopes": [" openid", "email ", "openid"],
+        }));
+
+        let url = authorize_url(request).expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "scope").as_deref(), Some("openid email"));
+    }
+
+    #[test]
+    fn verifying_the_id_token_requests_openid() {
+        let request = sign_in_request(&serde_json::json!({
+            "scopes": ["email"],
+            "verifyIdToken": true,
+        }));
+
+        let url = authorize_url(request).expect("sign-in did not start");
+
+        assert_eq!(query_param(&url, "scope").as_deref(), Some("email openid"));
+    }
+
+    /// A loopback redirect URI on a port that was free a moment ago.
+    fn fixed_redirect_uri() -> String {
+        let listener =
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("failed to bind");
+        let port = listener.local_addr().expect("no local address").port();
+        format!("http://127.0.0.1:{port}")
+    }
+
+    #[test]
+    fn server_auth_code_is_handed_off_with_its_state_and_verifier() {
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({
+                "serverClientId": "backend-client-id",
+                "redirectUri": fixed_redirect_uri(),
+            })))
+            .expect("invalid sign-in");
+
+        let url = flow.start().expect("sign-in did not start");
+        let browser = send_callback(&url, "code=server-code");
+        flow.wait_for_callback().expect("callback was rejected");
+        let token = flow.exchange().expect("code hand-off failed");
+        browser.join().expect("browser thread panicked");
+
+        assert_eq!(
+            query_param(&url, "client_id").as_deref(),
+            Some("backend-client-id")
+        );
+        assert_eq!(token.server_auth_code.as_deref(), Some("server-code"));
+        assert_eq!(token.server_auth_state, query_param(&url, "state"));
+        assert_eq!(token.access_token, None);
+        assert!(token.is_expired());
+        let verifier = PkceCodeVerifier::new(
+            token
+                .server_code_verifier
+                .expect("no PKCE verifier was returned"),
+        );
+        assert_eq!(
+            query_param(&url, "code_challenge").as_deref(),
+            Some(PkceCodeChallenge::from_code_verifier_sha256(&verifier).as_str())
+        );
+    }
+
+    #[test]
+    fn server_auth_code_without_pkce_has_no_verifier() {
+        let (_app, auth) = google_auth();
+        let mut flow = auth
+            .sign_in_flow(sign_in_request(&serde_json::json!({
+                "serverClientId": "backend-client-id",
+                "redirectUri": fixed_redirect_uri(),
+                "pkceMethod": "none",
+            })))
+            .expect("invalid sign-in");
+
+        let url = flow.start().expect("sign-in did not start");
+        let browser = send_callback(&url, "code=server-code");
+        flow.wait_for_callback().expect("callback was rejected");
+        let token = flow.exchange().expect("code hand-off failed");
+        browser.join().expect("browser thread panicked");
+
+        assert_eq!(query_param(&url, "code_challenge"), None);
+        assert!(token.server_auth_state.is_some());
+        assert_eq!(token.server_code_verifier, None);
+    }
+
+    fn webview_storage_error(overrides: &serde_json::Value) -> Option<String> {
+        let request = sign_in_request(overrides);
+        match validate_options(&request, &SignInRequest::default_scopes()) {
+            Err(crate::Error::ConfigurationError { field, .. }) => field,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn webview_can_keep_its_data_in_a_directory() {
+        assert_eq!(
+            webview_storage_error(&serde_json::json!({
+                "authMode": "webview",
+                "webviewDataDirectory": "/tmp/google-auth-webview",
+            })),
+            None
+        );
+    }
+
+    #[test]
+    fn webview_data_directory_needs_a_persistent_webview() {
+        for overrides in [
+            serde_json::json!({ "webviewDataDirectory": "/tmp/google-auth-webview" }),
+            serde_json::json!({
+                "authMode": "webview",
+                "privateWindow": true,
+                "webviewDataDirectory": "/tmp/google-auth-webview",
+            }),
+        ] {
+            assert_eq!(
+                webview_storage_error(&overrides).as_deref(),
+                Some("webview_data_directory"),
+                "{overrides} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn webview_cookies_need_the_webview_auth_mode() {
+        assert_eq!(
+            webview_storage_error(&serde_json::json!({ "returnWebviewCookies": true })).as_deref(),
+            Some("return_webview_cookies")
+        );
+        assert_eq!(
+            webview_storage_error(&serde_json::json!({
+                "authMode": "webview",
+                "returnWebviewCookies": true,
+            })),
+            None
+        );
+    }
+}
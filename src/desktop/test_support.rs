@@ -0,0 +1,215 @@
+//! Shared helpers for tests driving the desktop flows.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::de::DeserializeOwned;
+use tauri::App;
+use tauri::test::{MockRuntime, mock_app};
+use url::Url;
+
+use super::{Accounts, GoogleAuth, Sessions, SpecialTokenResponse};
+use crate::models::{RefreshTokenRequest, SignInRequest, SignOutRequest};
+
+/// A [`GoogleAuth`] on a mock app, without token store or customizations. The app
+/// is returned so it outlives the plugin.
+pub fn google_auth() -> (App<MockRuntime>, GoogleAuth<MockRuntime>) {
+    let app = mock_app();
+    let auth = GoogleAuth {
+        app: app.handle().clone(),
+        success_page_renderer: None,
+        state_codec: None,
+        port_fallback: None,
+        id_token_keys: Arc::default(),
+        sessions: Sessions::default(),
+        accounts: Arc::new(Accounts {
+            current: Mutex::default(),
+            store: None,
+        }),
+    };
+    (app, auth)
+}
+
+/// A sign-in request for the client `client-id`, with `overrides` applied on top,
+/// given as the camelCase JSON the guest sends.
+pub fn sign_in_request(overrides: &serde_json::Value) -> SignInRequest {
+    request(serde_json::json!({ "clientId": "client-id" }), overrides)
+}
+
+/// A refresh of the refresh token `refresh-token` of the client `client-id` at
+/// `token_url`, with `overrides` applied on top.
+pub fn refresh_request(token_url: &str, overrides: &serde_json::Value) -> RefreshTokenRequest {
+    request(
+        serde_json::json!({
+            "clientId": "client-id",
+            "refreshToken": "refresh-token",
+            "tokenUrl": token_url,
+        }),
+        overrides,
+    )
+}
+
+/// A sign-out revoking at `revocation_url`, with `overrides` applied on top.
+pub fn sign_out_request(revocation_url: &str, overrides: &serde_json::Value) -> SignOutRequest {
+    request(
+        serde_json::json!({ "revocationUrl": revocation_url }),
+        overrides,
+    )
+}
+
+fn request<T: DeserializeOwned>(
+    mut request: serde_json::Value,
+    overrides: &serde_json::Value,
+) -> T {
+    if let (Some(request), Some(overrides)) = (request.as_object_mut(), overrides.as_object()) {
+        request.extend(overrides.clone());
+    }
+    serde_json::from_value(request).expect("invalid test request")
+}
+
+/// The value of the query parameter `name` of `url`.
+pub fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Redirects a browser to the loopback server like Google would after consent,
+/// with `params` and the state of `authorize_url` in the query, on another thread.
+/// The thread returns the page the browser was answered with.
+pub fn send_callback(authorize_url: &Url, params: &str) -> JoinHandle<String> {
+    let redirect_uri = query_param(authorize_url, "redirect_uri").expect("no redirect URI");
+    let port = Url::parse(&redirect_uri)
+        .ok()
+        .and_then(|uri| uri.port())
+        .expect("redirect URI has no port");
+    let state = query_param(authorize_url, "state").expect("no state");
+    let request =
+        format!("GET /?{params}&state={state} HTTP/1.1\r\nHost: localhost:{port}\r\n\r\n");
+    std::thread::spawn(move || {
+        let mut browser =
+            TcpStream::connect((Ipv4Addr::LOCALHOST, port)).expect("failed to connect");
+        browser
+            .write_all(request.as_bytes())
+            .expect("failed to send the callback");
+        let mut page = String::new();
+        let _ = browser.read_to_string(&mut page);
+        page
+    })
+}
+
+/// Starts a sign-in for `request` and returns its authorization URL.
+pub fn authorize_url(request: SignInRequest) -> crate::Result<Url> {
+    let (_app, auth) = google_auth();
+    auth.sign_in_flow(request)?.start()
+}
+
+/// Parses a token endpoint response body.
+pub fn token_response(json: &str) -> SpecialTokenResponse {
+    serde_json::from_str(json).expect("invalid test token response")
+}
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// The request line, e.g. `POST /token HTTP/1.1`.
+    pub line: String,
+    /// Header names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of the form field `name` of the body.
+    pub fn form_param(&self, name: &str) -> Option<String> {
+        url::form_urlencoded::parse(self.body.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+/// A local HTTP server standing in for Google's endpoints. It answers each request
+/// with the next queued `(status, body)` JSON response, then stops.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    pub fn start(responses: &[(u16, &str)]) -> Self {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("failed to bind");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("no local address")
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let responses: Vec<(u16, String)> = responses
+            .iter()
+            .map(|&(status, body)| (status, body.to_string()))
+            .collect();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let Ok((stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut reader = BufReader::new(stream);
+                let Some(request) = read_request(&mut reader) else {
+                    continue;
+                };
+                // Recorded before answering, so the client sees it once it has the response
+                recorded.lock().expect("server poisoned").push(request);
+                let response = format!(
+                    "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = reader.get_mut().write_all(response.as_bytes());
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// The URL of `path` on this server.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.url)
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().expect("server poisoned").clone()
+    }
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<RecordedRequest> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let Some((name, value)) = header.trim_end().split_once(':') else {
+            break;
+        };
+        headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut request_body = vec![0; length];
+    reader.read_exact(&mut request_body).ok()?;
+    Some(RecordedRequest {
+        line: line.trim_end().to_string(),
+        headers,
+        body: String::from_utf8_lossy(&request_body).into_owned(),
+    })
+}
@@ -0,0 +1,185 @@
+//! Transports the loopback redirect server accepts connections on.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Accepts the browser's connections to the redirect URI.
+///
+/// Google only redirects to `http://` loopback URIs, so [`TcpTransport`] is the
+/// default and only implementation. Everything past accepting a connection is
+/// written against this trait so other transports can be slotted in later.
+pub trait CallbackTransport {
+    type Stream: Read + Write;
+
+    /// The port the redirect URI has to point at.
+    fn port(&self) -> std::io::Result<u16>;
+
+    /// Accepts the next connection, or returns `None` when none is pending so the
+    /// caller can check for cancellation before polling again.
+    fn accept(&self) -> std::io::Result<Option<Self::Stream>>;
+}
+
+/// How long an accepted connection may stall reading its request or the response,
+/// so an idle socket (e.g. a browser's preconnect) can't hold up the accept loop.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The port [`TcpTransport::bind`] listens on.
+pub enum BindPort {
+    /// Exactly this port, as given by the redirect URI.
+    Fixed(u16),
+    /// The first free port of the range.
+    Range(RangeInclusive<u16>),
+    /// An OS-assigned port, with this many tries.
+    Random(u32),
+}
+
+/// TCP listeners on loopback addresses, all sharing one port.
+pub struct TcpTransport(Vec<TcpListener>);
+
+impl TcpTransport {
+    /// Binds the first of `addrs` to `port`, then the others to the same port where
+    /// possible. A `localhost` redirect may reach either `127.0.0.1` or `::1`
+    /// depending on the browser, so listening on both keeps the callback from being
+    /// dropped.
+    pub fn bind(addrs: &[IpAddr], port: BindPort) -> crate::Result<Self> {
+        let (&primary, others) = addrs
+            .split_first()
+            .ok_or_else(|| crate::Error::configuration(None, "No loopback address to bind"))?;
+        let listener = match port {
+            BindPort::Fixed(p) => bind_reusable(SocketAddr::new(primary, p)).map_err(|e| {
+                crate::Error::NetworkError(format!("Failed to bind to port {p}: {e}"))
+            })?,
+            BindPort::Range(range) => bind_port_in_range(primary, range)?,
+            // Port 0 means the OS assigns an available port
+            BindPort::Random(attempts) => bind_random_port(primary, attempts)?,
+        };
+        let port = listener.local_addr()?.port();
+
+        let mut listeners = vec![listener];
+        // The other address families are best effort, e.g. IPv6 may be disabled
+        listeners.extend(
+            others
+                .iter()
+                .filter_map(|&addr| bind_reusable(SocketAddr::new(addr, port)).ok()),
+        );
+        for listener in &listeners {
+            listener.set_nonblocking(true)?;
+        }
+        Ok(Self(listeners))
+    }
+}
+
+impl CallbackTransport for TcpTransport {
+    type Stream = TcpStream;
+
+    fn port(&self) -> std::io::Result<u16> {
+        Ok(self.0[0].local_addr()?.port())
+    }
+
+    fn accept(&self) -> std::io::Result<Option<TcpStream>> {
+        for listener in &self.0 {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    // Some platforms hand out streams inheriting the listener's non-blocking mode
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+                    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+                    return Ok(Some(stream));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Binds a loopback listener on a fixed port with `SO_REUSEADDR` set, so a port left
+/// in `TIME_WAIT` by a previous run can be reused immediately. On Windows the option
+/// would let other processes steal the port, so it is only set on Unix.
+fn bind_reusable(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(128)?;
+    Ok(socket.into())
+}
+
+/// Binds a loopback listener on the first port of `range` that isn't taken.
+fn bind_port_in_range(addr: IpAddr, range: RangeInclusive<u16>) -> crate::Result<TcpListener> {
+    let (start, end) = (*range.start(), *range.end());
+    range
+        .into_iter()
+        .find_map(|port| bind_reusable(SocketAddr::new(addr, port)).ok())
+        .ok_or_else(|| {
+            crate::Error::NetworkError(format!("Every port in range {start}-{end} is busy"))
+        })
+}
+
+/// Binds a loopback listener on an OS-assigned port, retrying a few times since
+/// binding can transiently fail under socket exhaustion.
+fn bind_random_port(addr: IpAddr, attempts: u32) -> crate::Result<TcpListener> {
+    let mut errors = Vec::new();
+    for _ in 0..attempts.max(1) {
+        match TcpListener::bind((addr, 0)) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    Err(crate::Error::NetworkError(format!(
+        "Failed to bind to any available port after {} attempts: {}",
+        errors.len(),
+        errors.join("; ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    /// A listener occupying a port whose successor is free at the time.
+    fn occupy_port_before_a_free_one() -> TcpListener {
+        loop {
+            let occupied = TcpListener::bind((LOOPBACK, 0)).expect("failed to bind");
+            let port = occupied.local_addr().expect("no local address").port();
+            if port < u16::MAX && TcpListener::bind((LOOPBACK, port + 1)).is_ok() {
+                return occupied;
+            }
+        }
+    }
+
+    #[test]
+    fn port_range_skips_an_occupied_port() {
+        let occupied = occupy_port_before_a_free_one();
+        let port = occupied.local_addr().expect("no local address").port();
+
+        let transport = TcpTransport::bind(&[LOOPBACK], BindPort::Range(port..=port + 1))
+            .expect("no port in the range was bound");
+
+        assert_eq!(transport.port().expect("transport has no port"), port + 1);
+    }
+
+    #[test]
+    fn busy_port_range_is_a_network_error() {
+        let occupied = TcpListener::bind((LOOPBACK, 0)).expect("failed to bind");
+        let port = occupied.local_addr().expect("no local address").port();
+
+        let result = TcpTransport::bind(&[LOOPBACK], BindPort::Range(port..=port));
+
+        assert!(
+            matches!(result, Err(crate::Error::NetworkError(message)) if message.contains("is busy"))
+        );
+    }
+}
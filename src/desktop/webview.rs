@@ -0,0 +1,202 @@
+//! Showing the authorization URL in a window of the app instead of the system browser.
+//!
+//! The window is redirected to the loopback server like a browser would be, so the
+//! callback is received, validated and answered exactly as in the browser flow; the
+//! loopback listener is still required. Navigation to the redirect URI is watched so
+//! that closing the window after it no longer cancels the sign-in.
+//! Google blocks sign-ins from embedded webviews it detects (`disallowed_useragent`),
+//! so this only suits apps that accept that risk.
+//!
+//! The window shares the app's webview storage unless it is private, which keeps
+//! nothing, or given a data directory of its own, which keeps its cookies across
+//! launches for single sign-on.
+//!
+//! With `return_webview_cookies` the window's Google cookies are read before it is
+//! closed and handed to the app. They are the user's Google session, valid for
+//! every Google service, so this is strictly opt-in.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::webview::Cookie;
+use tauri::{AppHandle, Runtime, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent};
+use url::Url;
+
+use crate::models::WebviewCookie;
+
+const WINDOW_TITLE: &str = "Sign in with Google";
+/// The page whose cookies make up the Google session, including those of
+/// `.google.com`.
+const GOOGLE_ACCOUNTS_URL: &str = "https://accounts.google.com/";
+const WINDOW_SIZE: (f64, f64) = (500.0, 650.0);
+
+/// Opens `url` in a new window for the sign-in `session_id`, in incognito mode when
+/// `private` or else storing its data in `data_directory`, if given. `WKWebView`
+/// can't use a custom directory, so on macOS the app's default store is used.
+/// Closing the window before it reached `redirect_uri` sets `cancelled`, so the
+/// user can back out of the sign-in.
+pub fn open<R: Runtime>(
+    app: &AppHandle<R>,
+    session_id: &str,
+    url: &Url,
+    redirect_uri: &Url,
+    private: bool,
+    data_directory: Option<&Path>,
+    cancelled: Arc<AtomicBool>,
+) -> tauri::Result<WebviewWindow<R>> {
+    let redirected = Arc::new(AtomicBool::new(false));
+    let redirect_uri = redirect_uri.clone();
+    let on_navigation = {
+        let redirected = redirected.clone();
+        move |target: &Url| {
+            if is_redirect(target, &redirect_uri) {
+                redirected.store(true, Ordering::SeqCst);
+            }
+            true
+        }
+    };
+    let mut builder = WebviewWindowBuilder::new(
+        app,
+        format!("google-auth-{session_id}"),
+        WebviewUrl::External(url.clone()),
+    )
+    .title(WINDOW_TITLE)
+    .inner_size(WINDOW_SIZE.0, WINDOW_SIZE.1)
+    .center()
+    .incognito(private)
+    .on_navigation(on_navigation);
+    if let Some(data_directory) = data_directory {
+        builder = builder.data_directory(data_directory.to_path_buf());
+    }
+    let window = builder.build()?;
+    window.on_window_event(move |event| {
+        if matches!(event, WindowEvent::Destroyed) && !redirected.load(Ordering::SeqCst) {
+            cancelled.store(true, Ordering::SeqCst);
+        }
+    });
+    Ok(window)
+}
+
+/// Reads the cookies `window` sends to Google's sign-in page. Must not run on the
+/// main thread, where it deadlocks on Windows; the sign-in commands are async.
+pub fn google_cookies<R: Runtime>(window: &WebviewWindow<R>) -> tauri::Result<Vec<WebviewCookie>> {
+    let url = Url::parse(GOOGLE_ACCOUNTS_URL).expect("invalid Google accounts URL");
+    Ok(window
+        .cookies_for_url(url)?
+        .iter()
+        .map(webview_cookie)
+        .collect())
+}
+
+fn webview_cookie(cookie: &Cookie<'_>) -> WebviewCookie {
+    WebviewCookie {
+        name: cookie.name().to_string(),
+        value: cookie.value().to_string(),
+        domain: cookie.domain().map(str::to_string),
+        path: cookie.path().map(str::to_string),
+        secure: cookie.secure().unwrap_or(false),
+        http_only: cookie.http_only().unwrap_or(false),
+        expires_at: cookie
+            .expires_datetime()
+            .map(time::OffsetDateTime::unix_timestamp),
+    }
+}
+
+/// Whether navigating to `target` returns to the loopback `redirect_uri`, which
+/// carries the code or error: same scheme, host, port and path, any query.
+fn is_redirect(target: &Url, redirect_uri: &Url) -> bool {
+    target.scheme() == redirect_uri.scheme()
+        && target.host() == redirect_uri.host()
+        && target.port_or_known_default() == redirect_uri.port_or_known_default()
+        && target.path() == redirect_uri.path()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).expect("invalid test URL")
+    }
+
+    #[test]
+    fn callback_to_the_redirect_uri_is_recognized() {
+        let redirect_uri = url("http://127.0.0.1:8080");
+
+        assert!(is_redirect(
+            &url("http://127.0.0.1:8080/?code=abc&state=xyz"),
+            &redirect_uri
+        ));
+        assert!(is_redirect(
+            &url("http://127.0.0.1:8080/?error=access_denied&state=xyz"),
+            &redirect_uri
+        ));
+    }
+
+    #[test]
+    fn redirect_uri_with_a_path_must_match_it() {
+        let redirect_uri = url("http://localhost:8080/callback");
+
+        assert!(is_redirect(
+            &url("http://localhost:8080/callback?code=abc"),
+            &redirect_uri
+        ));
+        assert!(!is_redirect(
+            &url("http://localhost:8080/?code=abc"),
+            &redirect_uri
+        ));
+    }
+
+    #[test]
+    fn other_pages_are_not_the_redirect() {
+        let redirect_uri = url("http://127.0.0.1:8080");
+
+        assert!(!is_redirect(
+            &url("https://accounts.google.com/o/oauth2/auth?redirect_uri=http://127.0.0.1:8080"),
+            &redirect_uri
+        ));
+        assert!(!is_redirect(
+            &url("http://127.0.0.1:9090/?code=abc"),
+            &redirect_uri
+        ));
+        assert!(!is_redirect(
+            &url("http://localhost:8080/?code=abc"),
+            &redirect_uri
+        ));
+        assert!(!is_redirect(
+            &url("https://127.0.0.1:8080/?code=abc"),
+            &redirect_uri
+        ));
+    }
+
+    #[test]
+    fn cookies_keep_their_attributes() {
+        let cookie = Cookie::build(("SID", "session-secret"))
+            .domain(".google.com")
+            .path("/")
+            .secure(true)
+            .http_only(true)
+            .expires(
+                time::OffsetDateTime::from_unix_timestamp(4_102_444_800).expect("invalid time"),
+            )
+            .build();
+
+        let cookie = webview_cookie(&cookie);
+
+        assert_eq!(cookie.name, "SID");
+        assert_eq!(cookie.value, "session-secret");
+        assert_eq!(cookie.domain.as_deref(), Some("google.com"));
+        assert_eq!(cookie.path.as_deref(), Some("/"));
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.expires_at, Some(4_102_444_800));
+    }
+
+    #[test]
+    fn session_cookies_have_no_expiry() {
+        let cookie = webview_cookie(&Cookie::new("NID", "value"));
+
+        assert_eq!(cookie.expires_at, None);
+        assert!(!cookie.secure && !cookie.http_only);
+    }
+}
@@ -1,4 +1,7 @@
-use serde::{Serialize, ser::Serializer};
+use serde::{
+    Serialize,
+    ser::{SerializeStruct, Serializer},
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -13,23 +16,172 @@ pub enum Error {
     AuthenticationFailed(String),
     #[error("User cancelled the sign-in flow")]
     UserCancelled,
+    /// Waiting for the sign-in, or a request to Google, took too long.
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    /// A `prompt=none` request needs the user; holds Google's reason, e.g.
+    /// `consent_required` or `login_required`.
+    #[error("User interaction required: {0}")]
+    InteractionRequired(String),
     #[error("No user is currently signed in")]
     NoUserSignedIn,
-    #[error("Invalid client ID provided")]
+    #[error(
+        "Invalid client ID or secret. Check the OAuth client credentials in the Google Cloud Console (APIs & Services > Credentials)"
+    )]
     InvalidClientId,
     #[error("Token refresh failed: {0}")]
     TokenRefreshFailed(String),
+    /// Google rejected the authorization code or refresh token (`invalid_grant`),
+    /// e.g. because it expired, was revoked or was already used; signing in again
+    /// is the only fix. Holds Google's description.
+    #[error("Invalid grant: {0}")]
+    InvalidGrant(String),
     #[error("Network error: {0}")]
     NetworkError(String),
-    #[error("Configuration error: {0}")]
-    ConfigurationError(String),
+    /// Google Play services are missing, disabled or outdated on the Android device;
+    /// holds the reason, e.g. `SERVICE_VERSION_UPDATE_REQUIRED`.
+    #[error("Google Play services are unavailable: {0}")]
+    PlayServicesUnavailable(String),
+    /// One of Google's endpoints answered with a non-success status.
+    #[error("HTTP {status}: {body}")]
+    HttpStatus { status: u16, body: String },
+    #[error(
+        "Configuration error{}: {message}",
+        field.as_ref().map(|f| format!(" in `{f}`")).unwrap_or_default()
+    )]
+    ConfigurationError {
+        field: Option<String>,
+        message: String,
+    },
 }
 
+impl Error {
+    /// Stable identifier of the error kind, e.g. for analytics.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            #[cfg(mobile)]
+            Self::PluginInvoke(_) => "plugin_invoke",
+            Self::AuthenticationFailed(_) => "authentication_failed",
+            Self::UserCancelled => "user_cancelled",
+            Self::Timeout(_) => "timeout",
+            Self::InteractionRequired(_) => "interaction_required",
+            Self::NoUserSignedIn => "no_user_signed_in",
+            Self::InvalidClientId => "invalid_client_id",
+            Self::TokenRefreshFailed(_) => "token_refresh_failed",
+            Self::InvalidGrant(_) => "invalid_grant",
+            Self::NetworkError(_) => "network_error",
+            Self::PlayServicesUnavailable(_) => "play_services_unavailable",
+            Self::HttpStatus { .. } => "http_status",
+            Self::ConfigurationError { .. } => "configuration_error",
+        }
+    }
+
+    /// Whether the error is likely to go away when retrying later: a
+    /// [`Error::Timeout`], an [`Error::NetworkError`] or an [`Error::HttpStatus`]
+    /// with a 5xx status. Rejections by Google, such as [`Error::InvalidGrant`],
+    /// are not.
+    #[must_use]
+    pub const fn is_transient(&self) -> bool {
+        match self {
+            Self::Timeout(_) | Self::NetworkError(_) => true,
+            Self::HttpStatus { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn configuration(field: Option<&str>, message: impl Into<String>) -> Self {
+        Self::ConfigurationError {
+            field: field.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+/// Serializes as `{ "code": "...", "message": "..." }`, so frontends can branch on
+/// [`Error::code`] while still showing the message.
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        let mut error = serializer.serialize_struct("Error", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_status(status: u16) -> Error {
+        Error::HttpStatus {
+            status,
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn timeouts_network_errors_and_server_errors_are_transient() {
+        assert!(Error::Timeout("token request".into()).is_transient());
+        assert!(Error::NetworkError("connection reset".into()).is_transient());
+        assert!(http_status(500).is_transient());
+        assert!(http_status(503).is_transient());
+    }
+
+    #[test]
+    fn rejections_are_not_transient() {
+        assert!(!http_status(400).is_transient());
+        assert!(!http_status(429).is_transient());
+        assert!(!Error::InvalidGrant("Token has been revoked".into()).is_transient());
+        assert!(!Error::TokenRefreshFailed("invalid_client".into()).is_transient());
+        assert!(!Error::UserCancelled.is_transient());
+    }
+
+    fn json(error: &Error) -> serde_json::Value {
+        serde_json::to_value(error).expect("serialization failed")
+    }
+
+    #[test]
+    fn errors_serialize_as_code_and_message() {
+        assert_eq!(
+            json(&Error::UserCancelled),
+            serde_json::json!({
+                "code": "user_cancelled",
+                "message": "User cancelled the sign-in flow",
+            })
+        );
+        assert_eq!(
+            json(&Error::NetworkError("connection reset".into())),
+            serde_json::json!({
+                "code": "network_error",
+                "message": "Network error: connection reset",
+            })
+        );
+        assert_eq!(
+            json(&http_status(503)),
+            serde_json::json!({ "code": "http_status", "message": "HTTP 503: " })
+        );
+    }
+
+    #[test]
+    fn configuration_errors_name_their_field_in_the_message() {
+        assert_eq!(
+            json(&Error::configuration(
+                Some("redirect_uri"),
+                "Invalid redirect URL"
+            )),
+            serde_json::json!({
+                "code": "configuration_error",
+                "message": "Configuration error in `redirect_uri`: Invalid redirect URL",
+            })
+        );
+        assert_eq!(
+            json(&Error::configuration(None, "No loopback address to bind"))["message"],
+            "Configuration error: No loopback address to bind"
+        );
     }
 }
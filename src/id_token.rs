@@ -0,0 +1,203 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::de::DeserializeOwned;
+
+use crate::models::{IdTokenClaims, JoseHeader};
+
+/// Decodes the JOSE header of an ID token, e.g. to check which algorithm and
+/// key ID Google signed it with. The signature is not verified.
+pub fn decode_id_token_header(id_token: &str) -> crate::Result<JoseHeader> {
+    decode_segment(id_token, 0, "header")
+}
+
+/// Decodes the claims of an ID token. The signature is not verified, so only trust
+/// them for a token received straight from Google over TLS, as sign-ins do.
+pub fn decode_id_token_claims(id_token: &str) -> crate::Result<IdTokenClaims> {
+    decode_segment(id_token, 1, "claims")
+}
+
+/// Fails with [`crate::Error::AuthenticationFailed`] unless the ID token carries a
+/// verified email, for sign-ins with `require_verified_email` set.
+pub fn require_verified_email(id_token: Option<&str>) -> crate::Result<()> {
+    let id_token = id_token.ok_or_else(|| {
+        crate::Error::AuthenticationFailed(
+            "No ID token returned; cannot check the email is verified".to_string(),
+        )
+    })?;
+    let claims = decode_id_token_claims(id_token)?;
+    if !claims.is_email_verified() {
+        return Err(crate::Error::AuthenticationFailed(
+            "The account's email address is not verified".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Fails with [`crate::Error::AuthenticationFailed`] unless the ID token's `hd` claim
+/// is `domain`, or is present at all for `*`. The `hd` authorize parameter only
+/// narrows the account chooser, so the returned claim is what restricts the domain.
+pub fn require_hosted_domain(id_token: Option<&str>, domain: &str) -> crate::Result<()> {
+    let id_token = id_token.ok_or_else(|| {
+        crate::Error::AuthenticationFailed(
+            "No ID token returned; cannot check the hosted domain".to_string(),
+        )
+    })?;
+    let claims = decode_id_token_claims(id_token)?;
+    match claims.hd.as_deref() {
+        Some(hd) if domain == "*" || hd.eq_ignore_ascii_case(domain) => Ok(()),
+        _ => Err(crate::Error::AuthenticationFailed(
+            "Hosted domain mismatch".to_string(),
+        )),
+    }
+}
+
+/// The current time in Unix seconds, which ID token lifetimes are checked against.
+#[cfg(desktop)]
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+/// Base64url-decodes and parses one dot-separated segment of a JWT.
+pub fn decode_segment<T: DeserializeOwned>(
+    id_token: &str,
+    index: usize,
+    name: &str,
+) -> crate::Result<T> {
+    let segment = id_token
+        .split('.')
+        .nth(index)
+        .filter(|segment| !segment.is_empty())
+        .ok_or_else(|| {
+            crate::Error::AuthenticationFailed(format!("Malformed ID token: missing {name}"))
+        })?;
+    let bytes = URL_SAFE_NO_PAD.decode(segment).map_err(|e| {
+        crate::Error::AuthenticationFailed(format!("Failed to decode ID token {name}: {e}"))
+    })?;
+    serde_json::from_slice(&bytes).map_err(|e| {
+        crate::Error::AuthenticationFailed(format!("Failed to parse ID token {name}: {e}"))
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// An unsigned JWT (`alg: none`) carrying `claims`.
+    pub fn unsigned_jwt(claims: &serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("{header}.{payload}.")
+    }
+
+    #[test]
+    fn claims_are_decoded_from_the_payload() {
+        let id_token = unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "email": "jane@example.com",
+            "email_verified": true,
+            "name": "Jane Doe",
+            "picture": "https://example.com/jane.png",
+            "hd": "example.com",
+            "exp": 1_700_003_600,
+            "iat": 1_700_000_000,
+        }));
+
+        let claims = decode_id_token_claims(&id_token).expect("claims were not decoded");
+
+        assert_eq!(claims.sub, "1234567890");
+        assert_eq!(claims.email.as_deref(), Some("jane@example.com"));
+        assert_eq!(claims.email_verified, Some(true));
+        assert_eq!(claims.name.as_deref(), Some("Jane Doe"));
+        assert_eq!(
+            claims.picture.as_deref(),
+            Some("https://example.com/jane.png")
+        );
+        assert_eq!(claims.hd.as_deref(), Some("example.com"));
+        assert_eq!(claims.exp, 1_700_003_600);
+        assert_eq!(claims.iat, 1_700_000_000);
+        assert!(claims.is_email_verified());
+    }
+
+    #[test]
+    fn optional_claims_may_be_missing() {
+        let id_token = unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "exp": 1_700_003_600,
+            "iat": 1_700_000_000,
+        }));
+
+        let claims = decode_id_token_claims(&id_token).expect("claims were not decoded");
+
+        assert_eq!(claims.email, None);
+        assert_eq!(claims.hd, None);
+        assert!(!claims.is_email_verified());
+    }
+
+    #[test]
+    fn header_is_decoded_without_checking_the_signature() {
+        let id_token = unsigned_jwt(&serde_json::json!({}));
+
+        let header = decode_id_token_header(&id_token).expect("header was not decoded");
+
+        assert_eq!(header.alg, "none");
+        assert_eq!(header.kid, None);
+        assert_eq!(header.typ.as_deref(), Some("JWT"));
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for id_token in [
+            "",
+            "header-only",
+            "eyJhbGciOiJub25lIn0.not base64.",
+            "eyJhbGciOiJub25lIn0.e30.",
+        ] {
+            assert!(
+                matches!(
+                    decode_id_token_claims(id_token),
+                    Err(crate::Error::AuthenticationFailed(_))
+                ),
+                "{id_token:?} was decoded"
+            );
+        }
+    }
+
+    fn token_for_domain(hd: Option<&str>) -> String {
+        unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "hd": hd,
+            "exp": 1_700_003_600,
+            "iat": 1_700_000_000,
+        }))
+    }
+
+    #[test]
+    fn matching_hosted_domain_is_accepted() {
+        let id_token = token_for_domain(Some("example.com"));
+
+        assert!(require_hosted_domain(Some(&id_token), "example.com").is_ok());
+        assert!(require_hosted_domain(Some(&id_token), "Example.COM").is_ok());
+        assert!(require_hosted_domain(Some(&id_token), "*").is_ok());
+    }
+
+    #[test]
+    fn other_or_missing_hosted_domain_is_a_mismatch() {
+        for id_token in [token_for_domain(Some("other.com")), token_for_domain(None)] {
+            assert!(
+                matches!(require_hosted_domain(Some(&id_token), "example.com"), Err(crate::Error::AuthenticationFailed(message)) if message == "Hosted domain mismatch")
+            );
+        }
+        assert!(require_hosted_domain(Some(&token_for_domain(None)), "*").is_err());
+    }
+
+    #[test]
+    fn hosted_domain_cannot_be_checked_without_an_id_token() {
+        assert!(matches!(
+            require_hosted_domain(None, "example.com"),
+            Err(crate::Error::AuthenticationFailed(_))
+        ));
+    }
+}
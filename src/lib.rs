@@ -1,18 +1,36 @@
+use std::sync::Arc;
+
 use tauri::{
     Manager, Runtime,
-    plugin::{Builder, TauriPlugin},
+    plugin::{Builder as PluginBuilder, TauriPlugin},
 };
 
+#[cfg(desktop)]
+pub use desktop::{SignInCancelHandle, SignInFlow, SignInState};
+pub use id_token::{decode_id_token_claims, decode_id_token_header};
 pub use models::*;
+pub use scopes::validate_scopes;
+#[cfg(desktop)]
+pub use success_page::{DefaultSuccessPageRenderer, SuccessPage, SuccessPageRenderer};
+#[cfg(all(desktop, feature = "keyring"))]
+pub use token_store::KeyringTokenStore;
+#[cfg(desktop)]
+pub use token_store::TokenStore;
 
 #[cfg(desktop)]
 mod desktop;
 #[cfg(mobile)]
 mod mobile;
+#[cfg(desktop)]
+mod success_page;
+#[cfg(desktop)]
+mod token_store;
 
 mod commands;
 mod error;
+mod id_token;
 mod models;
+mod scopes;
 
 pub use error::{Error, Result};
 
@@ -32,22 +50,132 @@ impl<R: Runtime, T: Manager<R>> crate::GoogleAuthExt<R> for T {
     }
 }
 
+/// The hook registered with [`Builder::on_auth_event`], kept as app state.
+struct AuthEventHook(Arc<dyn Fn(&AuthEvent) + Send + Sync>);
+
+/// Builder for configuring the google-auth plugin.
+#[derive(Default)]
+pub struct Builder {
+    auth_event_hook: Option<AuthEventHook>,
+    #[cfg(desktop)]
+    success_page_renderer: Option<Box<dyn SuccessPageRenderer>>,
+    #[cfg(desktop)]
+    state_codec: Option<desktop::StateCodec>,
+    #[cfg(desktop)]
+    port_fallback: Option<Box<desktop::PortFallback>>,
+    #[cfg(desktop)]
+    token_store: Option<Box<dyn TokenStore>>,
+}
+
+impl Builder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a custom renderer for the page shown in the browser after a desktop sign-in.
+    #[cfg(desktop)]
+    #[must_use]
+    pub fn success_page_renderer(mut self, renderer: impl SuccessPageRenderer + 'static) -> Self {
+        self.success_page_renderer = Some(Box::new(renderer));
+        self
+    }
+
+    /// Replaces the random `state` of desktop sign-ins, e.g. with signed data for
+    /// stateless correlation. `validator` receives the state returned to the redirect
+    /// URI and must reject anything `provider` did not produce, since it is then the
+    /// only CSRF protection; a rejected state fails the sign-in.
+    #[cfg(desktop)]
+    #[must_use]
+    pub fn state_provider(
+        mut self,
+        provider: impl Fn() -> String + Send + Sync + 'static,
+        validator: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.state_codec = Some(desktop::StateCodec {
+            provider: Box::new(provider),
+            validator: Box::new(validator),
+        });
+        self
+    }
+
+    /// Lets desktop sign-ins whose fixed redirect port is taken fall back to a random
+    /// port instead of failing. `callback` receives the preferred and the chosen port,
+    /// e.g. to register the new redirect URI or warn that Google may reject it.
+    #[cfg(desktop)]
+    #[must_use]
+    pub fn on_port_fallback(mut self, callback: impl Fn(u16, u16) + Send + Sync + 'static) -> Self {
+        self.port_fallback = Some(Box::new(callback));
+        self
+    }
+
+    /// Persists desktop sessions in `store`, so `silent_sign_in` can restore them after
+    /// the app restarts. With the `keyring` feature, this replaces the default
+    /// `KeyringTokenStore`.
+    #[cfg(desktop)]
+    #[must_use]
+    pub fn token_store(mut self, store: impl TokenStore + 'static) -> Self {
+        self.token_store = Some(Box::new(store));
+        self
+    }
+
+    /// Calls `hook` after every sign-in, token refresh and sign-out command with an
+    /// anonymized [`AuthEvent`]. The hook runs on a background thread, so it never
+    /// delays or fails the operation itself.
+    #[must_use]
+    pub fn on_auth_event(mut self, hook: impl Fn(&AuthEvent) + Send + Sync + 'static) -> Self {
+        self.auth_event_hook = Some(AuthEventHook(Arc::new(hook)));
+        self
+    }
+
+    /// Builds the plugin.
+    #[must_use]
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        PluginBuilder::new("google-auth")
+            .invoke_handler(tauri::generate_handler![
+                commands::sign_in,
+                commands::request_additional_scopes,
+                commands::cancel_sign_in,
+                commands::sign_in_device_code,
+                commands::sign_out,
+                commands::build_sign_out_request,
+                commands::refresh_token,
+                commands::silent_sign_in,
+                commands::get_current_user,
+                commands::check_connectivity,
+                commands::plugin_info,
+                commands::validate_scopes
+            ])
+            .setup(move |app, api| {
+                #[cfg(all(desktop, feature = "keyring"))]
+                let token_store = self.token_store.or_else(|| {
+                    Some(Box::new(KeyringTokenStore::new(&app.config().identifier)) as _)
+                });
+                #[cfg(all(desktop, not(feature = "keyring")))]
+                let token_store = self.token_store;
+                #[cfg(mobile)]
+                let google_auth = mobile::init(app, api)?;
+                #[cfg(desktop)]
+                let google_auth = desktop::init(
+                    app,
+                    api,
+                    self.success_page_renderer,
+                    self.state_codec,
+                    self.port_fallback,
+                    token_store,
+                )?;
+                app.manage(google_auth);
+                if let Some(hook) = self.auth_event_hook {
+                    app.manage(hook);
+                }
+                Ok(())
+            })
+            .build()
+    }
+}
+
 /// Initializes the plugin.
 #[must_use]
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("google-auth")
-        .invoke_handler(tauri::generate_handler![
-            commands::sign_in,
-            commands::sign_out,
-            commands::refresh_token
-        ])
-        .setup(|app, api| {
-            #[cfg(mobile)]
-            let google_auth = mobile::init(app, api)?;
-            #[cfg(desktop)]
-            let google_auth = desktop::init(app, api)?;
-            app.manage(google_auth);
-            Ok(())
-        })
-        .build()
+    Builder::new().build()
 }
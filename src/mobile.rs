@@ -1,16 +1,29 @@
-use serde::de::DeserializeOwned;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use tauri::{
     AppHandle, Runtime,
-    plugin::{PluginApi, PluginHandle},
+    plugin::{PluginApi, PluginHandle, mobile::PluginInvokeError},
 };
 
 use crate::models::{
-    RefreshTokenRequest, SignInRequest, SignOutRequest, SignOutResponse, TokenResponse,
+    ConnectivityCheckRequest, DeviceSignInRequest, RefreshTokenRequest, SignInRequest,
+    SignOutRequest, SignOutResponse, TokenResponse, UserProfile,
 };
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_google_auth);
 
+/// Code the Android plugin rejects with when Google Play services are missing,
+/// disabled or outdated; the message holds the reason.
+const PLAY_SERVICES_UNAVAILABLE: &str = "PLAY_SERVICES_UNAVAILABLE";
+// Codes the native plugins reject transient failures with; the HTTP status one is
+// followed by the status, e.g. `HTTP_STATUS_503`
+const NETWORK_ERROR: &str = "NETWORK_ERROR";
+const TIMEOUT: &str = "TIMEOUT";
+const HTTP_STATUS_PREFIX: &str = "HTTP_STATUS_";
+
 // initializes the Kotlin or Swift plugin classes
 #[allow(clippy::needless_pass_by_value)]
 pub fn init<R: Runtime, C: DeserializeOwned>(
@@ -28,21 +41,436 @@ pub fn init<R: Runtime, C: DeserializeOwned>(
 pub struct GoogleAuth<R: Runtime>(PluginHandle<R>);
 
 impl<R: Runtime> GoogleAuth<R> {
+    /// Runs [`sign_in`](Self::sign_in) on a blocking thread, as the native plugin
+    /// call blocks until the user finishes signing in.
+    pub async fn sign_in_async(&self, payload: SignInRequest) -> crate::Result<TokenResponse> {
+        let auth = Self(self.0.clone());
+        tauri::async_runtime::spawn_blocking(move || auth.sign_in(payload))
+            .await
+            .map_err(|e| crate::Error::AuthenticationFailed(format!("Sign-in task failed: {e}")))?
+    }
+
     pub fn sign_in(&self, payload: SignInRequest) -> crate::Result<TokenResponse> {
-        self.0
-            .run_mobile_plugin("signIn", payload)
-            .map_err(Into::into)
+        self.run_sign_in("signIn", payload)
+    }
+
+    /// Asks for `scopes` on top of those already granted, through the native
+    /// `requestAdditionalScopes` command. It receives `payload` with the new scopes
+    /// merged in and `includeGrantedScopes` set, and resolves like `signIn` with a
+    /// token covering all of them.
+    pub async fn request_additional_scopes(
+        &self,
+        scopes: Vec<String>,
+        payload: SignInRequest,
+    ) -> crate::Result<TokenResponse> {
+        let auth = Self(self.0.clone());
+        let payload = payload.with_additional_scopes(scopes);
+        tauri::async_runtime::spawn_blocking(move || {
+            auth.run_sign_in("requestAdditionalScopes", payload)
+        })
+        .await
+        .map_err(|e| crate::Error::AuthenticationFailed(format!("Sign-in task failed: {e}")))?
+    }
+
+    /// Runs the native sign-in `command` and checks its result against `payload`.
+    fn run_sign_in(
+        &self,
+        command: &str,
+        mut payload: SignInRequest,
+    ) -> crate::Result<TokenResponse> {
+        if payload.verify_id_token {
+            return Err(crate::Error::configuration(
+                Some("verify_id_token"),
+                "ID token signature verification is only supported on desktop",
+            ));
+        }
+        if payload.fetch_profile {
+            return Err(crate::Error::configuration(
+                Some("fetch_profile"),
+                "Fetching the user profile is only supported on desktop",
+            ));
+        }
+        let (scopes, warnings) = crate::scopes::normalize_scopes(
+            payload
+                .scopes
+                .take()
+                .unwrap_or_else(SignInRequest::default_scopes),
+        )?;
+        payload.scopes = Some(scopes);
+        request_offline_access(&mut payload);
+        let require_verified_email = payload.require_verified_email;
+        let require_id_token = payload.require_id_token;
+        let hosted_domain = payload.hosted_domain.clone();
+        let required_scopes = payload.required_scopes.take();
+        let requested_scopes = payload.scopes.clone().unwrap_or_default();
+        let mut response = self.run(command, payload).map(finalize)?;
+        if require_id_token && response.id_token.is_none() {
+            return Err(crate::Error::AuthenticationFailed(
+                "No ID token returned; request the `openid` scope to get one".to_string(),
+            ));
+        }
+        response.warnings.extend(warnings);
+        response.collect_warnings(&requested_scopes);
+        if let Some(required) = &required_scopes {
+            response.ensure_scopes(required)?;
+        }
+        if require_verified_email {
+            crate::id_token::require_verified_email(response.id_token.as_deref())?;
+        }
+        if let Some(hosted_domain) = &hosted_domain {
+            crate::id_token::require_hosted_domain(response.id_token.as_deref(), hosted_domain)?;
+        }
+        Ok(response)
+    }
+
+    /// The device authorization grant is for desktops without a usable browser;
+    /// phones sign in natively instead.
+    #[allow(
+        clippy::unused_self,
+        clippy::unused_async,
+        clippy::needless_pass_by_value
+    )]
+    pub async fn sign_in_device_code(
+        &self,
+        _payload: DeviceSignInRequest,
+    ) -> crate::Result<TokenResponse> {
+        Err(crate::Error::configuration(
+            None,
+            "Device sign-in is only supported on desktop",
+        ))
+    }
+
+    /// Dismisses the native account picker of the pending sign-in, if any, making its
+    /// `sign_in` call fail. Mobile allows a single sign-in at a time, so the session ID
+    /// is ignored.
+    pub fn cancel_sign_in(&self, _session_id: &str) -> bool {
+        // A native side that can't cancel (or has nothing to cancel) just reports false
+        self.run::<CancelSignInResponse>("cancelSignIn", ())
+            .is_ok_and(|response| response.cancelled)
     }
 
     pub fn sign_out(&self, payload: SignOutRequest) -> crate::Result<SignOutResponse> {
-        self.0
-            .run_mobile_plugin("signOut", payload)
-            .map_err(Into::into)
+        self.run("signOut", payload)
     }
 
+    /// Refreshes the tokens natively. When the native side reports no new refresh
+    /// token, the one that was used stays valid and is returned in the response.
     pub fn refresh_token(&self, payload: RefreshTokenRequest) -> crate::Result<TokenResponse> {
+        let used = payload.refresh_token.clone();
+        let mut response: TokenResponse = self.run("refreshToken", payload)?;
+        if response.refresh_token.as_deref().is_none_or(str::is_empty) {
+            response.refresh_token = used;
+        }
+        Ok(finalize(response))
+    }
+
+    /// Refreshes like [`Self::refresh_token`], but rides out transient failures.
+    ///
+    /// When the refresh fails with a transient error (see
+    /// [`crate::Error::is_transient`]: a timeout, a network error or a 5xx status
+    /// from Google) and `token` has not expired yet, or expired less than `grace`
+    /// ago, `token` is returned unchanged so the app keeps working through a brief
+    /// outage. After that window, and for any other error (e.g. a revoked refresh
+    /// token), the error is returned.
+    /// A token with an unknown expiry is never kept.
+    pub fn refresh_with_grace(
+        &self,
+        token: &TokenResponse,
+        payload: RefreshTokenRequest,
+        grace: Duration,
+    ) -> crate::Result<TokenResponse> {
+        match self.refresh_token(payload) {
+            Err(e) if e.is_transient() && token.is_within_grace(grace) => Ok(token.clone()),
+            result => result,
+        }
+    }
+
+    /// Returns `token` unchanged while its access token is valid for at least
+    /// [`TokenResponse::DEFAULT_REFRESH_SKEW`], otherwise refreshes it with `payload`.
+    /// Without a refresh token in `payload`, the one of `token` is used.
+    pub fn ensure_valid_token(
+        &self,
+        token: TokenResponse,
+        mut payload: RefreshTokenRequest,
+    ) -> crate::Result<TokenResponse> {
+        if !token.is_expired_with_skew(TokenResponse::DEFAULT_REFRESH_SKEW) {
+            return Ok(token);
+        }
+        let refresh_token = payload
+            .refresh_token
+            .take()
+            .or(token.refresh_token)
+            .ok_or_else(|| {
+                crate::Error::TokenRefreshFailed(
+                    "Access token expired and no refresh token is available".to_string(),
+                )
+            })?;
+        payload.refresh_token = Some(refresh_token);
+
+        self.refresh_token(payload)
+    }
+
+    /// Restores the session of a previously signed-in user without showing any UI.
+    ///
+    /// Invokes the native `silentSignIn` command with `payload`. The native side must
+    /// never present anything: it resolves with the same token object as `signIn`
+    /// when it can get tokens for the requested scopes on its own, and with an empty
+    /// object when it can't, which becomes [`crate::Error::NoUserSignedIn`]. It only
+    /// rejects on actual failures, such as an unreachable Google.
+    ///
+    /// A refresh token used to restore the session is returned like in
+    /// [`Self::refresh_token`].
+    pub fn silent_sign_in(&self, payload: RefreshTokenRequest) -> crate::Result<TokenResponse> {
+        let used = payload.refresh_token.clone();
+        let response: serde_json::Map<String, serde_json::Value> =
+            self.run("silentSignIn", payload)?;
+        if response.is_empty() {
+            return Err(crate::Error::NoUserSignedIn);
+        }
+        let mut response: TokenResponse =
+            serde_json::from_value(serde_json::Value::Object(response)).map_err(|e| {
+                crate::Error::AuthenticationFailed(format!(
+                    "Malformed silent sign-in response: {e}"
+                ))
+            })?;
+        if response.refresh_token.as_deref().is_none_or(str::is_empty) {
+            response.refresh_token = used;
+        }
+        Ok(finalize(response))
+    }
+
+    /// Returns the signed-in user, or `None` when nobody is signed in.
+    ///
+    /// Invokes the native `getCurrentUser` command, which resolves with the
+    /// `idToken` of the current session (`{ "idToken": "..." }`) and with an empty
+    /// object (or an empty `idToken`) when there is none. The profile is read from the
+    /// ID token's claims.
+    pub fn get_current_user(&self) -> crate::Result<Option<UserProfile>> {
+        let response: CurrentUserResponse = self.run("getCurrentUser", ())?;
+        response.into_profile()
+    }
+
+    /// Checks that Google's sign-in servers are reachable by opening a TCP
+    /// connection to the accounts host. The native SDKs use the system's network
+    /// settings, so only the request timeout of `payload` applies.
+    #[allow(clippy::unused_self)]
+    pub fn check_connectivity(&self, payload: &ConnectivityCheckRequest) -> crate::Result<()> {
+        let addr = ("accounts.google.com", 443)
+            .to_socket_addrs()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to resolve Google: {e}")))?
+            .next()
+            .ok_or_else(|| crate::Error::NetworkError("No address found for Google".to_string()))?;
+        TcpStream::connect_timeout(
+            &addr,
+            Duration::from_secs(payload.request_timeout_seconds.unwrap_or(10)),
+        )
+        .map(|_| ())
+        .map_err(|e| crate::Error::NetworkError(format!("Failed to reach Google: {e}")))
+    }
+
+    /// Runs the native `command`, recognizing the error codes the plugins reject with.
+    fn run<T: DeserializeOwned>(&self, command: &str, payload: impl Serialize) -> crate::Result<T> {
         self.0
-            .run_mobile_plugin("refreshToken", payload)
-            .map_err(Into::into)
+            .run_mobile_plugin(command, payload)
+            .map_err(map_native_error)
+    }
+}
+
+/// Maps the cross-platform `offline_access` onto the native SDKs, which grant offline
+/// access as a server auth code for a backend to redeem. Without a `server_client_id`
+/// of its own, the code is issued to `client_id`.
+fn request_offline_access(payload: &mut SignInRequest) {
+    if payload.offline_access == Some(true) && payload.server_client_id.is_none() {
+        payload.server_client_id = Some(payload.client_id.clone());
+    }
+}
+
+/// Maps a rejected native call to a dedicated error when its code is known.
+fn map_native_error(error: PluginInvokeError) -> crate::Error {
+    let PluginInvokeError::InvokeRejected(response) = error else {
+        return error.into();
+    };
+    let message = || response.message.clone().unwrap_or_default();
+    match response.code.as_deref() {
+        Some(PLAY_SERVICES_UNAVAILABLE) => crate::Error::PlayServicesUnavailable(message()),
+        Some(NETWORK_ERROR) => crate::Error::NetworkError(message()),
+        Some(TIMEOUT) => crate::Error::Timeout(message()),
+        Some(code) => match code
+            .strip_prefix(HTTP_STATUS_PREFIX)
+            .and_then(|status| status.parse().ok())
+        {
+            Some(status) => crate::Error::HttpStatus {
+                status,
+                body: message(),
+            },
+            None => PluginInvokeError::InvokeRejected(response).into(),
+        },
+        None => PluginInvokeError::InvokeRejected(response).into(),
+    }
+}
+
+#[derive(Deserialize)]
+struct CancelSignInResponse {
+    cancelled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurrentUserResponse {
+    id_token: Option<String>,
+}
+
+impl CurrentUserResponse {
+    fn into_profile(self) -> crate::Result<Option<UserProfile>> {
+        self.id_token
+            .filter(|id_token| !id_token.is_empty())
+            .map(|id_token| {
+                crate::id_token::decode_id_token_claims(&id_token).map(UserProfile::from)
+            })
+            .transpose()
+    }
+}
+
+/// Fills in the fields the native plugins don't report.
+fn finalize(mut response: TokenResponse) -> TokenResponse {
+    // The native plugins report missing tokens as empty strings
+    response.id_token = response.id_token.filter(|id_token| !id_token.is_empty());
+    response.refresh_token = response
+        .refresh_token
+        .filter(|refresh_token| !refresh_token.is_empty());
+    // An unknown expiry is omitted; guard against a native side reporting it as 0
+    response.expires_at = response.expires_at.filter(|&expires_at| expires_at > 0);
+    response.is_offline = response.refresh_token.is_some();
+    response.fill_expires_at_iso();
+    response.fill_account_id();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id_token::tests::unsigned_jwt;
+
+    fn current_user(response: &serde_json::Value) -> crate::Result<Option<UserProfile>> {
+        serde_json::from_value::<CurrentUserResponse>(response.clone())
+            .expect("invalid native response")
+            .into_profile()
+    }
+
+    #[test]
+    fn signed_out_native_responses_have_no_user() {
+        for response in [serde_json::json!({}), serde_json::json!({ "idToken": "" })] {
+            assert_eq!(
+                current_user(&response).expect("response was rejected"),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn current_user_is_read_from_the_id_token() {
+        let id_token = unsigned_jwt(&serde_json::json!({
+            "sub": "1234567890",
+            "email": "jane@example.com",
+            "email_verified": true,
+            "name": "Jane Doe",
+            "exp": 1_700_003_600,
+            "iat": 1_700_000_000,
+        }));
+
+        let user = current_user(&serde_json::json!({ "idToken": id_token }))
+            .expect("response was rejected")
+            .expect("no user was read");
+
+        assert_eq!(user.sub, "1234567890");
+        assert_eq!(user.email.as_deref(), Some("jane@example.com"));
+        assert_eq!(user.name.as_deref(), Some("Jane Doe"));
+        let json = serde_json::to_value(&user).expect("serialization failed");
+        assert_eq!(json["emailVerified"], true);
+    }
+
+    fn rejected(code: &str, message: &str) -> crate::Error {
+        map_native_error(PluginInvokeError::InvokeRejected(
+            tauri::plugin::mobile::ErrorResponse {
+                code: Some(code.to_string()),
+                message: Some(message.to_string()),
+                data: (),
+            },
+        ))
+    }
+
+    #[test]
+    fn missing_play_services_are_mapped_to_their_variant() {
+        let error = rejected(PLAY_SERVICES_UNAVAILABLE, "SERVICE_VERSION_UPDATE_REQUIRED");
+
+        assert!(
+            matches!(error, crate::Error::PlayServicesUnavailable(reason) if reason == "SERVICE_VERSION_UPDATE_REQUIRED")
+        );
+    }
+
+    #[test]
+    fn transient_native_failures_are_mapped() {
+        assert!(matches!(
+            rejected(NETWORK_ERROR, "Unable to resolve host"),
+            crate::Error::NetworkError(_)
+        ));
+        assert!(matches!(
+            rejected(TIMEOUT, "timeout"),
+            crate::Error::Timeout(_)
+        ));
+        assert!(
+            matches!(rejected("HTTP_STATUS_503", "Service Unavailable"), crate::Error::HttpStatus { status: 503, body } if body == "Service Unavailable")
+        );
+    }
+
+    #[test]
+    fn other_native_errors_stay_plugin_errors() {
+        for code in ["SIGN_IN_FAILED", "HTTP_STATUS_abc"] {
+            assert!(matches!(
+                rejected(code, "failed"),
+                crate::Error::PluginInvoke(_)
+            ));
+        }
+    }
+
+    fn offline_request(overrides: &serde_json::Value) -> SignInRequest {
+        let mut request = serde_json::json!({ "clientId": "web-client-id" });
+        if let (Some(request), Some(overrides)) = (request.as_object_mut(), overrides.as_object()) {
+            request.extend(overrides.clone());
+        }
+        let mut request: SignInRequest =
+            serde_json::from_value(request).expect("invalid test request");
+        request_offline_access(&mut request);
+        request
+    }
+
+    #[test]
+    fn offline_access_requests_a_server_auth_code_for_the_client() {
+        let request = offline_request(&serde_json::json!({ "offlineAccess": true }));
+
+        assert_eq!(request.server_client_id.as_deref(), Some("web-client-id"));
+    }
+
+    #[test]
+    fn offline_access_keeps_the_given_server_client() {
+        let request = offline_request(&serde_json::json!({
+            "offlineAccess": true,
+            "serverClientId": "backend-client-id",
+        }));
+
+        assert_eq!(
+            request.server_client_id.as_deref(),
+            Some("backend-client-id")
+        );
+    }
+
+    #[test]
+    fn online_access_requests_no_server_auth_code() {
+        for overrides in [
+            serde_json::json!({}),
+            serde_json::json!({ "offlineAccess": false }),
+        ] {
+            assert_eq!(offline_request(&overrides).server_client_id, None);
+        }
     }
 }
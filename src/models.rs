@@ -1,3 +1,8 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, Eq)]
@@ -8,61 +13,1211 @@ pub enum FlowType {
     Web,
 }
 
+/// Where a desktop sign-in shows Google's sign-in page.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthMode {
+    /// The user's default browser.
+    #[default]
+    SystemBrowser,
+    /// A window of the app. Google blocks sign-ins from embedded webviews it
+    /// detects, so this can fail with `disallowed_useragent`.
+    Webview,
+}
+
+/// The OAuth client type registered in the Google Cloud Console.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ClientType {
+    /// "Desktop app": any loopback port is accepted.
+    Desktop,
+    /// "Web application": only the exact registered redirect URIs are accepted.
+    Web,
+    /// "TVs and Limited Input devices": only usable with the device flow.
+    LimitedInput,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct SignInRequest {
+    #[serde(alias = "client_id")]
     pub client_id: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "client_secret")]
     pub client_secret: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scopes: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "required_scopes")]
+    pub required_scopes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "hosted_domain")]
     pub hosted_domain: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "login_hint")]
     pub login_hint: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, alias = "include_granted_scopes")]
+    pub include_granted_scopes: bool,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "redirect_uri")]
     pub redirect_uri: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "success_html_response"
+    )]
     pub success_html_response: Option<String>,
+    #[serde(default, alias = "wrap_success_html")]
+    pub wrap_success_html: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "success_redirect_url"
+    )]
+    pub success_redirect_url: Option<String>,
+    #[serde(default, alias = "return_callback_params")]
+    pub return_callback_params: bool,
+    #[serde(default, alias = "private_window")]
+    pub private_window: bool,
+    #[serde(default, alias = "auth_mode")]
+    pub auth_mode: AuthMode,
+    /// Directory the [`AuthMode::Webview`] window keeps its cookies and storage in,
+    /// apart from the app's own windows, so a Google or SSO session survives restarts.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "webview_data_directory"
+    )]
+    pub webview_data_directory: Option<PathBuf>,
+    /// Returns the Google session cookies of the [`AuthMode::Webview`] window in
+    /// [`TokenResponse::webview_cookies`]. They sign in to the user's whole Google
+    /// account, not just this app; see the README before turning this on.
+    #[serde(default, alias = "return_webview_cookies")]
+    pub return_webview_cookies: bool,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "bind_attempts")]
+    pub bind_attempts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "bind_host")]
+    pub bind_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "port_range")]
+    pub port_range: Option<(u16, u16)>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "ip_version")]
+    pub ip_version: Option<IpVersion>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "timeout_seconds")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "response_type")]
+    pub response_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "offline_access")]
+    pub offline_access: Option<bool>,
+    #[serde(default, alias = "require_verified_email")]
+    pub require_verified_email: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "id_token_leeway_seconds"
+    )]
+    pub id_token_leeway_seconds: Option<u64>,
+    #[serde(default, alias = "verify_id_token")]
+    pub verify_id_token: bool,
+    /// Audiences the verified ID token's `aud` may match, e.g. the client IDs of an
+    /// app's web and installed clients. Defaults to `client_id`.
+    #[serde(skip_serializing_if = "Option::is_none", alias = "accepted_audiences")]
+    pub accepted_audiences: Option<Vec<String>>,
+    #[serde(default, alias = "require_id_token")]
+    pub require_id_token: bool,
+    #[serde(default, alias = "fetch_profile")]
+    pub fetch_profile: bool,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "auth_url")]
+    pub auth_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "token_url")]
+    pub token_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "revocation_url")]
+    pub revocation_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "pkce_method")]
+    pub pkce_method: Option<PkceMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "request_id")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "client_type")]
+    pub client_type: Option<ClientType>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "extra_headers")]
+    pub extra_headers: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "proxy_url")]
+    pub proxy_url: Option<String>,
+    #[serde(default, alias = "danger_accept_invalid_certs")]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "connect_timeout_seconds"
+    )]
+    pub connect_timeout_seconds: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "request_timeout_seconds"
+    )]
+    pub request_timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "max_retries")]
+    pub max_retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "retry_base_delay_ms")]
+    pub retry_base_delay_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "server_client_id")]
+    pub server_client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "flow_type")]
     pub flow_type: Option<FlowType>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl SignInRequest {
+    /// Scopes requested when `scopes` is `None`. An explicitly empty list is
+    /// still rejected, since requesting nothing is a mistake.
+    pub const DEFAULT_SCOPES: &[&str] = &["openid", "email", "profile"];
+
+    #[must_use]
+    pub fn default_scopes() -> Vec<String> {
+        Self::DEFAULT_SCOPES
+            .iter()
+            .map(|s| (*s).to_string())
+            .collect()
+    }
+
+    /// Adds `scopes` to those of this request and turns on `include_granted_scopes`,
+    /// for an incremental authorization.
+    #[must_use]
+    pub fn with_additional_scopes(mut self, scopes: Vec<String>) -> Self {
+        let requested = self.scopes.get_or_insert_with(Self::default_scopes);
+        for scope in scopes {
+            if !requested.contains(&scope) {
+                requested.push(scope);
+            }
+        }
+        self.include_granted_scopes = true;
+        self
+    }
+}
+
+/// Placeholder for tokens in [`TokenResponse::redacted`] and `Debug` output.
+pub const REDACTED: &str = "***";
+
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenResponse {
+    #[serde(alias = "id_token")]
     pub id_token: Option<String>,
-    pub access_token: String,
+    /// `None` when a desktop sign-in with `server_client_id` handed its code to the
+    /// backend unexchanged, so the app got no tokens.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "access_token"
+    )]
+    pub access_token: Option<String>,
     pub scopes: Vec<String>,
+    #[serde(alias = "refresh_token")]
     pub refresh_token: Option<String>,
+    #[serde(alias = "expires_at")]
     pub expires_at: Option<i64>,
+    #[serde(alias = "expires_at_iso")]
+    pub expires_at_iso: Option<String>,
+    /// The token's lifetime as Google reported it, which unlike `expires_at` doesn't
+    /// depend on the local clock, e.g. to recompute the expiry against a trusted one.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "expires_in_seconds"
+    )]
+    pub expires_in_seconds: Option<i64>,
+    #[serde(default, alias = "is_offline")]
+    pub is_offline: bool,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "callback_params")]
+    pub callback_params: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<UserProfile>,
+    /// One-time code for a backend to redeem for its own tokens, when signing in
+    /// with `server_client_id`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "server_auth_code"
+    )]
+    pub server_auth_code: Option<String>,
+    /// The `state` the callback carrying `server_auth_code` was validated with, for
+    /// a backend to match against the one the client generated (desktop only).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "server_auth_state"
+    )]
+    pub server_auth_state: Option<String>,
+    /// The PKCE verifier a backend must send when redeeming `server_auth_code`, unless
+    /// the sign-in was made without PKCE (desktop only).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "server_code_verifier"
+    )]
+    pub server_code_verifier: Option<String>,
+    /// The `sub` of the signed-in user's ID token, which identifies the session in
+    /// multi-account apps and the token store.
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "account_id")]
+    pub account_id: Option<String>,
+    /// The Google session cookies of the sign-in window, with
+    /// `return_webview_cookies` (desktop only). Never stored with the session.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "webview_cookies"
+    )]
+    pub webview_cookies: Option<Vec<WebviewCookie>>,
+}
+
+impl TokenResponse {
+    /// A copy with the access, refresh and ID tokens replaced by [`REDACTED`], for
+    /// logging or telemetry. It serializes with the same field names.
+    #[must_use]
+    pub fn redacted(&self) -> RedactedTokenResponse {
+        RedactedTokenResponse(Self {
+            id_token: self.id_token.as_ref().map(|_| REDACTED.to_string()),
+            access_token: self.access_token.as_ref().map(|_| REDACTED.to_string()),
+            refresh_token: self.refresh_token.as_ref().map(|_| REDACTED.to_string()),
+            server_auth_code: self.server_auth_code.as_ref().map(|_| REDACTED.to_string()),
+            server_code_verifier: self
+                .server_code_verifier
+                .as_ref()
+                .map(|_| REDACTED.to_string()),
+            webview_cookies: self.webview_cookies.as_ref().map(|cookies| {
+                cookies
+                    .iter()
+                    .map(|cookie| WebviewCookie {
+                        value: REDACTED.to_string(),
+                        ..cookie.clone()
+                    })
+                    .collect()
+            }),
+            ..self.clone()
+        })
+    }
+
+    /// Decodes the claims of `id_token`, e.g. for the user's `sub` and email, or
+    /// `None` when no ID token was returned. This is a plain decode; the signature
+    /// is not verified.
+    pub fn claims(&self) -> crate::Result<Option<IdTokenClaims>> {
+        self.id_token
+            .as_deref()
+            .filter(|id_token| !id_token.is_empty())
+            .map(crate::id_token::decode_id_token_claims)
+            .transpose()
+    }
+
+    /// Whether `scope` was granted. Google reports `email` and `profile` by their
+    /// full `userinfo` URLs, so both spellings match.
+    #[must_use]
+    pub fn has_scope(&self, scope: &str) -> bool {
+        let alias = match scope {
+            "email" => "https://www.googleapis.com/auth/userinfo.email",
+            "profile" => "https://www.googleapis.com/auth/userinfo.profile",
+            "https://www.googleapis.com/auth/userinfo.email" => "email",
+            "https://www.googleapis.com/auth/userinfo.profile" => "profile",
+            _ => scope,
+        };
+        self.scopes.iter().any(|s| s == scope || s == alias)
+    }
+
+    /// Derives `expires_at_iso` from `expires_at`.
+    pub(crate) fn fill_expires_at_iso(&mut self) {
+        self.expires_at_iso = self.expires_at.and_then(|expires_at| {
+            time::OffsetDateTime::from_unix_timestamp(expires_at)
+                .ok()?
+                .format(&time::format_description::well_known::Rfc3339)
+                .ok()
+        });
+    }
+
+    /// Derives `account_id` from the `sub` claim of `id_token`, keeping the current
+    /// one when there is no ID token to decode.
+    pub(crate) fn fill_account_id(&mut self) {
+        if let Ok(Some(claims)) = self.claims() {
+            self.account_id = Some(claims.sub);
+        }
+    }
+
+    /// Whether the access token is still valid, or expired less than `grace` ago.
+    pub(crate) fn is_within_grace(&self, grace: Duration) -> bool {
+        let grace = i64::try_from(grace.as_secs()).unwrap_or(i64::MAX);
+        self.seconds_until_refresh(Duration::ZERO)
+            .is_some_and(|remaining| remaining.saturating_add(grace) > 0)
+    }
+
+    /// Records the non-fatal issues of a sign-in that requested `requested` scopes.
+    pub(crate) fn collect_warnings(&mut self, requested: &[String]) {
+        if self.refresh_token.is_none() {
+            self.warnings.push("No refresh token returned".to_string());
+        }
+        let missing: Vec<&str> = requested
+            .iter()
+            .filter(|scope| !self.has_scope(scope))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            self.warnings.push(format!(
+                "Granted scopes differ from requested; not granted: {}",
+                missing.join(", ")
+            ));
+        }
+        match self.id_token.as_deref() {
+            None => self.warnings.push("No ID token returned".to_string()),
+            Some(id_token) => {
+                if let Err(e) = crate::id_token::decode_id_token_claims(id_token) {
+                    self.warnings.push(format!("ID token decode skipped: {e}"));
+                }
+            }
+        }
+    }
+
+    /// Builds an HTTP client that sends `Authorization: Bearer <access token>` with
+    /// every request. The token is captured at build time, so build a new client
+    /// after refreshing. Fails without an access token.
+    #[cfg(feature = "reqwest")]
+    pub fn authorized_client(&self) -> crate::Result<reqwest::Client> {
+        use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+
+        let access_token = self.access_token.as_deref().ok_or_else(|| {
+            crate::Error::AuthenticationFailed(
+                "No access token; a server auth code sign-in returns none".to_string(),
+            )
+        })?;
+        let mut authorization =
+            HeaderValue::from_str(&format!("Bearer {access_token}")).map_err(|e| {
+                crate::Error::AuthenticationFailed(format!("Invalid access token: {e}"))
+            })?;
+        authorization.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, authorization);
+
+        reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| crate::Error::NetworkError(format!("Failed to build HTTP client: {e}")))
+    }
+
+    /// Fails with [`crate::Error::AuthenticationFailed`] naming every scope in
+    /// `required` that was not granted.
+    pub(crate) fn ensure_scopes(&self, required: &[String]) -> crate::Result<()> {
+        let missing: Vec<&str> = required
+            .iter()
+            .filter(|scope| !self.has_scope(scope))
+            .map(String::as_str)
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::AuthenticationFailed(format!(
+                "Required scopes not granted: {}",
+                missing.join(", ")
+            )))
+        }
+    }
+
+    /// Seconds until the access token should be refreshed, refreshing `skew` early to
+    /// absorb clock drift and latency. Negative values mean "refresh now"; `None` when
+    /// the expiry is unknown.
+    #[must_use]
+    pub fn seconds_until_refresh(&self, skew: Duration) -> Option<i64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        self.seconds_until_refresh_at(skew, now)
+    }
+
+    /// Same as [`Self::seconds_until_refresh`], measured from `now` (Unix seconds).
+    #[must_use]
+    pub fn seconds_until_refresh_at(&self, skew: Duration, now: i64) -> Option<i64> {
+        let skew = i64::try_from(skew.as_secs()).unwrap_or(i64::MAX);
+        self.expires_at
+            .map(|expires_at| expires_at.saturating_sub(skew).saturating_sub(now))
+    }
+
+    /// How long before expiry `GoogleAuth::ensure_valid_token` refreshes a token.
+    pub const DEFAULT_REFRESH_SKEW: Duration = Duration::from_secs(60);
+
+    /// Whether the access token has expired. A token with an unknown expiry never
+    /// does, while a response without an access token always counts as expired.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_with_skew(Duration::ZERO)
+    }
+
+    /// Whether the access token has expired or will within `skew`.
+    #[must_use]
+    pub fn is_expired_with_skew(&self, skew: Duration) -> bool {
+        self.access_token.is_none()
+            || self
+                .seconds_until_refresh(skew)
+                .is_some_and(|remaining| remaining <= 0)
+    }
+
+    /// Time left until the access token expires, zero once it has; `None` when the
+    /// expiry is unknown.
+    #[must_use]
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.seconds_until_refresh(Duration::ZERO)
+            .map(|remaining| Duration::from_secs(remaining.try_into().unwrap_or_default()))
+    }
+}
+
+/// Keeps tokens out of logs; use [`TokenResponse::redacted`] to log other fields.
+impl fmt::Debug for TokenResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenResponse")
+            .field("id_token", &self.id_token.as_ref().map(|_| REDACTED))
+            .field(
+                "access_token",
+                &self.access_token.as_ref().map(|_| REDACTED),
+            )
+            .field("scopes", &self.scopes)
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| REDACTED),
+            )
+            .field("expires_at", &self.expires_at)
+            .field("expires_at_iso", &self.expires_at_iso)
+            .field("expires_in_seconds", &self.expires_in_seconds)
+            .field("is_offline", &self.is_offline)
+            .field("callback_params", &self.callback_params)
+            .field("warnings", &self.warnings)
+            .field("profile", &self.profile)
+            .field(
+                "server_auth_code",
+                &self.server_auth_code.as_ref().map(|_| REDACTED),
+            )
+            .field("server_auth_state", &self.server_auth_state)
+            .field(
+                "server_code_verifier",
+                &self.server_code_verifier.as_ref().map(|_| REDACTED),
+            )
+            .field("account_id", &self.account_id)
+            .field("webview_cookies", &self.webview_cookies)
+            .finish()
+    }
+}
+
+/// A cookie of the [`AuthMode::Webview`] sign-in window. Its value is a credential
+/// for the user's Google account, so `Debug` output redacts it.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebviewCookie {
+    pub name: String,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default, alias = "http_only")]
+    pub http_only: bool,
+    /// When the cookie expires (Unix seconds), or `None` for a session cookie.
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "expires_at")]
+    pub expires_at: Option<i64>,
+}
+
+impl fmt::Debug for WebviewCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebviewCookie")
+            .field("name", &self.name)
+            .field("value", &REDACTED)
+            .field("domain", &self.domain)
+            .field("path", &self.path)
+            .field("secure", &self.secure)
+            .field("http_only", &self.http_only)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// A [`TokenResponse`] without its secrets, see [`TokenResponse::redacted`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct RedactedTokenResponse(TokenResponse);
+
+impl std::ops::Deref for RedactedTokenResponse {
+    type Target = TokenResponse;
+
+    fn deref(&self) -> &TokenResponse {
+        &self.0
+    }
+}
+
+/// Payload of the event emitted when a desktop sign-in starts waiting for its callback.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignInStarted {
+    pub session_id: String,
+    pub request_id: String,
+    pub authorize_url: String,
+}
+
+/// A step of a desktop sign-in, as reported by its stage event.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SignInStage {
+    OpeningBrowser,
+    AwaitingCallback,
+    ExchangingCode,
+    Complete,
+    Failed,
+}
+
+/// Payload of the event emitted whenever a desktop sign-in moves to another stage.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignInProgress {
+    pub session_id: String,
+    pub request_id: String,
+    pub stage: SignInStage,
+    /// Why the sign-in failed, for [`SignInStage::Failed`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Payload of the event emitted when a device sign-in has a code for the user to
+/// enter at `verification_url` on another device.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceCode {
+    pub request_id: String,
+    pub user_code: String,
+    pub verification_url: String,
+    /// Seconds until the code expires.
+    pub expires_in: u64,
+}
+
+/// Payload of the warning event emitted when a desktop `redirect_uri` was sent to
+/// Google in a different form than given, e.g. without its trailing slash.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectUriNormalized {
+    pub original: String,
+    pub normalized: String,
+}
+
+/// The signed-in user's profile, as returned by Google's userinfo endpoint.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct UserProfile {
+    pub sub: String,
+    pub email: Option<String>,
+    // The aliases read back what this serializes, e.g. from a token store
+    #[serde(alias = "emailVerified")]
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    #[serde(alias = "givenName")]
+    pub given_name: Option<String>,
+    #[serde(alias = "familyName")]
+    pub family_name: Option<String>,
+    pub picture: Option<String>,
+    pub locale: Option<String>,
+}
+
+impl From<IdTokenClaims> for UserProfile {
+    /// Takes the profile claims of an ID token; those it doesn't carry are left empty.
+    fn from(claims: IdTokenClaims) -> Self {
+        Self {
+            sub: claims.sub,
+            email: claims.email,
+            email_verified: claims.email_verified,
+            name: claims.name,
+            given_name: None,
+            family_name: None,
+            picture: claims.picture,
+            locale: None,
+        }
+    }
+}
+
+/// An access token's state, as reported by Google's tokeninfo endpoint.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenIntrospection {
+    /// Whether the token is valid and was issued to the expected client.
+    pub active: bool,
+    /// The granted scopes, space-separated.
+    pub scope: Option<String>,
+    /// Expiry as seconds since the Unix epoch.
+    pub exp: Option<i64>,
+    pub aud: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Claims carried in the payload of a Google ID token.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct IdTokenClaims {
+    pub iss: Option<String>,
+    pub aud: Option<String>,
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    pub hd: Option<String>,
+    pub exp: i64,
+    pub iat: i64,
+    pub nbf: Option<i64>,
+    pub nonce: Option<String>,
+}
+
+impl IdTokenClaims {
+    /// Clock skew tolerated by ID token verifications that don't set
+    /// `id_token_leeway_seconds`.
+    pub const DEFAULT_LEEWAY: Duration = Duration::from_secs(60);
+
+    /// Fails with [`crate::Error::AuthenticationFailed`] if the token has expired
+    /// (`exp`) or is not valid yet (`iat`, `nbf`), tolerating `leeway` of clock skew.
+    pub fn validate_times(&self, leeway: Duration) -> crate::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        self.validate_times_at(leeway, now)
+    }
+
+    /// Same as [`Self::validate_times`], checked at `now` (Unix seconds).
+    pub fn validate_times_at(&self, leeway: Duration, now: i64) -> crate::Result<()> {
+        let leeway = i64::try_from(leeway.as_secs()).unwrap_or(i64::MAX);
+        let failed = if self.exp.saturating_add(leeway) <= now {
+            Some("exp")
+        } else if self.iat.saturating_sub(leeway) > now {
+            Some("iat")
+        } else if self.nbf.is_some_and(|nbf| nbf.saturating_sub(leeway) > now) {
+            Some("nbf")
+        } else {
+            None
+        };
+        failed.map_or(Ok(()), |claim| {
+            Err(crate::Error::AuthenticationFailed(format!(
+                "ID token failed time validation: `{claim}` is out of range"
+            )))
+        })
+    }
+
+    /// Whether Google vouches for `email`. Never match accounts on an unverified
+    /// email; a missing claim counts as unverified.
+    #[must_use]
+    pub fn is_email_verified(&self) -> bool {
+        self.email.is_some() && self.email_verified == Some(true)
+    }
+}
+
+/// The JOSE header of a Google ID token.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JoseHeader {
+    pub alg: String,
+    pub kid: Option<String>,
+    pub typ: Option<String>,
 }
 
+/// A sign-in with the device authorization grant, for machines that can't open
+/// a browser. Needs a "TVs and Limited Input devices" client, whose secret Google
+/// requires even though it can't be kept secret.
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SignOutRequest {
+pub struct DeviceSignInRequest {
+    #[serde(alias = "client_id")]
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "client_secret")]
+    pub client_secret: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "timeout_seconds")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "request_id")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "device_code_url")]
+    pub device_code_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "token_url")]
+    pub token_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "proxy_url")]
+    pub proxy_url: Option<String>,
+    #[serde(default, alias = "danger_accept_invalid_certs")]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "connect_timeout_seconds"
+    )]
+    pub connect_timeout_seconds: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "request_timeout_seconds"
+    )]
+    pub request_timeout_seconds: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignOutRequest {
+    /// The session to sign out of, see [`TokenResponse::account_id`]. On desktop, the
+    /// tokens the token store holds for it are revoked when none are given.
+    #[serde(skip_serializing_if = "Option::is_none", alias = "account_id")]
+    pub account_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "access_token")]
     pub access_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "refresh_token")]
+    pub refresh_token: Option<String>,
+    #[serde(default, alias = "revoke_grant")]
+    pub revoke_grant: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "revocation_url")]
+    pub revocation_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "proxy_url")]
+    pub proxy_url: Option<String>,
+    #[serde(default, alias = "danger_accept_invalid_certs")]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "connect_timeout_seconds"
+    )]
+    pub connect_timeout_seconds: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "request_timeout_seconds"
+    )]
+    pub request_timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "flow_type")]
     pub flow_type: Option<FlowType>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl SignOutRequest {
+    /// Builds the sign-out request for a stored session.
+    ///
+    /// Revoking a refresh token ends the whole grant, so the app no longer shows up
+    /// as connected and the next sign-in asks for consent again. That is the default
+    /// whenever the session has a refresh token; otherwise the access token is revoked.
+    #[must_use]
+    pub fn for_session(session: &TokenResponse) -> Self {
+        Self {
+            account_id: session.account_id.clone(),
+            access_token: session.access_token.clone(),
+            refresh_token: session.refresh_token.clone(),
+            revoke_grant: session.refresh_token.is_some(),
+            strict: None,
+            revocation_url: None,
+            proxy_url: None,
+            danger_accept_invalid_certs: false,
+            connect_timeout_seconds: None,
+            request_timeout_seconds: None,
+            flow_type: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SignOutResponse {
     pub success: bool,
+    #[serde(default, alias = "access_token_revoked")]
+    pub access_token_revoked: bool,
+    #[serde(default, alias = "refresh_token_revoked")]
+    pub refresh_token_revoked: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RefreshTokenRequest {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "refresh_token")]
     pub refresh_token: Option<String>,
+    #[serde(alias = "client_id")]
     pub client_id: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "client_secret")]
     pub client_secret: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scopes: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "timeout_seconds")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "request_id")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "extra_headers")]
+    pub extra_headers: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "token_url")]
+    pub token_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "proxy_url")]
+    pub proxy_url: Option<String>,
+    #[serde(default, alias = "danger_accept_invalid_certs")]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "connect_timeout_seconds"
+    )]
+    pub connect_timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "max_retries")]
+    pub max_retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "retry_base_delay_ms")]
+    pub retry_base_delay_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "account_id")]
+    pub account_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "flow_type")]
     pub flow_type: Option<FlowType>,
 }
+
+/// How a desktop sign-in derives its PKCE code challenge.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PkceMethod {
+    /// The SHA-256 hash of the verifier, as Google recommends.
+    #[default]
+    #[serde(alias = "S256")]
+    S256,
+    /// The verifier itself, for test harnesses and legacy setups.
+    Plain,
+    /// No PKCE at all. Insecure: an intercepted code can then be exchanged by
+    /// anyone holding the client ID, so only use this to debug.
+    None,
+}
+
+/// Address family of the desktop loopback server.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+/// Why [`crate::validate_scopes`] flagged a scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScopeWarningKind {
+    /// Not in the bundled list; possibly a typo.
+    Unknown,
+    /// Requires Google's restricted-scope verification before publishing.
+    Restricted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopeWarning {
+    pub scope: String,
+    pub kind: ScopeWarningKind,
+}
+
+/// The operation an [`AuthEvent`] reports on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthOperation {
+    SignIn,
+    RefreshToken,
+    SignOut,
+}
+
+/// Anonymized outcome of a sign-in, refresh or sign-out, passed to the
+/// `Builder::on_auth_event` hook. Carries no tokens or account details.
+#[derive(Debug, Clone)]
+pub struct AuthEvent {
+    pub operation: AuthOperation,
+    pub success: bool,
+    pub duration: Duration,
+    /// [`crate::Error::code`] of the failure.
+    pub error_code: Option<&'static str>,
+}
+
+/// Network settings for a connectivity check, the same as those of the sign-in,
+/// refresh and sign-out requests it is meant to predict.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityCheckRequest {
+    #[serde(skip_serializing_if = "Option::is_none", alias = "proxy_url")]
+    pub proxy_url: Option<String>,
+    #[serde(default, alias = "danger_accept_invalid_certs")]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "connect_timeout_seconds"
+    )]
+    pub connect_timeout_seconds: Option<u64>,
+    /// Defaults to 10 seconds, shorter than for other requests, to keep a setup
+    /// screen responsive.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "request_timeout_seconds"
+    )]
+    pub request_timeout_seconds: Option<u64>,
+}
+
+/// Proxy, certificate and timeout settings of the desktop helpers that take no
+/// request payload, such as `GoogleAuth::validate_refresh_token`; the defaults are
+/// those of the other requests.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkSettings {
+    #[serde(skip_serializing_if = "Option::is_none", alias = "proxy_url")]
+    pub proxy_url: Option<String>,
+    #[serde(default, alias = "danger_accept_invalid_certs")]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "connect_timeout_seconds"
+    )]
+    pub connect_timeout_seconds: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        alias = "request_timeout_seconds"
+    )]
+    pub request_timeout_seconds: Option<u64>,
+}
+
+/// Build details of the plugin, for diagnostics.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    pub version: String,
+    pub platform: String,
+    pub features: Vec<String>,
+}
+
+impl PluginInfo {
+    #[must_use]
+    pub fn current() -> Self {
+        let mut features = Vec::new();
+        if cfg!(feature = "reqwest") {
+            features.push("reqwest".to_string());
+        }
+        if cfg!(feature = "keyring") {
+            features.push("keyring".to_string());
+        }
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: std::env::consts::OS.to_string(),
+            features,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_in_request_accepts_both_casings() {
+        let camel: SignInRequest = serde_json::from_value(serde_json::json!({
+            "clientId": "client",
+            "clientSecret": "secret",
+            "redirectUri": "http://127.0.0.1:8080",
+            "timeoutSeconds": 30,
+            "requireVerifiedEmail": true,
+        }))
+        .expect("camelCase was rejected");
+        let snake: SignInRequest = serde_json::from_value(serde_json::json!({
+            "client_id": "client",
+            "client_secret": "secret",
+            "redirect_uri": "http://127.0.0.1:8080",
+            "timeout_seconds": 30,
+            "require_verified_email": true,
+        }))
+        .expect("snake_case was rejected");
+
+        for request in [camel, snake] {
+            assert_eq!(request.client_id, "client");
+            assert_eq!(request.client_secret.as_deref(), Some("secret"));
+            assert_eq!(
+                request.redirect_uri.as_deref(),
+                Some("http://127.0.0.1:8080")
+            );
+            assert_eq!(request.timeout_seconds, Some(30));
+            assert!(request.require_verified_email);
+        }
+    }
+
+    #[test]
+    fn refresh_and_sign_out_requests_accept_both_casings() {
+        for (client_id, refresh_token) in
+            [("clientId", "refreshToken"), ("client_id", "refresh_token")]
+        {
+            let refresh: RefreshTokenRequest = serde_json::from_value(serde_json::json!({
+                client_id: "client",
+                refresh_token: "rt",
+            }))
+            .expect("refresh request was rejected");
+            assert_eq!(refresh.client_id, "client");
+            assert_eq!(refresh.refresh_token.as_deref(), Some("rt"));
+
+            let sign_out: SignOutRequest = serde_json::from_value(serde_json::json!({
+                refresh_token: "rt",
+            }))
+            .expect("sign-out request was rejected");
+            assert_eq!(sign_out.refresh_token.as_deref(), Some("rt"));
+        }
+    }
+
+    #[test]
+    fn requests_still_serialize_as_camel_case() {
+        let request: SignInRequest =
+            serde_json::from_value(serde_json::json!({ "client_id": "client" }))
+                .expect("snake_case was rejected");
+
+        let json = serde_json::to_value(&request).expect("serialization failed");
+
+        assert_eq!(json["clientId"], "client");
+        assert!(json.get("client_id").is_none());
+    }
+
+    fn now() -> i64 {
+        i64::try_from(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("clock before 1970")
+                .as_secs(),
+        )
+        .expect("clock out of range")
+    }
+
+    fn token_expiring_at(expires_at: Option<i64>) -> TokenResponse {
+        serde_json::from_value(serde_json::json!({
+            "accessToken": "at",
+            "scopes": [],
+            "expiresAt": expires_at,
+        }))
+        .expect("invalid test token")
+    }
+
+    #[test]
+    fn token_past_its_expiry_is_expired() {
+        let token = token_expiring_at(Some(now() - 10));
+
+        assert!(token.is_expired());
+        assert_eq!(token.expires_in(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn token_before_its_expiry_is_valid() {
+        let token = token_expiring_at(Some(now() + 3600));
+
+        assert!(!token.is_expired());
+        assert!(token.expires_in().is_some_and(
+            |left| left > Duration::from_secs(3500) && left <= Duration::from_secs(3600)
+        ));
+        assert!(token.is_expired_with_skew(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn response_without_an_access_token_is_expired() {
+        let mut token = token_expiring_at(None);
+        token.access_token = None;
+
+        assert!(token.is_expired());
+        assert!(
+            serde_json::to_value(&token)
+                .expect("serialization failed")
+                .get("accessToken")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn token_without_expiry_never_expires() {
+        let token = token_expiring_at(None);
+
+        assert!(!token.is_expired());
+        assert!(!token.is_expired_with_skew(Duration::from_secs(u64::from(u32::MAX))));
+        assert_eq!(token.expires_in(), None);
+    }
+
+    #[test]
+    fn time_until_refresh_subtracts_the_skew() {
+        let token = token_expiring_at(Some(1_000));
+
+        assert_eq!(
+            token.seconds_until_refresh_at(Duration::from_secs(60), 900),
+            Some(40)
+        );
+        assert_eq!(
+            token.seconds_until_refresh_at(Duration::from_secs(60), 1_000),
+            Some(-60)
+        );
+    }
+
+    #[test]
+    fn user_profile_serializes_as_camel_case_and_reads_back() {
+        let profile: UserProfile = serde_json::from_value(serde_json::json!({
+            "sub": "1234567890",
+            "email_verified": true,
+            "given_name": "Jane",
+            "family_name": "Doe",
+        }))
+        .expect("userinfo casing was rejected");
+
+        let json = serde_json::to_value(&profile).expect("serialization failed");
+
+        assert_eq!(json["emailVerified"], true);
+        assert_eq!(json["givenName"], "Jane");
+        assert_eq!(json["familyName"], "Doe");
+        let read_back: UserProfile = serde_json::from_value(json).expect("camelCase was rejected");
+        assert_eq!(read_back, profile);
+    }
+
+    #[test]
+    fn server_client_id_accepts_both_casings() {
+        for key in ["serverClientId", "server_client_id"] {
+            let request: SignInRequest = serde_json::from_value(serde_json::json!({
+                "clientId": "client",
+                key: "backend.apps.googleusercontent.com",
+            }))
+            .expect("request was rejected");
+
+            assert_eq!(
+                request.server_client_id.as_deref(),
+                Some("backend.apps.googleusercontent.com")
+            );
+        }
+    }
+
+    #[test]
+    fn server_auth_code_is_serialized_only_when_present() {
+        let mut token = token_expiring_at(None);
+        let json = serde_json::to_value(&token).expect("serialization failed");
+        assert!(json.get("serverAuthCode").is_none());
+
+        token.server_auth_code = Some("4/0AX4XfW".to_string());
+        let json = serde_json::to_value(&token).expect("serialization failed");
+        assert_eq!(json["serverAuthCode"], "4/0AX4XfW");
+
+        let read_back: TokenResponse = serde_json::from_value(json).expect("read back failed");
+        assert_eq!(read_back.server_auth_code.as_deref(), Some("4/0AX4XfW"));
+    }
+
+    #[test]
+    fn server_auth_code_is_redacted() {
+        let mut token = token_expiring_at(None);
+        token.server_auth_code = Some("4/0AX4XfW".to_string());
+
+        let json = serde_json::to_value(token.redacted()).expect("serialization failed");
+
+        assert_eq!(json["serverAuthCode"], REDACTED);
+        assert!(!format!("{token:?}").contains("4/0AX4XfW"));
+    }
+
+    #[test]
+    fn server_code_verifier_is_redacted_but_the_state_is_kept() {
+        let mut token = token_expiring_at(None);
+        token.server_auth_state = Some("state-value".to_string());
+        token.server_code_verifier = Some("verifier-value".to_string());
+
+        let json = serde_json::to_value(token.redacted()).expect("serialization failed");
+
+        assert_eq!(json["serverAuthState"], "state-value");
+        assert_eq!(json["serverCodeVerifier"], REDACTED);
+        assert!(!format!("{token:?}").contains("verifier-value"));
+    }
+
+    #[test]
+    fn webview_cookie_values_are_redacted() {
+        let mut token = token_expiring_at(None);
+        token.webview_cookies = Some(vec![WebviewCookie {
+            name: "SID".to_string(),
+            value: "session-secret".to_string(),
+            domain: Some(".google.com".to_string()),
+            path: Some("/".to_string()),
+            secure: true,
+            http_only: true,
+            expires_at: None,
+        }]);
+
+        let json = serde_json::to_value(token.redacted()).expect("serialization failed");
+
+        assert_eq!(json["webviewCookies"][0]["name"], "SID");
+        assert_eq!(json["webviewCookies"][0]["value"], REDACTED);
+        assert_eq!(json["webviewCookies"][0]["httpOnly"], true);
+        assert!(!format!("{token:?}").contains("session-secret"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Bundled metadata of Google OAuth scopes, for catching typos during setup.
+
+use crate::models::{ScopeWarning, ScopeWarningKind};
+
+const SCOPE_PREFIX: &str = "https://www.googleapis.com/auth/";
+
+/// Scopes usable without Google's restricted-scope verification, by their suffix
+/// after [`SCOPE_PREFIX`] or their full value when they have no such prefix.
+const KNOWN_SCOPES: &[&str] = &[
+    "openid",
+    "email",
+    "profile",
+    "userinfo.email",
+    "userinfo.profile",
+    "calendar",
+    "calendar.readonly",
+    "calendar.events",
+    "calendar.events.readonly",
+    "contacts",
+    "contacts.readonly",
+    "contacts.other.readonly",
+    "directory.readonly",
+    "user.birthday.read",
+    "user.phonenumbers.read",
+    "user.addresses.read",
+    "drive.file",
+    "drive.appdata",
+    "drive.install",
+    "documents",
+    "documents.readonly",
+    "spreadsheets",
+    "spreadsheets.readonly",
+    "presentations",
+    "presentations.readonly",
+    "forms.body",
+    "forms.body.readonly",
+    "forms.responses.readonly",
+    "tasks",
+    "tasks.readonly",
+    "youtube",
+    "youtube.readonly",
+    "youtube.upload",
+    "youtube.force-ssl",
+    "fitness.activity.read",
+    "fitness.activity.write",
+    "fitness.body.read",
+    "fitness.location.read",
+    "photospicker.mediaitems.readonly",
+    "cloud-platform",
+    "cloud-platform.read-only",
+    "devstorage.read_only",
+    "devstorage.read_write",
+];
+
+/// Scopes that require Google's restricted-scope verification and a security
+/// assessment before an app can be published.
+const RESTRICTED_SCOPES: &[&str] = &[
+    "https://mail.google.com/",
+    "gmail.readonly",
+    "gmail.modify",
+    "gmail.compose",
+    "gmail.insert",
+    "gmail.metadata",
+    "gmail.settings.basic",
+    "gmail.settings.sharing",
+    "drive",
+    "drive.readonly",
+    "drive.metadata",
+    "drive.metadata.readonly",
+    "drive.activity",
+    "drive.activity.readonly",
+    "drive.scripts",
+];
+
+/// Checks `scopes` against the bundled scope lists. Unlisted scopes are only
+/// warned about, as they may be new or API-specific.
+pub fn validate_scopes(scopes: &[String]) -> crate::Result<Vec<ScopeWarning>> {
+    if scopes.is_empty() {
+        return Err(crate::Error::configuration(
+            Some("scopes"),
+            "Empty scopes array. At least one scope is required for authentication",
+        ));
+    }
+
+    Ok(scopes
+        .iter()
+        .filter_map(|scope| {
+            let name = scope.strip_prefix(SCOPE_PREFIX).unwrap_or(scope);
+            let kind = if RESTRICTED_SCOPES.contains(&name) {
+                ScopeWarningKind::Restricted
+            } else if KNOWN_SCOPES.contains(&name) {
+                return None;
+            } else {
+                ScopeWarningKind::Unknown
+            };
+            Some(ScopeWarning {
+                scope: scope.clone(),
+                kind,
+            })
+        })
+        .collect())
+}
+
+/// Trims and dedupes the requested `scopes`, returning them with a warning for
+/// each entry that had to be altered, as a stray space or duplicate would
+/// otherwise silently change the grant. Fails for an entry that is empty after
+/// trimming or has characters RFC 6749 doesn't allow in a scope.
+pub fn normalize_scopes(scopes: Vec<String>) -> crate::Result<(Vec<String>, Vec<String>)> {
+    let mut normalized: Vec<String> = Vec::with_capacity(scopes.len());
+    let mut warnings = Vec::new();
+    for scope in scopes {
+        let trimmed = scope.trim();
+        if trimmed.is_empty() {
+            return Err(crate::Error::configuration(
+                Some("scopes"),
+                "Scopes must not be empty or whitespace only",
+            ));
+        }
+        if let Some(invalid) = trimmed.chars().find(|&c| !is_scope_char(c)) {
+            let hint = if invalid.is_whitespace() {
+                "; pass each scope as its own entry"
+            } else {
+                ""
+            };
+            return Err(crate::Error::configuration(
+                Some("scopes"),
+                format!("Scope `{trimmed}` contains the invalid character {invalid:?}{hint}"),
+            ));
+        }
+        if trimmed.len() != scope.len() {
+            warnings.push(format!("Trimmed whitespace around scope `{trimmed}`"));
+        }
+        if normalized.iter().any(|seen| seen == trimmed) {
+            warnings.push(format!("Ignored duplicate scope `{trimmed}`"));
+            continue;
+        }
+        normalized.push(trimmed.to_string());
+    }
+    Ok((normalized, warnings))
+}
+
+/// The `scope-token` characters of RFC 6749, section 3.3: printable ASCII except
+/// the space, `"` and `\`.
+const fn is_scope_char(c: char) -> bool {
+    matches!(c, '\x21' | '\x23'..='\x5B' | '\x5D'..='\x7E')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(scopes: &[&str]) -> Vec<String> {
+        scopes.iter().map(ToString::to_string).collect()
+    }
+
+    fn rejection(scopes: &[&str]) -> String {
+        match normalize_scopes(self::scopes(scopes)) {
+            Err(crate::Error::ConfigurationError { field, message }) => {
+                assert_eq!(field.as_deref(), Some("scopes"));
+                message
+            }
+            other => panic!("scopes were not rejected: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn clean_scopes_are_kept_in_order() {
+        let (normalized, warnings) =
+            normalize_scopes(scopes(&["openid", "email", "profile"])).expect("scopes rejected");
+
+        assert_eq!(normalized, ["openid", "email", "profile"]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn whitespace_around_a_scope_is_trimmed_with_a_warning() {
+        let (normalized, warnings) =
+            normalize_scopes(scopes(&["openid ", "\temail\n"])).expect("scopes rejected");
+
+        assert_eq!(normalized, ["openid", "email"]);
+        assert_eq!(
+            warnings,
+            [
+                "Trimmed whitespace around scope `openid`",
+                "Trimmed whitespace around scope `email`",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_scopes_are_dropped_with_a_warning() {
+        let (normalized, warnings) =
+            normalize_scopes(scopes(&["email", "openid", "email"])).expect("scopes rejected");
+
+        assert_eq!(normalized, ["email", "openid"]);
+        assert_eq!(warnings, ["Ignored duplicate scope `email`"]);
+    }
+
+    #[test]
+    fn scopes_are_deduped_after_trimming() {
+        let (normalized, warnings) =
+            normalize_scopes(scopes(&["email", " email"])).expect("scopes rejected");
+
+        assert_eq!(normalized, ["email"]);
+        assert_eq!(
+            warnings,
+            [
+                "Trimmed whitespace around scope `email`",
+                "Ignored duplicate scope `email`",
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_scopes_are_rejected() {
+        assert!(rejection(&["openid", "  "]).contains("must not be empty"));
+        assert!(rejection(&[""]).contains("must not be empty"));
+    }
+
+    #[test]
+    fn space_separated_scopes_are_rejected_with_a_hint() {
+        let message = rejection(&["openid email"]);
+
+        assert!(message.contains("`openid email`"));
+        assert!(message.contains("pass each scope as its own entry"));
+    }
+
+    #[test]
+    fn scopes_with_characters_outside_rfc_6749_are_rejected() {
+        let message = rejection(&["email\"profile"]);
+
+        assert!(message.contains("invalid character '\"'"));
+        assert!(!message.contains("own entry"));
+        assert!(rejection(&["caf\u{e9}"]).contains("invalid character"));
+    }
+}
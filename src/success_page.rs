@@ -0,0 +1,73 @@
+use crate::models::IdTokenClaims;
+
+const SUCCESS_HTML_RESPONSE: &str = "Go back to your app :)";
+
+/// HTTP response written to the browser once the loopback callback is handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuccessPage {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Renders the page shown in the browser after a successful desktop sign-in.
+///
+/// Install a custom implementation with [`crate::Builder::success_page_renderer`]
+/// to take full control over the response. It takes precedence over the
+/// `successHtmlResponse` and `wrapSuccessHtml` request options.
+pub trait SuccessPageRenderer: Send + Sync {
+    /// Builds the response. `claims` holds the decoded (unverified) ID token claims
+    /// when Google returned an ID token.
+    fn render(&self, claims: Option<&IdTokenClaims>) -> SuccessPage;
+}
+
+/// The built-in renderer, driven by the `successHtmlResponse` and
+/// `wrapSuccessHtml` request options.
+#[derive(Debug, Clone)]
+pub struct DefaultSuccessPageRenderer {
+    pub message: String,
+    pub wrap_html: bool,
+}
+
+impl Default for DefaultSuccessPageRenderer {
+    fn default() -> Self {
+        Self {
+            message: SUCCESS_HTML_RESPONSE.to_string(),
+            wrap_html: false,
+        }
+    }
+}
+
+impl SuccessPageRenderer for DefaultSuccessPageRenderer {
+    fn render(&self, _claims: Option<&IdTokenClaims>) -> SuccessPage {
+        let (headers, body) = if self.wrap_html {
+            (
+                vec![(
+                    "content-type".to_string(),
+                    "text/html; charset=utf-8".to_string(),
+                )],
+                wrap_success_html(&self.message),
+            )
+        } else {
+            (Vec::new(), self.message.clone())
+        };
+
+        SuccessPage {
+            status: 200,
+            headers,
+            body,
+        }
+    }
+}
+
+/// Wraps a bare success message in a minimal HTML document declaring UTF-8 so
+/// localized messages render correctly. Full HTML documents are left untouched.
+fn wrap_success_html(message: &str) -> String {
+    let head = message.trim_start().to_ascii_lowercase();
+    if head.starts_with("<!doctype") || head.starts_with("<html") {
+        return message.to_string();
+    }
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body>{message}</body></html>"
+    )
+}
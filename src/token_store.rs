@@ -0,0 +1,87 @@
+use crate::models::TokenResponse;
+
+/// Persists desktop sessions across app restarts, keyed by account ID (the `sub`
+/// of the user's ID token).
+///
+/// Install one with [`crate::Builder::token_store`]. Sign-ins then save their tokens,
+/// `silent_sign_in` restores the session of the `account_id` it is given, and
+/// `sign_out` deletes it. Sessions hold refresh tokens, so back the store with
+/// secure storage such as the OS keychain rather than plain files.
+pub trait TokenStore: Send + Sync {
+    /// Saves `token`, replacing any session stored for `account_id`.
+    fn save(&self, account_id: &str, token: &TokenResponse) -> crate::Result<()>;
+
+    /// Loads the session of `account_id`, or `None` if none is stored.
+    fn load(&self, account_id: &str) -> crate::Result<Option<TokenResponse>>;
+
+    /// Deletes the session of `account_id`; deleting a missing one succeeds.
+    fn delete(&self, account_id: &str) -> crate::Result<()>;
+}
+
+/// A [`TokenStore`] backed by the OS credential store. Needs the `keyring` feature.
+///
+/// Sessions go to the Keychain on macOS, the Credential Manager on Windows and the
+/// Secret Service (e.g. GNOME Keyring or `KWallet`) on Linux.
+///
+/// The plugin uses it by default when the feature is enabled and no other store is
+/// installed, with the app's identifier as the service name.
+#[cfg(feature = "keyring")]
+pub struct KeyringTokenStore {
+    service: String,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringTokenStore {
+    /// Stores sessions under `service`, one credential per account ID.
+    #[must_use]
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+        }
+    }
+
+    fn entry(&self, account_id: &str) -> crate::Result<keyring::Entry> {
+        keyring::Entry::new(&self.service, account_id).map_err(keyring_error)
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl TokenStore for KeyringTokenStore {
+    fn save(&self, account_id: &str, token: &TokenResponse) -> crate::Result<()> {
+        // Only the session is kept; the per-response extras would just eat into the
+        // small size limit of the Windows Credential Manager
+        let session = TokenResponse {
+            callback_params: None,
+            warnings: Vec::new(),
+            webview_cookies: None,
+            ..token.clone()
+        };
+        let json = serde_json::to_string(&session).map_err(std::io::Error::other)?;
+        self.entry(account_id)?
+            .set_password(&json)
+            .map_err(keyring_error)
+    }
+
+    fn load(&self, account_id: &str) -> crate::Result<Option<TokenResponse>> {
+        match self.entry(account_id)?.get_password() {
+            Ok(json) => Ok(Some(
+                serde_json::from_str(&json).map_err(std::io::Error::other)?,
+            )),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    fn delete(&self, account_id: &str) -> crate::Result<()> {
+        match self.entry(account_id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+#[allow(clippy::needless_pass_by_value)]
+fn keyring_error(e: keyring::Error) -> crate::Error {
+    std::io::Error::other(format!("OS credential store: {e}")).into()
+}